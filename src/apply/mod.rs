@@ -0,0 +1,1078 @@
+use crate::diff::DiffOptions;
+use crate::merge::MergeOptions;
+use crate::patch::Hunk;
+use crate::patch::Line;
+use crate::patch::Patch;
+use crate::range::DiffRange;
+use crate::utils::LineEnding;
+use crate::utils::LineIter;
+use crate::utils::Text;
+use crate::utils::split_line_ending;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::cmp;
+use core::fmt;
+use core::iter;
+use core::ops;
+
+#[cfg(test)]
+mod tests;
+
+/// An error returned when [`apply`]ing a `Patch` fails
+///
+/// [`apply`]: fn.apply.html
+#[derive(Debug)]
+pub struct ApplyError {
+    hunk: usize,
+    kind: ApplyErrorKind,
+}
+
+impl ApplyError {
+    fn new(hunk: usize, kind: ApplyErrorKind) -> Self {
+        Self { hunk, kind }
+    }
+
+    /// Returns the index (starting at `1`) of the hunk which failed to apply
+    pub fn hunk(&self) -> usize {
+        self.hunk
+    }
+
+    /// Returns `true` if the hunk failed to apply because it appears to already be applied
+    ///
+    /// This is detected by checking whether the reverse of the hunk applies cleanly, similar to
+    /// GNU patch's "Reversed (or previously applied) patch detected!" check.
+    pub fn is_reversed(&self) -> bool {
+        self.kind == ApplyErrorKind::Reversed
+    }
+}
+
+impl fmt::Display for ApplyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            ApplyErrorKind::NoMatch => write!(f, "error applying hunk #{}", self.hunk),
+            ApplyErrorKind::Reversed => write!(
+                f,
+                "error applying hunk #{}: reversed (or previously applied) hunk detected",
+                self.hunk
+            ),
+        }
+    }
+}
+
+impl core::error::Error for ApplyError {}
+
+/// The reason a hunk failed to apply
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ApplyErrorKind {
+    /// No position in the base image matches the hunk
+    NoMatch,
+    /// The hunk doesn't match but its reverse does
+    Reversed,
+}
+
+/// A collection of options for modifying the way a patch is applied
+///
+/// # Examples
+///
+/// ```
+/// use diffy::ApplyOptions;
+/// use diffy::Patch;
+///
+/// let patch = Patch::from_str(
+///     "\
+/// --- a/ideals
+/// +++ b/ideals
+/// @@ -1,2 +1,2 @@
+///  First Ideal
+/// -Second ideal
+/// +Second Ideal
+/// ",
+/// )
+/// .unwrap();
+///
+/// let patched = "First Ideal\nSecond Ideal\n";
+///
+/// // Applying the patch a second time fails since it is already applied
+/// let err = diffy::apply(patched, &patch).unwrap_err();
+/// assert!(err.is_reversed());
+///
+/// // ...unless hunks which are already applied are skipped
+/// let mut options = ApplyOptions::new();
+/// options.set_skip_applied(true);
+/// assert_eq!(options.apply(patched, &patch).unwrap(), patched);
+/// ```
+#[derive(Debug)]
+pub struct ApplyOptions {
+    skip_applied: bool,
+    ignore_cr_at_eol: bool,
+    unidiff_zero: bool,
+}
+
+impl ApplyOptions {
+    /// Constructs a new `ApplyOptions` with default settings
+    ///
+    /// ## Defaults
+    /// * skip_applied = false
+    /// * ignore_cr_at_eol = false
+    /// * unidiff_zero = false
+    pub fn new() -> Self {
+        Self {
+            skip_applied: false,
+            ignore_cr_at_eol: false,
+            unidiff_zero: false,
+        }
+    }
+
+    /// Skip hunks which appear to already be applied instead of failing
+    ///
+    /// A hunk is considered to already be applied if it fails to apply but its reverse applies
+    /// cleanly. Enabling this makes re-applying a patch idempotent, similar to GNU patch's
+    /// `--forward` option.
+    pub fn set_skip_applied(&mut self, skip_applied: bool) -> &mut Self {
+        self.skip_applied = skip_applied;
+        self
+    }
+
+    /// Treat lines ending in `\r\n` and `\n` as equal when matching hunks against the base image
+    ///
+    /// Lines added by the patch are written using the line ending style of the base image, so
+    /// that a patch created from files with `\n` line endings can be applied to a checkout with
+    /// `\r\n` line endings (and vice versa). This is similar to git's `--ignore-cr-at-eol`.
+    ///
+    /// ```
+    /// use diffy::ApplyOptions;
+    /// use diffy::create_patch;
+    ///
+    /// let patch = create_patch("alpha\nbeta\n", "alpha\nBETA\n");
+    ///
+    /// let mut options = ApplyOptions::new();
+    /// options.set_ignore_cr_at_eol(true);
+    ///
+    /// assert_eq!(
+    ///     options.apply("alpha\r\nbeta\r\n", &patch).unwrap(),
+    ///     "alpha\r\nBETA\r\n",
+    /// );
+    /// ```
+    pub fn set_ignore_cr_at_eol(&mut self, ignore_cr_at_eol: bool) -> &mut Self {
+        self.ignore_cr_at_eol = ignore_cr_at_eol;
+        self
+    }
+
+    /// Apply hunks without context lines at the line numbers in their headers
+    ///
+    /// Hunks are normally located by searching the base image for their context and deleted
+    /// lines, starting at the position given by their header. A hunk without context lines, like
+    /// those created with [`DiffOptions::set_context_len(0)`](crate::DiffOptions::set_context_len),
+    /// matches at any position containing its deleted lines, or at the first position tried if it
+    /// only inserts lines.
+    ///
+    /// When this is enabled, hunks without context lines are instead applied exactly at the
+    /// position given by their header, adjusted for the lines added and removed by the previous
+    /// hunks, and fail to apply if the lines at that position differ from the deleted lines. This
+    /// is similar to git's `--unidiff-zero`.
+    ///
+    /// ```
+    /// use diffy::ApplyOptions;
+    /// use diffy::DiffOptions;
+    ///
+    /// let original = "x\na\nx\nb\n";
+    /// let patch = DiffOptions::new()
+    ///     .set_context_len(0)
+    ///     .create_patch(original, "x\na\nb\n");
+    ///
+    /// // The deleted line is searched for, and the first `x` matches
+    /// assert_eq!(diffy::apply(original, &patch).unwrap(), "a\nx\nb\n");
+    ///
+    /// let mut options = ApplyOptions::new();
+    /// options.set_unidiff_zero(true);
+    /// assert_eq!(options.apply(original, &patch).unwrap(), "x\na\nb\n");
+    /// ```
+    pub fn set_unidiff_zero(&mut self, unidiff_zero: bool) -> &mut Self {
+        self.unidiff_zero = unidiff_zero;
+        self
+    }
+
+    /// Apply a `Patch` to a base image based on the configured options
+    pub fn apply(&self, base_image: &str, patch: &Patch<'_, str>) -> Result<String, ApplyError> {
+        let image = self.apply_image(base_image, patch)?;
+
+        match self.line_ending(base_image) {
+            Some(line_ending) => {
+                let mut output = String::with_capacity(base_image.len());
+                for line in image {
+                    match line {
+                        ImageLine::Unpatched(line) => output.push_str(line),
+                        ImageLine::Patched(line) => line_ending.push_line(&mut output, line),
+                    }
+                }
+                Ok(output)
+            }
+            None => Ok(image.into_iter().map(ImageLine::into_inner).collect()),
+        }
+    }
+
+    /// Apply a non-utf8 `Patch` to a base image based on the configured options
+    pub fn apply_bytes(
+        &self,
+        base_image: &[u8],
+        patch: &Patch<'_, [u8]>,
+    ) -> Result<Vec<u8>, ApplyError> {
+        let image = self.apply_image(base_image, patch)?;
+
+        match self.line_ending(base_image) {
+            Some(line_ending) => {
+                let mut output = Vec::with_capacity(base_image.len());
+                for line in image {
+                    match line {
+                        ImageLine::Unpatched(line) => output.extend_from_slice(line),
+                        ImageLine::Patched(line) => line_ending.push_line_bytes(&mut output, line),
+                    }
+                }
+                Ok(output)
+            }
+            None => Ok(image
+                .into_iter()
+                .flat_map(ImageLine::into_inner)
+                .copied()
+                .collect()),
+        }
+    }
+
+    /// Check whether a `Patch` applies to a base image based on the configured options
+    ///
+    /// This reports the same errors as [`apply`](Self::apply) without building the patched
+    /// image.
+    pub fn check(&self, base_image: &str, patch: &Patch<'_, str>) -> Result<(), ApplyError> {
+        self.patch_image(&mut Claims::new(base_image), patch)
+    }
+
+    /// Check whether a non-utf8 `Patch` applies to a base image based on the configured options
+    ///
+    /// This reports the same errors as [`apply_bytes`](Self::apply_bytes) without building the
+    /// patched image.
+    pub fn check_bytes(
+        &self,
+        base_image: &[u8],
+        patch: &Patch<'_, [u8]>,
+    ) -> Result<(), ApplyError> {
+        self.patch_image(&mut Claims::new(base_image), patch)
+    }
+
+    // The line ending style patched lines are converted to, if any
+    fn line_ending<T: Text + ?Sized>(&self, base_image: &T) -> Option<LineEnding> {
+        self.ignore_cr_at_eol
+            .then(|| LineEnding::detect(base_image))
+    }
+
+    fn apply_image<'a, T: Text + ?Sized>(
+        &self,
+        base_image: &'a T,
+        patch: &'a Patch<'a, T>,
+    ) -> Result<Vec<ImageLine<'a, T>>, ApplyError> {
+        let mut image: Vec<_> = LineIter::new(base_image)
+            .map(ImageLine::Unpatched)
+            .collect();
+        self.patch_image(&mut image, patch)?;
+
+        Ok(image)
+    }
+
+    fn patch_image<'a, T: Text + ?Sized + 'a, I: Image<'a, T>>(
+        &self,
+        image: &mut I,
+        patch: &'a Patch<'a, T>,
+    ) -> Result<(), ApplyError> {
+        // The number of lines the image has more than the base image before the next hunk
+        let mut offset = 0;
+        for (i, hunk) in patch.hunks().iter().enumerate() {
+            let result = if self.unidiff_zero && !has_context(hunk) {
+                match old_position(hunk).checked_add_signed(offset) {
+                    Some(pos) => apply_hunk_at(image, hunk, pos, self.ignore_cr_at_eol),
+                    None => Err(ApplyErrorKind::NoMatch),
+                }
+            } else {
+                apply_hunk(image, hunk, self.ignore_cr_at_eol)
+            };
+            let delta = hunk.new_range().len() as isize - hunk.old_range().len() as isize;
+            match result {
+                Ok(pos) => offset = pos as isize - old_position(hunk) as isize + delta,
+                // The hunk's postimage is already in the image, in place of its preimage
+                Err(ApplyErrorKind::Reversed) if self.skip_applied => offset += delta,
+                Err(kind) => return Err(ApplyError::new(i + 1, kind)),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for ApplyOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug)]
+enum ImageLine<'a, T: ?Sized> {
+    Unpatched(&'a T),
+    Patched(&'a T),
+}
+
+impl<'a, T: ?Sized> ImageLine<'a, T> {
+    fn inner(&self) -> &'a T {
+        match self {
+            ImageLine::Unpatched(inner) | ImageLine::Patched(inner) => inner,
+        }
+    }
+
+    fn into_inner(self) -> &'a T {
+        self.inner()
+    }
+
+    fn is_patched(&self) -> bool {
+        match self {
+            ImageLine::Unpatched(_) => false,
+            ImageLine::Patched(_) => true,
+        }
+    }
+}
+
+impl<T: ?Sized> Copy for ImageLine<'_, T> {}
+
+impl<T: ?Sized> Clone for ImageLine<'_, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+/// An image which hunks are located in and applied to
+trait Image<'a, T: ?Sized + 'a> {
+    /// The number of lines in the image
+    fn len(&self) -> usize;
+
+    /// The lines of the image starting at `pos`, or `None` for lines which have been patched
+    fn lines_from(&self, pos: usize) -> impl Iterator<Item = Option<&'a T>> + '_;
+
+    /// Replaces the preimage of `lines` at `pos` with their postimage
+    fn patch(&mut self, pos: usize, lines: &[Line<'a, T>]);
+}
+
+impl<'a, T: ?Sized> Image<'a, T> for Vec<ImageLine<'a, T>> {
+    fn len(&self) -> usize {
+        self.as_slice().len()
+    }
+
+    fn lines_from(&self, pos: usize) -> impl Iterator<Item = Option<&'a T>> + '_ {
+        self.get(pos..)
+            .unwrap_or_default()
+            .iter()
+            .map(|line| match line {
+                ImageLine::Unpatched(line) => Some(*line),
+                ImageLine::Patched(_) => None,
+            })
+    }
+
+    fn patch(&mut self, pos: usize, lines: &[Line<'a, T>]) {
+        self.splice(
+            pos..pos + pre_image_line_count(lines),
+            post_image(lines).map(ImageLine::Patched),
+        );
+    }
+}
+
+/// A base image with hunks applied to it, which only records the ranges of lines the hunks
+/// replaced instead of building the patched image
+struct Claims<'a, T: ?Sized> {
+    base: Vec<&'a T>,
+    /// The ranges of lines replaced in the base image, in order
+    claims: Vec<Claim>,
+    /// The number of lines of the patched image
+    len: usize,
+}
+
+/// A range of lines of the base image replaced by a hunk
+struct Claim {
+    range: ops::Range<usize>,
+    /// The number of lines the range was replaced with
+    len: usize,
+    /// The position of the replacement lines in the patched image
+    start: usize,
+}
+
+/// A position in a [`Claims`] image
+struct Cursor {
+    /// The index of the next claim after the position
+    claim: usize,
+    /// The number of patched lines left before `base`
+    patched: usize,
+    /// The next unpatched line of the base image
+    base: usize,
+}
+
+impl<'a, T: Text + ?Sized> Claims<'a, T> {
+    fn new(base_image: &'a T) -> Self {
+        let base: Vec<_> = LineIter::new(base_image).collect();
+        Self {
+            len: base.len(),
+            base,
+            claims: Vec::new(),
+        }
+    }
+}
+
+impl<'a, T: ?Sized> Claims<'a, T> {
+    fn seek(&self, pos: usize) -> Cursor {
+        // The first claim which ends after `pos`
+        let claim = self
+            .claims
+            .partition_point(|claim| claim.start + claim.len <= pos);
+        match self.claims.get(claim) {
+            Some(Claim { range, len, start }) if *start <= pos => Cursor {
+                claim: claim + 1,
+                patched: start + len - pos,
+                base: range.end,
+            },
+            // The lines between claims are the same in the base image and the patched image
+            Some(Claim { range, start, .. }) => Cursor {
+                claim,
+                patched: 0,
+                base: range.start - (start - pos),
+            },
+            None => Cursor {
+                claim,
+                patched: 0,
+                base: self.base.len() - (self.len - pos),
+            },
+        }
+    }
+
+    // Moves `cursor` past the next line of the image, returning it
+    fn next_line(&self, cursor: &mut Cursor) -> Option<Option<&'a T>> {
+        loop {
+            if cursor.patched > 0 {
+                cursor.patched -= 1;
+                return Some(None);
+            }
+            match self.claims.get(cursor.claim) {
+                Some(claim) if claim.range.start == cursor.base => {
+                    cursor.claim += 1;
+                    cursor.patched = claim.len;
+                    cursor.base = claim.range.end;
+                }
+                _ => break,
+            }
+        }
+
+        let line = self.base.get(cursor.base)?;
+        cursor.base += 1;
+        Some(Some(*line))
+    }
+}
+
+impl<'a, T: ?Sized> Image<'a, T> for Claims<'a, T> {
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn lines_from(&self, pos: usize) -> impl Iterator<Item = Option<&'a T>> + '_ {
+        let mut cursor = self.seek(pos);
+        iter::from_fn(move || self.next_line(&mut cursor))
+    }
+
+    fn patch(&mut self, pos: usize, lines: &[Line<'a, T>]) {
+        // The preimage only covers unpatched lines, but can span claims which deleted lines
+        let mut cursor = self.seek(pos);
+        let (first, start) = (cursor.claim, cursor.base);
+        for _ in 0..pre_image_line_count(lines) {
+            self.next_line(&mut cursor);
+        }
+
+        let claim = Claim {
+            range: start..cursor.base,
+            len: post_image(lines).count(),
+            start: 0,
+        };
+        self.claims.splice(first..cursor.claim, iter::once(claim));
+
+        // Update the positions of the new claim and the ones after it, from the end of the
+        // previous claim in the patched image and the base image
+        let mut end = match first.checked_sub(1) {
+            Some(prev) => {
+                let prev = &self.claims[prev];
+                (prev.start + prev.len, prev.range.end)
+            }
+            None => (0, 0),
+        };
+        for claim in &mut self.claims[first..] {
+            claim.start = end.0 + (claim.range.start - end.1);
+            end = (claim.start + claim.len, claim.range.end);
+        }
+        self.len = end.0 + (self.base.len() - end.1);
+    }
+}
+
+/// Apply a `Patch` to a base image
+///
+/// ```
+/// use diffy::Patch;
+/// use diffy::apply;
+///
+/// let s = "\
+/// --- a/ideals
+/// +++ b/ideals
+/// @@ -1,4 +1,6 @@
+///  First:
+///      Life before death,
+///      strength before weakness,
+///      journey before destination.
+/// +Second:
+/// +    I will protect those who cannot protect themselves.
+/// ";
+///
+/// let patch = Patch::from_str(s).unwrap();
+///
+/// let base_image = "\
+/// First:
+///     Life before death,
+///     strength before weakness,
+///     journey before destination.
+/// ";
+///
+/// let expected = "\
+/// First:
+///     Life before death,
+///     strength before weakness,
+///     journey before destination.
+/// Second:
+///     I will protect those who cannot protect themselves.
+/// ";
+///
+/// assert_eq!(apply(base_image, &patch).unwrap(), expected);
+/// ```
+pub fn apply(base_image: &str, patch: &Patch<'_, str>) -> Result<String, ApplyError> {
+    ApplyOptions::default().apply(base_image, patch)
+}
+
+/// Apply a non-utf8 `Patch` to a base image
+///
+/// # Examples
+///
+/// ```
+/// use diffy::Patch;
+/// use diffy::apply_bytes;
+///
+/// let patch = Patch::from_bytes(
+///     b"\
+/// --- a/ideals
+/// +++ b/ideals
+/// @@ -1 +1,2 @@
+///  First Ideal
+/// +Second Ideal
+/// ",
+/// )
+/// .unwrap();
+///
+/// let applied = apply_bytes(b"First Ideal\n", &patch).unwrap();
+///
+/// assert_eq!(
+///     applied,
+///     b"\
+/// First Ideal
+/// Second Ideal
+/// ",
+/// );
+/// ```
+pub fn apply_bytes(base_image: &[u8], patch: &Patch<'_, [u8]>) -> Result<Vec<u8>, ApplyError> {
+    ApplyOptions::default().apply_bytes(base_image, patch)
+}
+
+/// Check whether a `Patch` applies to a base image
+///
+/// This reports the same errors as [`apply`] without building the patched image, which makes it
+/// cheaper when only the outcome is needed.
+///
+/// # Examples
+///
+/// ```
+/// use diffy::check;
+/// use diffy::create_patch;
+///
+/// let patch = create_patch("alpha\nbeta\n", "alpha\nBETA\n");
+///
+/// assert!(check("alpha\nbeta\n", &patch).is_ok());
+///
+/// let err = check("alpha\nBETA\n", &patch).unwrap_err();
+/// assert_eq!(err.hunk(), 1);
+/// assert!(err.is_reversed());
+/// ```
+pub fn check(base_image: &str, patch: &Patch<'_, str>) -> Result<(), ApplyError> {
+    ApplyOptions::default().check(base_image, patch)
+}
+
+/// Check whether a non-utf8 `Patch` applies to a base image
+///
+/// This reports the same errors as [`apply_bytes`] without building the patched image.
+pub fn check_bytes(base_image: &[u8], patch: &Patch<'_, [u8]>) -> Result<(), ApplyError> {
+    ApplyOptions::default().check_bytes(base_image, patch)
+}
+
+/// Apply a `Patch` to a base image, falling back to a three-way merge for hunks which fail to
+/// apply cleanly
+///
+/// This is similar to `git apply -3`. Hunks which apply cleanly are applied as usual. For a hunk
+/// which can't be applied, the hunk's preimage (its context and deleted lines) is lined up with
+/// the lines around the hunk's expected location using a diff, and used in place of them to
+/// reconstruct a common ancestor. The hunk's postimage is then merged into the base image using
+/// [`merge`].
+///
+/// Returns `Ok(String)` if every hunk applied cleanly or merged without conflict.
+/// Returns `Err(String)` if there were conflicts, with the conflicting regions marked with
+/// conflict markers.
+///
+/// See [`MergeOptions::apply_three_way`] to control how conflicts are rendered.
+///
+/// ```
+/// use diffy::Patch;
+/// use diffy::apply_three_way;
+///
+/// let s = "\
+/// --- a/ideals
+/// +++ b/ideals
+/// @@ -1,3 +1,3 @@
+///  First:
+/// -    Life before death,
+/// +    Life before death.
+///      strength before weakness,
+/// ";
+///
+/// let patch = Patch::from_str(s).unwrap();
+///
+/// let base_image = "\
+/// First:
+///     Life before Death,
+///     strength before weakness,
+/// ";
+///
+/// let expected = "\
+/// First:
+/// <<<<<<< ours
+///     Life before Death,
+/// ||||||| original
+///     Life before death,
+/// =======
+///     Life before death.
+/// >>>>>>> theirs
+///     strength before weakness,
+/// ";
+///
+/// assert_eq!(apply_three_way(base_image, &patch).unwrap_err(), expected);
+/// ```
+///
+/// [`merge`]: crate::merge
+pub fn apply_three_way(base_image: &str, patch: &Patch<'_, str>) -> Result<String, String> {
+    MergeOptions::default().apply_three_way(base_image, patch)
+}
+
+/// Apply a non-utf8 `Patch` to a base image, falling back to a three-way merge for hunks which
+/// fail to apply cleanly
+///
+/// See [`apply_three_way`] for details.
+pub fn apply_three_way_bytes(
+    base_image: &[u8],
+    patch: &Patch<'_, [u8]>,
+) -> Result<Vec<u8>, Vec<u8>> {
+    MergeOptions::default().apply_three_way_bytes(base_image, patch)
+}
+
+/// Reconstructs the ancestor and "theirs" images used to perform a three-way apply of `patch` on
+/// top of `base_image`, which itself acts as "ours".
+///
+/// Each hunk is located in the base image the same way [`apply`] does with `options`. Hunks which
+/// can't be located are instead lined up with the base image around their expected position,
+/// shifted by the offset of the previously located hunk, and have the lines they cover replaced
+/// by the hunk's preimage in the ancestor.
+pub(crate) fn three_way_images<'a, T: Text + ?Sized>(
+    base_image: &'a T,
+    patch: &'a Patch<'a, T>,
+    options: &ApplyOptions,
+) -> (Vec<&'a T>, Vec<&'a T>) {
+    let ignore_cr_at_eol = options.ignore_cr_at_eol;
+    let mut image: Vec<_> = LineIter::new(base_image)
+        .map(ImageLine::Unpatched)
+        .collect();
+
+    // (position, length) of the region of the base image covered by each hunk
+    let mut placements: Vec<(usize, usize, &Hunk<'a, T>)> = Vec::with_capacity(patch.hunks().len());
+    let mut offset = 0;
+
+    for hunk in patch.hunks() {
+        let expected = old_position(hunk);
+        let hint = cmp::min(expected.saturating_add_signed(offset), image.len());
+        let len = pre_image_line_count(hunk.lines());
+
+        // Placements of hunks which only insert lines don't cover any lines of the image, so
+        // they're checked separately to keep the regions of the hunks from overlapping
+        let fits = |pos: usize| {
+            placements
+                .iter()
+                .all(|&(other, other_len, _)| match (len, other_len) {
+                    (0, _) => pos <= other || other + other_len <= pos,
+                    (_, 0) => other <= pos || pos + len <= other,
+                    _ => true,
+                })
+        };
+        let found = if options.unidiff_zero && !has_context(hunk) {
+            Some(hint).filter(|&pos| {
+                match_fragment(&image, hunk.lines(), pos, ignore_cr_at_eol) && fits(pos)
+            })
+        } else {
+            find_position_where(&image, hunk.lines(), hint, ignore_cr_at_eol, fits)
+        };
+
+        let (pos, len) = match found {
+            Some(pos) => {
+                offset = pos as isize - expected as isize;
+                (pos, len)
+            }
+            None => {
+                // Skip past any lines already claimed by a previous hunk
+                let pos = image[hint..]
+                    .iter()
+                    .position(|line| !line.is_patched())
+                    .map_or(image.len(), |idx| hint + idx);
+                // Keep the region from covering the position of a hunk which only inserts lines
+                let bounds = placements
+                    .iter()
+                    .filter(|&&(_, other_len, _)| other_len == 0)
+                    .fold(0..image.len(), |bounds, &(other, ..)| {
+                        if other <= pos {
+                            cmp::max(bounds.start, other)..bounds.end
+                        } else {
+                            bounds.start..cmp::min(bounds.end, other)
+                        }
+                    });
+                align_pre_image(&image, hunk.lines(), pos, bounds, ignore_cr_at_eol)
+            }
+        };
+
+        for line in &mut image[pos..pos + len] {
+            *line = ImageLine::Patched(line.inner());
+        }
+        placements.push((pos, len, hunk));
+    }
+
+    // Lines inserted where another hunk's region starts go before it
+    placements.sort_by_key(|&(pos, len, _)| (pos, len));
+
+    let mut ancestor = Vec::with_capacity(image.len());
+    let mut theirs = Vec::with_capacity(image.len());
+    let mut idx = 0;
+    for (pos, len, hunk) in placements {
+        for line in &image[idx..pos] {
+            ancestor.push(line.inner());
+            theirs.push(line.inner());
+        }
+        ancestor.extend(pre_image(hunk.lines()));
+        theirs.extend(post_image(hunk.lines()));
+        idx = pos + len;
+    }
+    for line in &image[idx..] {
+        ancestor.push(line.inner());
+        theirs.push(line.inner());
+    }
+
+    (ancestor, theirs)
+}
+
+// Finds the region of unclaimed lines around `pos` in `image` which corresponds to the preimage
+// of a hunk which couldn't be located exactly, returning its position and length. The region is
+// kept within `bounds`.
+//
+// The preimage is diffed against a window of the image around `pos`. If any of their lines match,
+// the whole window is covered so that lines the base image added around the preimage are seen as
+// changes made by "ours" when merging. Otherwise as many lines as the preimage has are covered,
+// starting at `pos`.
+fn align_pre_image<T: Text + ?Sized>(
+    image: &[ImageLine<T>],
+    lines: &[Line<'_, T>],
+    pos: usize,
+    bounds: ops::Range<usize>,
+    ignore_cr_at_eol: bool,
+) -> (usize, usize) {
+    fn key<T: Text + ?Sized>(line: &T, ignore_cr_at_eol: bool) -> (&T, bool) {
+        if ignore_cr_at_eol {
+            split_line_ending(line)
+        } else {
+            (line, false)
+        }
+    }
+
+    let len = pre_image_line_count(lines);
+    let start = pos
+        - image[bounds.start..pos]
+            .iter()
+            .rev()
+            .take(len)
+            .take_while(|line| !line.is_patched())
+            .count();
+    let end = pos
+        + image[pos..bounds.end]
+            .iter()
+            .take(len * 2)
+            .take_while(|line| !line.is_patched())
+            .count();
+
+    let pre_image: Vec<_> = pre_image(lines)
+        .map(|line| key(line, ignore_cr_at_eol))
+        .collect();
+    let window: Vec<_> = image[start..end]
+        .iter()
+        .map(|line| key(line.inner(), ignore_cr_at_eol))
+        .collect();
+
+    let matched = DiffOptions::default()
+        .diff_slice_myers(&pre_image, &window)
+        .iter()
+        .any(|diff| matches!(diff, DiffRange::Equal(..)));
+
+    if matched {
+        (start, end - start)
+    } else {
+        (pos, cmp::min(len, end - pos))
+    }
+}
+
+/// Locates each hunk of `patch` in `base_image` without applying it.
+///
+/// Returns the 0-based position in the base image at which the preimage of each hunk starts.
+/// Each hunk is searched for starting at its expected position, shifted by the offset of the
+/// previous hunk.
+pub(crate) fn locate_hunks<T: Text + ?Sized>(
+    base_image: &T,
+    patch: &Patch<'_, T>,
+) -> Result<Vec<usize>, ApplyError> {
+    let mut image: Vec<_> = LineIter::new(base_image)
+        .map(ImageLine::Unpatched)
+        .collect();
+
+    let mut positions = Vec::with_capacity(patch.hunks().len());
+    let mut offset = 0;
+    for (i, hunk) in patch.hunks().iter().enumerate() {
+        let expected = old_position(hunk);
+        let hint = cmp::min(expected.saturating_add_signed(offset), image.len());
+        let pos = find_position_from(&image, hunk.lines(), hint, false)
+            .ok_or_else(|| ApplyError::new(i + 1, ApplyErrorKind::NoMatch))?;
+        offset = pos as isize - expected as isize;
+
+        for line in &mut image[pos..pos + pre_image_line_count(hunk.lines())] {
+            *line = ImageLine::Patched(line.inner());
+        }
+        positions.push(pos);
+    }
+
+    Ok(positions)
+}
+
+// The 0-based position in the original file at which the hunk's preimage starts
+fn old_position<T: ?Sized>(hunk: &Hunk<'_, T>) -> usize {
+    let range = hunk.old_range();
+    // An empty range refers to the line after which lines are inserted
+    if range.is_empty() {
+        range.start()
+    } else {
+        range.start().saturating_sub(1)
+    }
+}
+
+// Whether any of the lines of `hunk` are context lines
+fn has_context<T: ?Sized>(hunk: &Hunk<'_, T>) -> bool {
+    hunk.lines()
+        .iter()
+        .any(|line| matches!(line, Line::Context(_)))
+}
+
+// Applies `hunk` at `pos` in `image`, returning the position it was applied at
+fn apply_hunk<'a, T: Text + ?Sized + 'a, I: Image<'a, T>>(
+    image: &mut I,
+    hunk: &Hunk<'a, T>,
+    ignore_cr_at_eol: bool,
+) -> Result<usize, ApplyErrorKind> {
+    // Find position
+    let pos = match find_position(image, hunk, ignore_cr_at_eol) {
+        Some(pos) => pos,
+        None if is_applied(image, hunk, ignore_cr_at_eol) => {
+            return Err(ApplyErrorKind::Reversed);
+        }
+        None => return Err(ApplyErrorKind::NoMatch),
+    };
+
+    // update image
+    image.patch(pos, hunk.lines());
+
+    Ok(pos)
+}
+
+// Applies `hunk` at exactly `pos` in `image`, returning `pos`
+fn apply_hunk_at<'a, T: Text + ?Sized + 'a, I: Image<'a, T>>(
+    image: &mut I,
+    hunk: &Hunk<'a, T>,
+    pos: usize,
+    ignore_cr_at_eol: bool,
+) -> Result<usize, ApplyErrorKind> {
+    if !match_fragment(image, hunk.lines(), pos, ignore_cr_at_eol) {
+        let reversed = hunk.reverse();
+        return if post_image(hunk.lines()).next().is_some()
+            && match_fragment(image, reversed.lines(), pos, ignore_cr_at_eol)
+        {
+            Err(ApplyErrorKind::Reversed)
+        } else {
+            Err(ApplyErrorKind::NoMatch)
+        };
+    }
+
+    image.patch(pos, hunk.lines());
+
+    Ok(pos)
+}
+
+// Check whether `hunk` has already been applied to `image` by searching for a place to apply its
+// reverse.
+fn is_applied<'a, T: Text + ?Sized + 'a, I: Image<'a, T>>(
+    image: &I,
+    hunk: &Hunk<'_, T>,
+    ignore_cr_at_eol: bool,
+) -> bool {
+    // The reverse of a hunk without a postimage (e.g. lines deleted without any context) would
+    // trivially match anywhere
+    post_image(hunk.lines()).next().is_some()
+        && find_position(image, &hunk.reverse(), ignore_cr_at_eol).is_some()
+}
+
+// Search in `image` for a palce to apply hunk.
+// This follows the general algorithm (minus fuzzy-matching context lines) described in GNU patch's
+// man page.
+//
+// It might be worth looking into other possible positions to apply the hunk to as described here:
+// https://neil.fraser.name/writing/patch/
+fn find_position<'a, T: Text + ?Sized + 'a, I: Image<'a, T>>(
+    image: &I,
+    hunk: &Hunk<'_, T>,
+    ignore_cr_at_eol: bool,
+) -> Option<usize> {
+    // In order to avoid searching through positions which are out of bounds of the image,
+    // clamp the starting position based on the length of the image
+    let pos = cmp::min(hunk.new_range().start().saturating_sub(1), image.len());
+
+    find_position_from(image, hunk.lines(), pos, ignore_cr_at_eol)
+}
+
+fn find_position_from<'a, T: Text + ?Sized + 'a, I: Image<'a, T>>(
+    image: &I,
+    lines: &[Line<'_, T>],
+    pos: usize,
+    ignore_cr_at_eol: bool,
+) -> Option<usize> {
+    find_position_where(image, lines, pos, ignore_cr_at_eol, |_| true)
+}
+
+// Like `find_position_from`, but skips the positions for which `accept` returns false
+fn find_position_where<'a, T: Text + ?Sized + 'a, I: Image<'a, T>>(
+    image: &I,
+    lines: &[Line<'_, T>],
+    pos: usize,
+    ignore_cr_at_eol: bool,
+    accept: impl Fn(usize) -> bool,
+) -> Option<usize> {
+    // Create an iterator that starts with 'pos' and then interleaves
+    // moving pos backward/foward by one.
+    let backward = (0..pos).rev();
+    let forward = pos + 1..image.len();
+
+    iter::once(pos)
+        .chain(interleave(backward, forward))
+        .find(|&pos| match_fragment(image, lines, pos, ignore_cr_at_eol) && accept(pos))
+}
+
+fn pre_image_line_count<T: ?Sized>(lines: &[Line<'_, T>]) -> usize {
+    pre_image(lines).count()
+}
+
+fn post_image<'a, 'b, T: ?Sized>(lines: &'b [Line<'a, T>]) -> impl Iterator<Item = &'a T> + 'b {
+    lines.iter().filter_map(|line| match line {
+        Line::Context(l) | Line::Insert(l) => Some(*l),
+        Line::Delete(_) => None,
+    })
+}
+
+fn pre_image<'a, 'b, T: ?Sized>(lines: &'b [Line<'a, T>]) -> impl Iterator<Item = &'a T> + 'b {
+    lines.iter().filter_map(|line| match line {
+        Line::Context(l) | Line::Delete(l) => Some(*l),
+        Line::Insert(_) => None,
+    })
+}
+
+fn match_fragment<'a, T: Text + ?Sized + 'a, I: Image<'a, T>>(
+    image: &I,
+    lines: &[Line<'_, T>],
+    pos: usize,
+    ignore_cr_at_eol: bool,
+) -> bool {
+    if pre_image(lines).next().is_none() {
+        return pos <= image.len();
+    }
+
+    // If any of these lines have already been patched then we can't match at this position
+    let mut image = image.lines_from(pos);
+    pre_image(lines).all(|line| match image.next() {
+        Some(Some(image_line)) if ignore_cr_at_eol => {
+            split_line_ending(line) == split_line_ending(image_line)
+        }
+        Some(Some(image_line)) => line == image_line,
+        _ => false,
+    })
+}
+
+#[derive(Debug)]
+struct Interleave<I, J> {
+    a: iter::Fuse<I>,
+    b: iter::Fuse<J>,
+    flag: bool,
+}
+
+fn interleave<I, J>(
+    i: I,
+    j: J,
+) -> Interleave<<I as IntoIterator>::IntoIter, <J as IntoIterator>::IntoIter>
+where
+    I: IntoIterator,
+    J: IntoIterator<Item = I::Item>,
+{
+    Interleave {
+        a: i.into_iter().fuse(),
+        b: j.into_iter().fuse(),
+        flag: false,
+    }
+}
+
+impl<I, J> Iterator for Interleave<I, J>
+where
+    I: Iterator,
+    J: Iterator<Item = I::Item>,
+{
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<I::Item> {
+        self.flag = !self.flag;
+        if self.flag {
+            match self.a.next() {
+                None => self.b.next(),
+                item => item,
+            }
+        } else {
+            match self.b.next() {
+                None => self.a.next(),
+                item => item,
+            }
+        }
+    }
+}
@@ -0,0 +1,648 @@
+use super::*;
+use crate::ConflictStyle;
+use crate::create_patch;
+use alloc::string::ToString;
+
+mod three_way {
+    use super::*;
+
+    #[test]
+    fn clean_patch_is_applied() {
+        let original = "alpha\nbeta\ngamma\n";
+        let modified = "alpha\nBETA\ngamma\n";
+        let patch = create_patch(original, modified);
+
+        assert_eq!(apply_three_way(original, &patch).unwrap(), modified);
+        assert_eq!(
+            apply_three_way_bytes(original.as_bytes(), &create_patch_bytes(original, modified))
+                .unwrap(),
+            modified.as_bytes(),
+        );
+    }
+
+    #[test]
+    fn changed_context_merges_cleanly() {
+        let original = "one\ntwo\nthree\nfour\nfive\nsix\nseven\n";
+        let modified = "one\ntwo\nthree\nfour\nFIVE\nsix\nseven\n";
+        let patch = create_patch(original, modified);
+
+        // The first context line of the hunk was changed in the base image
+        let base_image = "one\nTWO\nthree\nfour\nfive\nsix\nseven\n";
+        assert!(apply(base_image, &patch).is_err());
+
+        let expected = "one\nTWO\nthree\nfour\nFIVE\nsix\nseven\n";
+        assert_eq!(apply_three_way(base_image, &patch).unwrap(), expected);
+        assert_eq!(
+            apply_three_way_bytes(
+                base_image.as_bytes(),
+                &create_patch_bytes(original, modified)
+            )
+            .unwrap(),
+            expected.as_bytes(),
+        );
+    }
+
+    #[test]
+    fn only_failing_hunk_conflicts() {
+        let original = "\
+a
+b
+c
+d
+e
+f
+g
+h
+i
+j
+k
+l
+m
+";
+        let modified = "\
+A
+b
+c
+d
+e
+f
+g
+h
+i
+j
+k
+l
+M
+";
+        let patch = create_patch(original, modified);
+        assert_eq!(patch.hunks().len(), 2);
+
+        let base_image = "\
+a
+b
+c
+d
+e
+f
+g
+h
+i
+j
+k
+l
+mm
+";
+        let expected = "\
+A
+b
+c
+d
+e
+f
+g
+h
+i
+j
+k
+l
+<<<<<<< ours
+mm
+=======
+M
+>>>>>>> theirs
+";
+
+        let mut options = MergeOptions::new();
+        options.set_conflict_style(ConflictStyle::Merge);
+        assert_eq!(
+            options.apply_three_way(base_image, &patch).unwrap_err(),
+            expected
+        );
+    }
+
+    #[test]
+    fn hunk_is_placed_after_shifted_hunks() {
+        let original = "\
+a
+b
+c
+d
+e
+f
+g
+h
+i
+j
+k
+l
+m
+";
+        let modified = "\
+A
+b
+c
+d
+e
+f
+g
+h
+i
+j
+k
+l
+M
+";
+        let patch = create_patch(original, modified);
+
+        // Two lines were added at the start of the file and the last line was changed
+        let base_image = "\
+0
+1
+a
+b
+c
+d
+e
+f
+g
+h
+i
+j
+k
+l
+mm
+";
+        let expected = "\
+0
+1
+A
+b
+c
+d
+e
+f
+g
+h
+i
+j
+k
+l
+<<<<<<< ours
+mm
+||||||| original
+m
+=======
+M
+>>>>>>> theirs
+";
+
+        assert_eq!(apply_three_way(base_image, &patch).unwrap_err(), expected);
+    }
+
+    #[test]
+    fn preimage_is_lined_up_with_base_image() {
+        let original = "d\nc\ne\nd\n";
+        let modified = "d\nc\ne\nC\n";
+        let patch = create_patch(original, modified);
+
+        // A line was inserted in the middle of the hunk's preimage
+        let base_image = "d\nc\nf\ne\nd\n";
+        assert!(apply(base_image, &patch).is_err());
+
+        let expected = "d\nc\nf\ne\nC\n";
+        assert_eq!(
+            crate::merge(original, base_image, modified).unwrap(),
+            expected
+        );
+        assert_eq!(apply_three_way(base_image, &patch).unwrap(), expected);
+    }
+
+    #[test]
+    fn insertion_is_not_covered_by_later_hunk() {
+        let patch = Patch::from_str(
+            "\
+--- a
++++ b
+@@ -1,0 +2,2 @@
++A
++B
+@@ -3,3 +5 @@
+-c
+-b
+-b
++A
+",
+        )
+        .unwrap();
+
+        let base_image = "b\nc\nc\nA\nb\n";
+        assert!(apply(base_image, &patch).is_err());
+        assert_eq!(
+            apply_three_way(base_image, &patch).unwrap_err(),
+            "\
+b
+<<<<<<< ours
+c
+c
+A
+b
+||||||| original
+c
+b
+b
+=======
+A
+B
+A
+>>>>>>> theirs
+"
+        );
+    }
+
+    #[test]
+    fn unidiff_zero() {
+        let patch = DiffOptions::new()
+            .set_context_len(0)
+            .create_patch("x\na\nx\nb\n", "x\na\nb\n");
+
+        let mut options = MergeOptions::new();
+        assert_eq!(
+            options.apply_three_way("x\na\nx\nb\n", &patch).unwrap(),
+            "a\nx\nb\n"
+        );
+        options.set_unidiff_zero(true);
+        assert_eq!(
+            options.apply_three_way("x\na\nx\nb\n", &patch).unwrap(),
+            "x\na\nb\n"
+        );
+
+        // A hunk which doesn't match at its position isn't searched for
+        let patch = DiffOptions::new()
+            .set_context_len(0)
+            .create_patch("a\nx\nb\n", "a\nX\nb\n");
+        let base_image = "x\na\ny\nb\n";
+        assert_eq!(
+            MergeOptions::new()
+                .apply_three_way(base_image, &patch)
+                .unwrap(),
+            "X\na\ny\nb\n"
+        );
+        assert_eq!(
+            options.apply_three_way(base_image, &patch).unwrap_err(),
+            "\
+<<<<<<< ours
+x
+a
+y
+||||||| original
+x
+=======
+X
+>>>>>>> theirs
+b
+"
+        );
+    }
+
+    fn create_patch_bytes<'a>(original: &'a str, modified: &'a str) -> Patch<'a, [u8]> {
+        crate::create_patch_bytes(original.as_bytes(), modified.as_bytes())
+    }
+}
+
+mod reversed {
+    use super::*;
+
+    const ORIGINAL: &str = "\
+a
+b
+c
+d
+e
+f
+g
+h
+i
+j
+k
+l
+m
+";
+    const MODIFIED: &str = "\
+A
+b
+c
+d
+e
+f
+g
+h
+i
+j
+k
+l
+M
+";
+
+    #[test]
+    fn already_applied_patch_is_detected() {
+        let patch = create_patch(ORIGINAL, MODIFIED);
+
+        let err = apply(MODIFIED, &patch).unwrap_err();
+        assert!(err.is_reversed());
+        assert_eq!(err.hunk(), 1);
+        assert_eq!(
+            err.to_string(),
+            "error applying hunk #1: reversed (or previously applied) hunk detected"
+        );
+    }
+
+    #[test]
+    fn mismatch_is_not_reported_as_reversed() {
+        let patch = create_patch(ORIGINAL, MODIFIED);
+
+        let err = apply("something\nelse\nentirely\n", &patch).unwrap_err();
+        assert!(!err.is_reversed());
+        assert_eq!(err.hunk(), 1);
+        assert_eq!(err.to_string(), "error applying hunk #1");
+    }
+
+    #[test]
+    fn pure_deletion_is_not_reported_as_reversed() {
+        let mut options = crate::DiffOptions::new();
+        options.set_context_len(0);
+        let patch = options.create_patch("a\nb\nc\n", "a\nc\n");
+
+        let err = apply("x\ny\nz\n", &patch).unwrap_err();
+        assert!(!err.is_reversed());
+    }
+
+    #[test]
+    fn skip_applied_is_idempotent() {
+        let patch = create_patch(ORIGINAL, MODIFIED);
+        let mut options = ApplyOptions::new();
+        options.set_skip_applied(true);
+
+        let patched = options.apply(ORIGINAL, &patch).unwrap();
+        assert_eq!(patched, MODIFIED);
+        assert_eq!(options.apply(&patched, &patch).unwrap(), MODIFIED);
+
+        let patch = crate::create_patch_bytes(ORIGINAL.as_bytes(), MODIFIED.as_bytes());
+        assert_eq!(
+            options.apply_bytes(MODIFIED.as_bytes(), &patch).unwrap(),
+            MODIFIED.as_bytes()
+        );
+    }
+
+    #[test]
+    fn skip_applied_applies_remaining_hunks() {
+        let patch = create_patch(ORIGINAL, MODIFIED);
+        let mut options = ApplyOptions::new();
+        options.set_skip_applied(true);
+
+        // Only the first hunk was previously applied
+        let partially_applied = MODIFIED.replace("M\n", "m\n");
+        assert!(apply(&partially_applied, &patch).unwrap_err().is_reversed());
+        assert_eq!(options.apply(&partially_applied, &patch).unwrap(), MODIFIED);
+    }
+
+    #[test]
+    fn skip_applied_offsets_unidiff_zero_hunks() {
+        let patch = crate::DiffOptions::new()
+            .set_context_len(0)
+            .create_patch("a\nx\nb\nx\n", "A\nA2\nx\nb\n");
+        assert_eq!(patch.hunks().len(), 2);
+        let mut options = ApplyOptions::new();
+        options.set_skip_applied(true).set_unidiff_zero(true);
+
+        // The first hunk was previously applied and added a line, which moves the second one
+        let partially_applied = "A\nA2\nx\nb\nx\n";
+        assert_eq!(
+            options.apply(partially_applied, &patch).unwrap(),
+            "A\nA2\nx\nb\n"
+        );
+        assert!(options.check(partially_applied, &patch).is_ok());
+    }
+}
+
+mod line_endings {
+    use super::*;
+
+    fn options() -> ApplyOptions {
+        let mut options = ApplyOptions::new();
+        options.set_ignore_cr_at_eol(true);
+        options
+    }
+
+    #[test]
+    fn lf_patch_applies_to_crlf_file() {
+        let patch = create_patch("a\nb\nc\n", "a\nB\nb2\nc\n");
+        let base_image = "a\r\nb\r\nc\r\n";
+
+        assert!(apply(base_image, &patch).is_err());
+        assert_eq!(
+            options().apply(base_image, &patch).unwrap(),
+            "a\r\nB\r\nb2\r\nc\r\n"
+        );
+
+        let patch = crate::create_patch_bytes(b"a\nb\nc\n", b"a\nB\nb2\nc\n");
+        assert_eq!(
+            options()
+                .apply_bytes(base_image.as_bytes(), &patch)
+                .unwrap(),
+            b"a\r\nB\r\nb2\r\nc\r\n"
+        );
+    }
+
+    #[test]
+    fn crlf_patch_applies_to_lf_file() {
+        let patch = create_patch("a\r\nb\r\nc\r\n", "a\r\nB\r\nc\r\n");
+
+        assert_eq!(options().apply("a\nb\nc\n", &patch).unwrap(), "a\nB\nc\n");
+    }
+
+    #[test]
+    fn missing_newline_at_eof_is_preserved() {
+        let patch = create_patch("a\nb\nc", "a\nb\nC");
+
+        assert_eq!(
+            options().apply("a\r\nb\r\nc", &patch).unwrap(),
+            "a\r\nb\r\nC"
+        );
+        assert!(options().apply("a\r\nb\r\nc\r\n", &patch).is_err());
+    }
+
+    #[test]
+    fn three_way_uses_ours_line_endings() {
+        let patch = create_patch("a\nb\nc\n", "a\nB\nc\n");
+
+        let mut options = MergeOptions::new();
+        options
+            .set_ignore_cr_at_eol(true)
+            .set_conflict_style(ConflictStyle::Merge);
+        assert_eq!(
+            options.apply_three_way("a\r\nb\r\nc\r\n", &patch).unwrap(),
+            "a\r\nB\r\nc\r\n"
+        );
+        assert_eq!(
+            options
+                .apply_three_way("a\r\nbb\r\nc\r\n", &patch)
+                .unwrap_err(),
+            "a\r\n<<<<<<< ours\r\nbb\r\n=======\r\nB\r\n>>>>>>> theirs\r\nc\r\n"
+        );
+    }
+}
+
+mod check {
+    use super::*;
+
+    #[test]
+    fn matches_apply() {
+        let patch = create_patch("a\nb\nc\n", "a\nB\nc\n");
+
+        for base_image in ["a\nb\nc\n", "a\nB\nc\n", "x\ny\nz\n", ""] {
+            assert_eq!(
+                check(base_image, &patch).map_err(|e| e.to_string()),
+                apply(base_image, &patch)
+                    .map(|_| ())
+                    .map_err(|e| e.to_string()),
+                "{base_image:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn matches_apply_after_earlier_hunks() {
+        // The second hunk spans the line deleted by the first one
+        let spanning = Patch::from_str(
+            "\
+--- a
++++ b
+@@ -2 +1,0 @@
+-b
+@@ -3,2 +2,2 @@
+-a
++X
+ c
+",
+        )
+        .unwrap();
+        // The second hunk needs a line added by the first one
+        let overlapping = Patch::from_str(
+            "\
+--- a
++++ b
+@@ -1,2 +1,2 @@
+-a
++x
+ b
+@@ -3,2 +3,2 @@
+ x
+-b
++y
+",
+        )
+        .unwrap();
+
+        for patch in [&spanning, &overlapping] {
+            for base_image in ["a\nb\nc\nd\n", "a\nc\nd\n", "x\nb\n", "a\nb\n"] {
+                assert_eq!(
+                    check(base_image, patch).map_err(|e| e.to_string()),
+                    apply(base_image, patch)
+                        .map(|_| ())
+                        .map_err(|e| e.to_string()),
+                    "{base_image:?}"
+                );
+            }
+        }
+        assert_eq!(apply("a\nb\nc\nd\n", &spanning).unwrap(), "X\nc\nd\n");
+        assert_eq!(check("a\nb\n", &overlapping).unwrap_err().hunk(), 2);
+    }
+
+    #[test]
+    fn uses_options() {
+        let patch = crate::create_patch_bytes(b"a\nb\nc\n", b"a\nB\nc\n");
+        assert!(check_bytes(b"a\r\nb\r\nc\r\n", &patch).is_err());
+        assert!(check_bytes(b"a\nB\nc\n", &patch).unwrap_err().is_reversed());
+
+        let mut options = ApplyOptions::new();
+        options.set_ignore_cr_at_eol(true).set_skip_applied(true);
+        assert!(options.check_bytes(b"a\r\nb\r\nc\r\n", &patch).is_ok());
+        assert!(options.check_bytes(b"a\nB\nc\n", &patch).is_ok());
+    }
+}
+
+mod unidiff_zero {
+    use super::*;
+    use crate::DiffOptions;
+
+    fn options() -> ApplyOptions {
+        let mut options = ApplyOptions::new();
+        options.set_unidiff_zero(true);
+        options
+    }
+
+    #[test]
+    fn insert_at_start() {
+        let patch = Patch::from_str("--- a\n+++ b\n@@ -0,0 +1 @@\n+x\n").unwrap();
+
+        assert_eq!(options().apply("a\nb\n", &patch).unwrap(), "x\na\nb\n");
+        assert_eq!(options().apply("", &patch).unwrap(), "x\n");
+    }
+
+    #[test]
+    fn insert_at_end() {
+        let patch = Patch::from_str("--- a\n+++ b\n@@ -2,0 +3,2 @@\n+x\n+y\n").unwrap();
+
+        assert_eq!(options().apply("a\nb\n", &patch).unwrap(), "a\nb\nx\ny\n");
+        assert!(options().apply("a\n", &patch).is_err());
+    }
+
+    #[test]
+    fn trusts_line_numbers() {
+        let patch = Patch::from_str("--- a\n+++ b\n@@ -5 +4,0 @@\n-x\n").unwrap();
+        let base_image = "1\n2\nx\n4\nx\n";
+
+        assert_eq!(apply(base_image, &patch).unwrap(), "1\n2\n4\nx\n");
+        assert_eq!(options().apply(base_image, &patch).unwrap(), "1\n2\nx\n4\n");
+    }
+
+    #[test]
+    fn validates_deleted_lines() {
+        let patch = Patch::from_str("--- a\n+++ b\n@@ -2 +2 @@\n-b\n+B\n").unwrap();
+
+        assert_eq!(options().apply("a\nb\nc\n", &patch).unwrap(), "a\nB\nc\n");
+        assert!(options().apply("b\na\nc\n", &patch).is_err());
+        assert!(
+            options()
+                .apply("a\nB\nc\n", &patch)
+                .unwrap_err()
+                .is_reversed()
+        );
+
+        let mut options = options();
+        options.set_skip_applied(true);
+        assert_eq!(options.apply("a\nB\nc\n", &patch).unwrap(), "a\nB\nc\n");
+    }
+
+    #[test]
+    fn follows_earlier_hunks() {
+        let original = "a\nx\nb\nx\nc\nx\nd\n";
+        let modified = "a\nn\nn\nb\nx\nc\nd\ne\n";
+        let patch = DiffOptions::new()
+            .set_context_len(0)
+            .create_patch(original, modified);
+        assert_eq!(patch.hunks().len(), 3);
+
+        assert_eq!(options().apply(original, &patch).unwrap(), modified);
+    }
+
+    #[test]
+    fn hunks_with_context_are_searched_for() {
+        let patch = create_patch("a\nb\nc\n", "a\nB\nc\n");
+
+        assert_eq!(
+            options().apply("x\na\nb\nc\n", &patch).unwrap(),
+            "x\na\nB\nc\n"
+        );
+    }
+}
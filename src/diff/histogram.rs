@@ -0,0 +1,155 @@
+//! Histogram diff
+//!
+//! An extension of patience diff, based on the algorithm used by git. Instead of only anchoring
+//! on unique lines, the longest common region containing the line which appears the fewest
+//! times in `old` is matched up, and the regions before and after it are diffed recursively.
+//! Regions without any common lines, or where every common line appears too often, are diffed
+//! with Myers' algorithm.
+
+use super::myers;
+use crate::range::DiffRange;
+use crate::range::Range;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::hash::Hash;
+use hashbrown::HashMap;
+
+/// Lines appearing more often than this in `old` aren't used to find common regions
+const MAX_CHAIN_LEN: usize = 64;
+
+/// Work left to do, popped off a stack so that the depth of the recursion doesn't grow with the
+/// number of regions
+enum Step<'a, 'b, T> {
+    Diff(Range<'a, [T]>, Range<'b, [T]>),
+    Push(DiffRange<'a, 'b, [T]>),
+}
+
+pub fn diff<'a, 'b, T: Eq + Hash>(old: &'a [T], new: &'b [T]) -> Vec<DiffRange<'a, 'b, [T]>> {
+    let mut solution = Vec::new();
+    let mut stack = vec![Step::Diff(Range::new(old, ..), Range::new(new, ..))];
+    while let Some(step) = stack.pop() {
+        match step {
+            Step::Diff(old, new) => conquer(old, new, &mut stack, &mut solution),
+            Step::Push(range) => solution.push(range),
+        }
+    }
+    solution
+}
+
+// Diffs `old` and `new` as far as possible, leaving the regions around a common region on
+// `stack`, which is handled in reverse order
+fn conquer<'a, 'b, T: Eq + Hash>(
+    mut old: Range<'a, [T]>,
+    mut new: Range<'b, [T]>,
+    stack: &mut Vec<Step<'a, 'b, T>>,
+    solution: &mut Vec<DiffRange<'a, 'b, [T]>>,
+) {
+    let common_prefix_len = old.common_prefix_len(new);
+    if common_prefix_len > 0 {
+        solution.push(DiffRange::Equal(
+            old.slice(..common_prefix_len),
+            new.slice(..common_prefix_len),
+        ));
+    }
+    old = old.slice(common_prefix_len..);
+    new = new.slice(common_prefix_len..);
+
+    let common_suffix_len = old.common_suffix_len(new);
+    if common_suffix_len > 0 {
+        stack.push(Step::Push(DiffRange::Equal(
+            old.slice(old.len() - common_suffix_len..),
+            new.slice(new.len() - common_suffix_len..),
+        )));
+    }
+    old = old.slice(..old.len() - common_suffix_len);
+    new = new.slice(..new.len() - common_suffix_len);
+
+    if old.is_empty() && new.is_empty() {
+        // Do nothing
+    } else if old.is_empty() {
+        solution.push(DiffRange::Insert(new));
+    } else if new.is_empty() {
+        solution.push(DiffRange::Delete(old));
+    } else if let Some(region) = find_region(old.as_slice(), new.as_slice()) {
+        stack.push(Step::Diff(
+            old.slice(region.old_start + region.len..),
+            new.slice(region.new_start + region.len..),
+        ));
+        stack.push(Step::Push(DiffRange::Equal(
+            old.slice(region.old_start..region.old_start + region.len),
+            new.slice(region.new_start..region.new_start + region.len),
+        )));
+        stack.push(Step::Diff(
+            old.slice(..region.old_start),
+            new.slice(..region.new_start),
+        ));
+    } else {
+        myers::diff_ranges(old, new, solution);
+    }
+}
+
+/// A region of lines which are the same in `old` and `new`
+struct Region {
+    old_start: usize,
+    new_start: usize,
+    len: usize,
+}
+
+// Finds the common region containing the line which appears the fewest times in `old`,
+// preferring longer regions when there are several
+fn find_region<T: Eq + Hash>(old: &[T], new: &[T]) -> Option<Region> {
+    // Where each line appears in old
+    let mut occurrences: HashMap<&T, Vec<usize>> = HashMap::new();
+    for (i, line) in old.iter().enumerate() {
+        occurrences.entry(line).or_default().push(i);
+    }
+
+    let mut best: Option<(usize, Region)> = None;
+    let mut new_idx = 0;
+    while new_idx < new.len() {
+        let mut next_idx = new_idx + 1;
+        let Some(positions) = occurrences.get(&new[new_idx]) else {
+            new_idx = next_idx;
+            continue;
+        };
+        if positions.len() > MAX_CHAIN_LEN {
+            new_idx = next_idx;
+            continue;
+        }
+
+        for &old_idx in positions {
+            let before = old[..old_idx]
+                .iter()
+                .rev()
+                .zip(new[..new_idx].iter().rev())
+                .take_while(|(a, b)| a == b)
+                .count();
+            let after = old[old_idx..]
+                .iter()
+                .zip(&new[new_idx..])
+                .take_while(|(a, b)| a == b)
+                .count();
+            let region = Region {
+                old_start: old_idx - before,
+                new_start: new_idx - before,
+                len: before + after,
+            };
+
+            let count = old[region.old_start..region.old_start + region.len]
+                .iter()
+                .map(|line| occurrences[line].len())
+                .min()
+                .unwrap_or(usize::MAX);
+            if best.as_ref().is_none_or(|(best_count, best)| {
+                count < *best_count || (count == *best_count && region.len > best.len)
+            }) {
+                next_idx = next_idx.max(region.new_start + region.len);
+                best = Some((count, region));
+            }
+        }
+
+        new_idx = next_idx;
+    }
+
+    best.map(|(_, region)| region)
+}
@@ -5,13 +5,18 @@ use crate::patch::Patch;
 use crate::range::DiffRange;
 use crate::range::SliceLike;
 use crate::utils::Classifier;
+use crate::utils::Text;
+use crate::utils::Whitespace;
 use alloc::borrow::Cow;
 use alloc::vec::Vec;
 use core::cmp;
+use core::hash::Hash;
 use core::ops;
 
 mod cleanup;
+mod histogram;
 mod myers;
+mod patience;
 
 #[cfg(test)]
 mod tests;
@@ -45,6 +50,28 @@ where
     }
 }
 
+/// The algorithm used to find the differences between two texts
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DiffAlgorithm {
+    /// Myers' algorithm, which finds a minimal diff.
+    ///
+    /// This is the default algorithm used by git.
+    Myers,
+
+    /// The patience algorithm, which matches up lines which appear exactly once in both texts
+    /// before diffing the regions between them.
+    ///
+    /// This tends to produce more readable diffs of code, where lines like blank lines and
+    /// closing braces would otherwise be matched up across unrelated changes.
+    Patience,
+
+    /// The histogram algorithm, an extension of the patience algorithm which also anchors on
+    /// lines which appear a few times, preferring the rarest ones.
+    ///
+    /// This is usually faster than the patience algorithm and produces similar diffs.
+    Histogram,
+}
+
 /// A collection of options for modifying the way a diff is performed
 ///
 /// # Examples
@@ -84,10 +111,14 @@ where
 /// ",
 /// );
 /// ```
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct DiffOptions {
+    algorithm: DiffAlgorithm,
     compact: bool,
     context_len: usize,
+    ignore_cr_at_eol: bool,
+    ignore_space_change: bool,
+    ignore_all_space: bool,
     original_filename: Option<Cow<'static, str>>,
     modified_filename: Option<Cow<'static, str>>,
 }
@@ -96,28 +127,118 @@ impl DiffOptions {
     /// Construct a new `DiffOptions` with default settings
     ///
     /// ## Defaults
+    /// * algorithm = DiffAlgorithm::Myers
+    /// * compact = true
     /// * context_len = 3
+    /// * ignore_cr_at_eol = false
+    /// * ignore_space_change = false
+    /// * ignore_all_space = false
     pub fn new() -> Self {
         Self {
+            algorithm: DiffAlgorithm::Myers,
             compact: true,
             context_len: 3,
+            ignore_cr_at_eol: false,
+            ignore_space_change: false,
+            ignore_all_space: false,
             original_filename: Some("original".into()),
             modified_filename: Some("modified".into()),
         }
     }
 
+    /// Set the algorithm used to find the differences between texts
+    ///
+    /// ```
+    /// use diffy::DiffAlgorithm;
+    /// use diffy::DiffOptions;
+    ///
+    /// let original = "fn a() {\n    a();\n}\n\nfn c() {\n    c();\n}\n";
+    /// let modified = "fn a() {\n    a();\n}\n\nfn b() {\n    b();\n}\n\nfn c() {\n    c();\n}\n";
+    ///
+    /// let patch = DiffOptions::new()
+    ///     .set_algorithm(DiffAlgorithm::Patience)
+    ///     .set_context_len(0)
+    ///     .create_patch(original, modified);
+    ///
+    /// assert_eq!(
+    ///     patch.to_string(),
+    ///     "\
+    /// --- original
+    /// +++ modified
+    /// @@ -4,0 +5,4 @@
+    /// +fn b() {
+    /// +    b();
+    /// +}
+    /// +
+    /// ",
+    /// );
+    /// ```
+    pub fn set_algorithm(&mut self, algorithm: DiffAlgorithm) -> &mut Self {
+        self.algorithm = algorithm;
+        self
+    }
+
     /// Set the number of context lines that should be used when producing a patch
     pub fn set_context_len(&mut self, context_len: usize) -> &mut Self {
         self.context_len = context_len;
         self
     }
 
+    /// Treat lines ending in `\r\n` and `\n` as equal when comparing texts
+    ///
+    /// Lines which only differ in their line ending won't show up as changes in the produced
+    /// patch, similar to git's `--ignore-cr-at-eol`. The lines included in the patch are taken
+    /// verbatim from the texts being compared, with context lines taken from the original text so
+    /// that the patch still applies to it.
+    ///
+    /// ```
+    /// use diffy::DiffOptions;
+    ///
+    /// let patch = DiffOptions::new()
+    ///     .set_ignore_cr_at_eol(true)
+    ///     .create_patch("alpha\nbeta\n", "alpha\r\nbeta\r\n");
+    ///
+    /// assert!(patch.hunks().is_empty());
+    /// ```
+    pub fn set_ignore_cr_at_eol(&mut self, ignore_cr_at_eol: bool) -> &mut Self {
+        self.ignore_cr_at_eol = ignore_cr_at_eol;
+        self
+    }
+
+    /// Treat lines which only differ in the amount of whitespace as equal when comparing texts
+    ///
+    /// Whitespace at the end of lines is ignored, and all other runs of whitespace are treated
+    /// as equal, similar to git's `--ignore-space-change`. The lines included in the patch are
+    /// taken verbatim from the texts being compared, with context lines taken from the original
+    /// text so that the patch still applies to it.
+    ///
+    /// ```
+    /// use diffy::DiffOptions;
+    ///
+    /// let patch = DiffOptions::new()
+    ///     .set_ignore_space_change(true)
+    ///     .create_patch("a  b\nc\n", "a\tb\nc \n");
+    ///
+    /// assert!(patch.hunks().is_empty());
+    /// ```
+    pub fn set_ignore_space_change(&mut self, ignore_space_change: bool) -> &mut Self {
+        self.ignore_space_change = ignore_space_change;
+        self
+    }
+
+    /// Ignore all whitespace when comparing lines
+    ///
+    /// This is similar to git's `--ignore-all-space`, and takes precedence over
+    /// [`set_ignore_space_change`](Self::set_ignore_space_change).
+    pub fn set_ignore_all_space(&mut self, ignore_all_space: bool) -> &mut Self {
+        self.ignore_all_space = ignore_all_space;
+        self
+    }
+
     /// Enable/Disable diff compaction. Compaction is a post-processing step which attempts to
     /// produce a prettier diff by reducing the number of edited blocks by shifting and merging
     /// edit blocks.
-    // TODO determine if this should be exposed in the public API
-    #[expect(dead_code)]
-    fn set_compact(&mut self, compact: bool) -> &mut Self {
+    pub fn set_compact(&mut self, compact: bool) -> &mut Self {
         self.compact = compact;
         self
     }
@@ -162,7 +283,7 @@ impl DiffOptions {
 
     /// Produce a Patch between two texts based on the configured options
     pub fn create_patch<'a>(&self, original: &'a str, modified: &'a str) -> Patch<'a, str> {
-        let mut classifier = Classifier::default();
+        let mut classifier = self.classifier();
         let (old_lines, old_ids) = classifier.classify_lines(original);
         let (new_lines, new_ids) = classifier.classify_lines(modified);
 
@@ -182,7 +303,7 @@ impl DiffOptions {
         original: &'a [u8],
         modified: &'a [u8],
     ) -> Patch<'a, [u8]> {
-        let mut classifier = Classifier::default();
+        let mut classifier = self.classifier();
         let (old_lines, old_ids) = classifier.classify_lines(original);
         let (new_lines, new_ids) = classifier.classify_lines(modified);
 
@@ -205,7 +326,42 @@ impl DiffOptions {
         )
     }
 
-    pub(crate) fn diff_slice<'a, T: PartialEq>(
+    pub(crate) fn ignore_cr_at_eol(&self) -> bool {
+        self.ignore_cr_at_eol
+    }
+
+    pub(crate) fn whitespace(&self) -> Whitespace {
+        Whitespace::new(self.ignore_space_change, self.ignore_all_space)
+    }
+
+    // Returns a classifier which compares lines based on the configured options
+    pub(crate) fn classifier<'a, T: Text + ?Sized>(&self) -> Classifier<'a, T> {
+        let mut classifier = Classifier::new(self.ignore_cr_at_eol);
+        classifier.set_whitespace(self.whitespace());
+        classifier
+    }
+
+    pub(crate) fn diff_slice<'a, T: Eq + Hash>(
+        &self,
+        old: &'a [T],
+        new: &'a [T],
+    ) -> Vec<DiffRange<'a, 'a, [T]>> {
+        let mut solution = match self.algorithm {
+            DiffAlgorithm::Myers => myers::diff(old, new),
+            DiffAlgorithm::Patience => patience::diff(old, new),
+            DiffAlgorithm::Histogram => histogram::diff(old, new),
+        };
+
+        if self.compact {
+            cleanup::compact(&mut solution);
+        }
+
+        solution
+    }
+
+    // Diffs slices of lines which can only be compared for equality, which always uses Myers'
+    // algorithm
+    pub(crate) fn diff_slice_myers<'a, T: PartialEq>(
         &self,
         old: &'a [T],
         new: &'a [T],
@@ -320,7 +476,7 @@ fn to_hunks<'a, T: ?Sized>(
         let mut lines = Vec::new();
 
         // Pre-context
-        for line in lines2.get(start2..script.new.start).into_iter().flatten() {
+        for line in lines1.get(start1..script.old.start).into_iter().flatten() {
             lines.push(Line::Context(*line));
         }
 
@@ -341,9 +497,8 @@ fn to_hunks<'a, T: ?Sized>(
                     cmp::min(s.old.start, lines1.len() - 1).saturating_sub(context_len);
                 if start1_next < end1 {
                     // Context lines between hunks
-                    for (_i1, i2) in (script.old.end..s.old.start).zip(script.new.end..s.new.start)
-                    {
-                        if let Some(line) = lines2.get(i2) {
+                    for i1 in script.old.end..s.old.start {
+                        if let Some(line) = lines1.get(i1) {
                             lines.push(Line::Context(*line));
                         }
                     }
@@ -369,7 +524,7 @@ fn to_hunks<'a, T: ?Sized>(
         }
 
         // Post-context
-        for line in lines2.get(script.new.end..end2).into_iter().flatten() {
+        for line in lines1.get(script.old.end..end1).into_iter().flatten() {
             lines.push(Line::Context(*line));
         }
 
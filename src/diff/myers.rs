@@ -241,11 +241,19 @@ fn conquer<'a, 'b, T: PartialEq>(
 }
 
 pub fn diff<'a, 'b, T: PartialEq>(old: &'a [T], new: &'b [T]) -> Vec<DiffRange<'a, 'b, [T]>> {
-    let old_recs = Range::new(old, ..);
-    let new_recs = Range::new(new, ..);
-
     let mut solution = Vec::new();
+    diff_ranges(Range::new(old, ..), Range::new(new, ..), &mut solution);
+    solution
+}
 
+/// Diffs part of two slices, appending the result to `solution`
+///
+/// Used by the other algorithms for the regions they can't split up any further.
+pub fn diff_ranges<'a, 'b, T: PartialEq>(
+    old: Range<'a, [T]>,
+    new: Range<'b, [T]>,
+    solution: &mut Vec<DiffRange<'a, 'b, [T]>>,
+) {
     // The arrays that hold the 'best possible x values' in search from:
     // `vf`: top left to bottom right
     // `vb`: bottom right to top left
@@ -253,9 +261,7 @@ pub fn diff<'a, 'b, T: PartialEq>(old: &'a [T], new: &'b [T]) -> Vec<DiffRange<'
     let mut vf = V::new(max_d);
     let mut vb = V::new(max_d);
 
-    conquer(old_recs, new_recs, &mut vf, &mut vb, &mut solution);
-
-    solution
+    conquer(old, new, &mut vf, &mut vb, solution);
 }
 
 #[cfg(test)]
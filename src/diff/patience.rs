@@ -0,0 +1,131 @@
+//! Patience diff
+//!
+//! Lines which appear exactly once in both slices are used as anchors. The longest sequence of
+//! anchors appearing in the same order in both slices is matched up, and the regions between
+//! them are diffed recursively. Regions without any unique lines are diffed with Myers'
+//! algorithm.
+
+use super::myers;
+use crate::range::DiffRange;
+use crate::range::Range;
+use alloc::vec::Vec;
+use core::hash::Hash;
+use hashbrown::HashMap;
+
+pub fn diff<'a, 'b, T: Eq + Hash>(old: &'a [T], new: &'b [T]) -> Vec<DiffRange<'a, 'b, [T]>> {
+    let mut solution = Vec::new();
+    conquer(Range::new(old, ..), Range::new(new, ..), &mut solution);
+    solution
+}
+
+fn conquer<'a, 'b, T: Eq + Hash>(
+    mut old: Range<'a, [T]>,
+    mut new: Range<'b, [T]>,
+    solution: &mut Vec<DiffRange<'a, 'b, [T]>>,
+) {
+    let common_prefix_len = old.common_prefix_len(new);
+    if common_prefix_len > 0 {
+        solution.push(DiffRange::Equal(
+            old.slice(..common_prefix_len),
+            new.slice(..common_prefix_len),
+        ));
+    }
+    old = old.slice(common_prefix_len..);
+    new = new.slice(common_prefix_len..);
+
+    let common_suffix_len = old.common_suffix_len(new);
+    let common_suffix = DiffRange::Equal(
+        old.slice(old.len() - common_suffix_len..),
+        new.slice(new.len() - common_suffix_len..),
+    );
+    old = old.slice(..old.len() - common_suffix_len);
+    new = new.slice(..new.len() - common_suffix_len);
+
+    if old.is_empty() && new.is_empty() {
+        // Do nothing
+    } else if old.is_empty() {
+        solution.push(DiffRange::Insert(new));
+    } else if new.is_empty() {
+        solution.push(DiffRange::Delete(old));
+    } else {
+        let anchors = unique_anchors(old.as_slice(), new.as_slice());
+        if anchors.is_empty() {
+            myers::diff_ranges(old, new, solution);
+        } else {
+            let (mut old_pos, mut new_pos) = (0, 0);
+            for (old_idx, new_idx) in anchors {
+                conquer(
+                    old.slice(old_pos..old_idx),
+                    new.slice(new_pos..new_idx),
+                    solution,
+                );
+                solution.push(DiffRange::Equal(
+                    old.slice(old_idx..old_idx + 1),
+                    new.slice(new_idx..new_idx + 1),
+                ));
+                (old_pos, new_pos) = (old_idx + 1, new_idx + 1);
+            }
+            conquer(old.slice(old_pos..), new.slice(new_pos..), solution);
+        }
+    }
+
+    if common_suffix_len > 0 {
+        solution.push(common_suffix);
+    }
+}
+
+// Returns the indexes of the longest sequence of lines which appear exactly once in both `old`
+// and `new`, and in the same order in both
+fn unique_anchors<T: Eq + Hash>(old: &[T], new: &[T]) -> Vec<(usize, usize)> {
+    // The number of times each line appears in old and new, and where it last appeared
+    let mut counts: HashMap<&T, (usize, usize, usize, usize)> = HashMap::new();
+    for (i, line) in old.iter().enumerate() {
+        let entry = counts.entry(line).or_default();
+        entry.0 += 1;
+        entry.1 = i;
+    }
+    for (i, line) in new.iter().enumerate() {
+        if let Some(entry) = counts.get_mut(line) {
+            entry.2 += 1;
+            entry.3 = i;
+        }
+    }
+
+    let unique: Vec<(usize, usize)> = old
+        .iter()
+        .filter_map(|line| match counts[line] {
+            (1, old_idx, 1, new_idx) => Some((old_idx, new_idx)),
+            _ => None,
+        })
+        .collect();
+
+    longest_increasing_subsequence(&unique)
+}
+
+// Finds the longest subsequence of `pairs` (ordered by their first index) whose second indexes
+// are increasing, using patience sorting
+fn longest_increasing_subsequence(pairs: &[(usize, usize)]) -> Vec<(usize, usize)> {
+    // The index into `pairs` of the top card of each pile
+    let mut piles: Vec<usize> = Vec::new();
+    // The index into `pairs` of the top card of the previous pile when each card was placed
+    let mut previous: Vec<Option<usize>> = Vec::with_capacity(pairs.len());
+
+    for (i, &(_, new_idx)) in pairs.iter().enumerate() {
+        let pile = piles.partition_point(|&top| pairs[top].1 < new_idx);
+        previous.push(pile.checked_sub(1).map(|pile| piles[pile]));
+        if pile == piles.len() {
+            piles.push(i);
+        } else {
+            piles[pile] = i;
+        }
+    }
+
+    let mut sequence = Vec::with_capacity(piles.len());
+    let mut card = piles.last().copied();
+    while let Some(i) = card {
+        sequence.push(pairs[i]);
+        card = previous[i];
+    }
+    sequence.reverse();
+    sequence
+}
@@ -1,11 +1,13 @@
 use super::*;
 use crate::PatchFormatter;
 use crate::apply::apply;
+use crate::apply::apply_bytes;
 use crate::diff::Diff;
 use crate::diff::DiffRange;
 use crate::patch::Patch;
 use crate::range::Range;
 use alloc::format;
+use alloc::string::String;
 use alloc::string::ToString;
 use alloc::vec;
 use alloc::vec::Vec;
@@ -817,3 +819,261 @@ Kupluh, Indeed
     let re_reverse = apply(&apply(original, &p).unwrap(), &reverse).unwrap();
     assert_eq!(re_reverse, original);
 }
+
+#[test]
+fn ignore_cr_at_eol() {
+    let original = "alpha\nbeta\ngamma\n";
+    let modified = "alpha\r\nBETA\r\ngamma\r\n";
+
+    let mut opts = DiffOptions::new();
+    opts.set_ignore_cr_at_eol(true);
+
+    let expected = "\
+--- original
++++ modified
+@@ -1,3 +1,3 @@
+ alpha
+-beta
++BETA\r
+ gamma
+";
+    let patch = opts.create_patch(original, modified);
+    assert_eq!(patch.to_string(), expected);
+
+    let bpatch = opts.create_patch_bytes(original.as_bytes(), modified.as_bytes());
+    assert_eq!(bpatch.to_bytes(), expected.as_bytes());
+
+    // A missing newline at the end of the file is still a change
+    let patch = opts.create_patch("alpha\r\nbeta\r\n", "alpha\nbeta");
+    assert_eq!(patch.hunks().len(), 1);
+}
+
+#[test]
+fn ignore_cr_at_eol_patch_applies_to_original() {
+    let original = "a\r\nb\r\nX\r\n";
+    let modified = "a\nb\nY\n";
+
+    let mut opts = DiffOptions::new();
+    opts.set_ignore_cr_at_eol(true);
+
+    // Context lines are taken from the original
+    let patch = opts.create_patch(original, modified);
+    assert_eq!(apply(original, &patch).unwrap(), "a\r\nb\r\nY\n");
+
+    let patch = opts.create_patch_bytes(original.as_bytes(), modified.as_bytes());
+    assert_eq!(
+        apply_bytes(original.as_bytes(), &patch).unwrap(),
+        b"a\r\nb\r\nY\n"
+    );
+}
+
+#[test]
+fn ignore_whitespace() {
+    let original = "if x {\n  a  b\n}\n";
+    let modified = "if x {\n  a\tb  \n  ab\n}\n";
+
+    let mut opts = DiffOptions::new();
+    opts.set_ignore_space_change(true);
+    let patch = opts.create_patch(original, modified);
+    assert_eq!(
+        patch.to_string(),
+        "\
+--- original
++++ modified
+@@ -1,3 +1,4 @@
+ if x {
+   a  b
++  ab
+ }
+"
+    );
+
+    // Adding or removing whitespace within a line is still a change
+    assert_eq!(opts.create_patch("ab\n", "a b\n").hunks().len(), 1);
+    assert_eq!(opts.create_patch("a\n", " a\n").hunks().len(), 1);
+
+    opts.set_ignore_all_space(true);
+    assert!(opts.create_patch("ab\n", " a b \r\n").hunks().is_empty());
+    assert_eq!(opts.create_patch("ab\n", "ab").hunks().len(), 1);
+
+    let patch = opts.create_patch_bytes(b"a b\n", b"ab\n");
+    assert!(patch.hunks().is_empty());
+}
+
+#[test]
+fn ignore_whitespace_patch_applies_to_original() {
+    let original = "a  b\nc\nd\nX\n";
+    let modified = "a b\nc\nd\nY\n";
+    let expected = "a  b\nc\nd\nY\n";
+
+    let mut opts = DiffOptions::new();
+    opts.set_ignore_space_change(true);
+    let patch = opts.create_patch(original, modified);
+    assert_eq!(apply(original, &patch).unwrap(), expected);
+
+    let mut opts = DiffOptions::new();
+    opts.set_ignore_all_space(true);
+    let patch = opts.create_patch(original, "ab\nc\nd\nY\n");
+    assert_eq!(apply(original, &patch).unwrap(), expected);
+
+    let patch = opts.create_patch_bytes(original.as_bytes(), b"ab\nc\nd\nY\n");
+    assert_eq!(
+        apply_bytes(original.as_bytes(), &patch).unwrap(),
+        expected.as_bytes()
+    );
+}
+
+#[test]
+fn algorithms() {
+    let original = r#"#include <stdio.h>
+
+// Frobs foo heartily
+int frobnitz(int foo)
+{
+    int i;
+    for(i = 0; i < 10; i++)
+    {
+        printf("Your answer is: ");
+        printf("%d\n", foo);
+    }
+}
+
+int fact(int n)
+{
+    if(n > 1)
+    {
+        return fact(n-1) * n;
+    }
+    return 1;
+}
+
+int main(int argc, char **argv)
+{
+    frobnitz(fact(10));
+}
+"#;
+    let modified = r#"#include <stdio.h>
+
+int fib(int n)
+{
+    if(n > 2)
+    {
+        return fib(n-1) + fib(n-2);
+    }
+    return 1;
+}
+
+// Frobs foo heartily
+int frobnitz(int foo)
+{
+    int i;
+    for(i = 0; i < 10; i++)
+    {
+        printf("%d\n", foo);
+    }
+}
+
+int main(int argc, char **argv)
+{
+    frobnitz(fib(10));
+}
+"#;
+
+    // Myers' algorithm matches up the braces of the unrelated functions
+    let mut opts = DiffOptions::new();
+    opts.set_context_len(0);
+    let patch = opts.create_patch(original, modified);
+    assert_eq!(patch.hunks().len(), 9);
+
+    let expected = r#"--- original
++++ modified
+@@ -2,0 +3,9 @@
++int fib(int n)
++{
++    if(n > 2)
++    {
++        return fib(n-1) + fib(n-2);
++    }
++    return 1;
++}
++
+@@ -9 +17,0 @@
+-        printf("Your answer is: ");
+@@ -14,9 +21,0 @@
+-int fact(int n)
+-{
+-    if(n > 1)
+-    {
+-        return fact(n-1) * n;
+-    }
+-    return 1;
+-}
+-
+@@ -25 +24 @@
+-    frobnitz(fact(10));
++    frobnitz(fib(10));
+"#;
+    for algorithm in [DiffAlgorithm::Patience, DiffAlgorithm::Histogram] {
+        let patch = opts
+            .set_algorithm(algorithm)
+            .create_patch(original, modified);
+        assert_eq!(patch.to_string(), expected, "{algorithm:?}");
+        assert_eq!(apply(original, &patch).unwrap(), modified);
+    }
+}
+
+#[test]
+fn algorithms_random_edits() {
+    struct Rng(u64);
+    impl Rng {
+        fn next(&mut self, n: u64) -> u64 {
+            self.0 = self
+                .0
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (self.0 >> 33) % n
+        }
+    }
+
+    let mut rng = Rng(11);
+    let mut opts = DiffOptions::new();
+    for _ in 0..200 {
+        // Few distinct lines, so that most lines are repeated
+        let mut text = || -> String {
+            (0..rng.next(30))
+                .map(|_| format!("{}\n", rng.next(8)))
+                .collect()
+        };
+        let (original, modified) = (text(), text());
+
+        for algorithm in [
+            DiffAlgorithm::Myers,
+            DiffAlgorithm::Patience,
+            DiffAlgorithm::Histogram,
+        ] {
+            for compact in [false, true] {
+                let patch = opts
+                    .set_algorithm(algorithm)
+                    .set_compact(compact)
+                    .create_patch(&original, &modified);
+                assert_eq!(
+                    apply(&original, &patch).unwrap(),
+                    modified,
+                    "{algorithm:?}\n{original}\n{modified}"
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn histogram_many_changes() {
+    // Every other line changes, so each unchanged line is a separate common region
+    let n = 2000;
+    let original: String = (0..n).map(|i| format!("{i}\nx\n")).collect();
+    let modified: String = (0..n).map(|i| format!("{i}\ny\n")).collect();
+    let patch = DiffOptions::new()
+        .set_algorithm(DiffAlgorithm::Histogram)
+        .create_patch(&original, &modified);
+    assert_eq!(apply(&original, &patch).unwrap(), modified);
+}
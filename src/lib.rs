@@ -8,7 +8,8 @@
 //! communicate differences between two versions of a file using a `diff` or
 //! `patch`.
 //!
-//! The current diff implementation is based on the [Myers' diff algorithm].
+//! By default, diffs are computed with [Myers' diff algorithm]. The patience
+//! and histogram algorithms can be chosen instead with [`DiffAlgorithm`].
 //!
 //! The documentation generally refers to "files" in many places but none of
 //! the apis explicitly operate on on-disk files. Instead this library
@@ -324,18 +325,38 @@ mod range;
 mod utils;
 
 pub use apply::ApplyError;
+pub use apply::ApplyOptions;
 pub use apply::apply;
 pub use apply::apply_bytes;
+pub use apply::apply_three_way;
+pub use apply::apply_three_way_bytes;
+pub use apply::check;
+pub use apply::check_bytes;
+pub use diff::DiffAlgorithm;
 pub use diff::DiffOptions;
 pub use diff::create_patch;
 pub use diff::create_patch_bytes;
 pub use merge::ConflictStyle;
+pub use merge::ConflictedChunk;
+pub use merge::ConflictedFile;
+pub use merge::MergeChunk;
+pub use merge::MergeConflict;
+pub use merge::MergeFavor;
+pub use merge::MergeLevel;
 pub use merge::MergeOptions;
+pub use merge::MergeResult;
+pub use merge::MergeSide;
+pub use merge::ParseConflictsError;
+pub use merge::TreeMergeResult;
+pub use merge::TreeMergeStatus;
 pub use merge::merge;
 pub use merge::merge_bytes;
+pub use patch::ComposeError;
 pub use patch::Hunk;
 pub use patch::HunkRange;
 pub use patch::Line;
+pub use patch::LineMapping;
 pub use patch::ParsePatchError;
 pub use patch::Patch;
 pub use patch::PatchFormatter;
+pub use patch::RebaseError;
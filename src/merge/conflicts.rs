@@ -0,0 +1,266 @@
+//! Parse files containing conflict markers.
+
+use alloc::vec::Vec;
+use core::fmt;
+
+use super::MergeConflict;
+use crate::utils::LineIter;
+use crate::utils::Text;
+use crate::utils::split_line_ending;
+
+/// A file containing conflict markers, split into the lines outside of conflicts and the
+/// conflicts themselves.
+///
+/// Returned by [`MergeOptions::parse_conflicts`]. Conflicts which have already been resolved by
+/// hand are just lines outside of conflicts, so partially resolved files can be parsed too.
+///
+/// Conflicts rendered with [`ConflictStyle::Merge`] don't include the lines of the common
+/// ancestor, so [`MergeConflict::ancestor`] is empty for them. The line numbers of each conflict
+/// are those of the files returned by [`ours`](Self::ours) and [`theirs`](Self::theirs), and of
+/// the common ancestor made up of the lines outside of conflicts and the ancestor lines of each
+/// conflict.
+///
+/// ```
+/// use diffy::MergeOptions;
+///
+/// let text = "\
+/// alpha
+/// <<<<<<< HEAD
+/// Beta
+/// ||||||| base
+/// beta
+/// =======
+/// BETA
+/// >>>>>>> feature
+/// gamma
+/// ";
+///
+/// let file = MergeOptions::new().parse_conflicts(text).unwrap();
+/// let conflict = file.conflicts().next().unwrap();
+/// assert_eq!(conflict.ancestor(), ["beta\n"]);
+/// assert_eq!(conflict.ours(), ["Beta\n"]);
+/// assert_eq!(conflict.theirs(), ["BETA\n"]);
+///
+/// assert_eq!(file.ours(), "alpha\nBeta\ngamma\n");
+/// assert_eq!(file.theirs(), "alpha\nBETA\ngamma\n");
+/// ```
+///
+/// [`MergeOptions::parse_conflicts`]: crate::MergeOptions::parse_conflicts
+/// [`ConflictStyle::Merge`]: crate::ConflictStyle::Merge
+#[derive(Debug, PartialEq, Eq)]
+pub struct ConflictedFile<'a, T: ?Sized> {
+    chunks: Vec<ConflictedChunk<'a, T>>,
+}
+
+/// A chunk of a [`ConflictedFile`].
+#[derive(Debug, PartialEq, Eq)]
+pub enum ConflictedChunk<'a, T: ?Sized> {
+    /// Lines outside of any conflict.
+    Resolved(Vec<&'a T>),
+
+    /// Lines between conflict markers.
+    Conflict(MergeConflict<'a, T>),
+}
+
+impl<'a, T: Text + ?Sized> ConflictedFile<'a, T> {
+    pub(crate) fn parse(text: &'a T, marker_len: usize) -> Result<Self, ParseConflictsError> {
+        let mut chunks = Vec::new();
+        let mut resolved = Vec::new();
+        // The number of lines of the ancestor, ours and theirs before the current line
+        let mut pos = [0; 3];
+        let mut conflict: Option<ConflictParser<'a, T>> = None;
+
+        for (i, line) in LineIter::new(text).enumerate() {
+            let error = |kind| Err(ParseConflictsError { line: i + 1, kind });
+            let marker = marker(line, marker_len);
+
+            let Some(current) = &mut conflict else {
+                if marker == Some(b'<') {
+                    if !resolved.is_empty() {
+                        chunks.push(ConflictedChunk::Resolved(core::mem::take(&mut resolved)));
+                    }
+                    conflict = Some(ConflictParser::new(i + 1));
+                } else {
+                    // Other markers are only meaningful inside of a conflict, and lines like
+                    // `=======` are common in some kinds of text
+                    resolved.push(line);
+                    pos.iter_mut().for_each(|pos| *pos += 1);
+                }
+                continue;
+            };
+
+            match (marker, current.section) {
+                (Some(b'<'), _) => return error(ParseConflictsErrorKind::NestedConflict),
+                (Some(b'|'), Section::Ours) => current.section = Section::Ancestor,
+                (Some(b'='), Section::Ours | Section::Ancestor) => {
+                    current.section = Section::Theirs
+                }
+                (Some(b'>'), Section::Theirs) => {
+                    let ConflictParser { lines, .. } = conflict.take().unwrap();
+                    let [ancestor, ours, theirs] = lines;
+                    let lens = [ancestor.len(), ours.len(), theirs.len()];
+                    chunks.push(ConflictedChunk::Conflict(MergeConflict::new(
+                        (pos[0], ancestor),
+                        (pos[1], ours),
+                        (pos[2], theirs),
+                    )));
+                    pos.iter_mut().zip(lens).for_each(|(pos, len)| *pos += len);
+                }
+                (Some(marker), _) => {
+                    return error(ParseConflictsErrorKind::UnexpectedMarker(marker));
+                }
+                (None, section) => current.lines[section as usize].push(line),
+            }
+        }
+
+        if let Some(conflict) = conflict {
+            return Err(ParseConflictsError {
+                line: conflict.start,
+                kind: ParseConflictsErrorKind::UnterminatedConflict,
+            });
+        }
+        if !resolved.is_empty() {
+            chunks.push(ConflictedChunk::Resolved(resolved));
+        }
+
+        Ok(Self { chunks })
+    }
+
+    /// Returns our version of the file, with each conflict replaced by our lines
+    pub fn ours(&self) -> T::Owned {
+        self.reconstruct(MergeConflict::ours)
+    }
+
+    /// Returns their version of the file, with each conflict replaced by their lines
+    pub fn theirs(&self) -> T::Owned {
+        self.reconstruct(MergeConflict::theirs)
+    }
+
+    fn reconstruct(
+        &self,
+        side: impl for<'b> Fn(&'b MergeConflict<'a, T>) -> &'b [&'a T],
+    ) -> T::Owned {
+        let mut output = Vec::new();
+        for chunk in &self.chunks {
+            let lines = match chunk {
+                ConflictedChunk::Resolved(lines) => lines,
+                ConflictedChunk::Conflict(conflict) => side(conflict),
+            };
+            lines
+                .iter()
+                .for_each(|line| output.extend_from_slice(line.as_bytes()));
+        }
+        T::owned_from_bytes(output).expect("lines of the file are valid text")
+    }
+}
+
+impl<'a, T: ?Sized> ConflictedFile<'a, T> {
+    /// Returns the chunks of the file, in order
+    pub fn chunks(&self) -> &[ConflictedChunk<'a, T>] {
+        &self.chunks
+    }
+
+    /// Returns an iterator over the conflicts in the file
+    pub fn conflicts(&self) -> impl Iterator<Item = &MergeConflict<'a, T>> + '_ {
+        self.chunks.iter().filter_map(|chunk| match chunk {
+            ConflictedChunk::Resolved(_) => None,
+            ConflictedChunk::Conflict(conflict) => Some(conflict),
+        })
+    }
+
+    /// Returns `true` if the file has any conflicts left
+    pub fn has_conflicts(&self) -> bool {
+        self.conflicts().next().is_some()
+    }
+}
+
+/// The section of a conflict being parsed, indexing `ConflictParser::lines`
+#[derive(Clone, Copy)]
+enum Section {
+    Ancestor,
+    Ours,
+    Theirs,
+}
+
+struct ConflictParser<'a, T: ?Sized> {
+    /// The line number of the `<<<<<<<` marker
+    start: usize,
+    section: Section,
+    lines: [Vec<&'a T>; 3],
+}
+
+impl<T: ?Sized> ConflictParser<'_, T> {
+    fn new(start: usize) -> Self {
+        Self {
+            start,
+            section: Section::Ours,
+            lines: [Vec::new(), Vec::new(), Vec::new()],
+        }
+    }
+}
+
+// Returns the character of the conflict marker `line` starts with, if any
+//
+// Markers are made up of exactly `marker_len` of the same character, followed by a label or the
+// end of the line. The `=======` marker never has a label.
+fn marker<T: Text + ?Sized>(line: &T, marker_len: usize) -> Option<u8> {
+    let (content, _) = split_line_ending(line);
+    let content = content.as_bytes();
+
+    let marker = *content.first()?;
+    if !b"<|=>".contains(&marker)
+        || content.len() < marker_len
+        || content[..marker_len].iter().any(|&b| b != marker)
+    {
+        return None;
+    }
+
+    match content.get(marker_len) {
+        None => Some(marker),
+        Some(b' ') if marker != b'=' => Some(marker),
+        Some(_) => None,
+    }
+}
+
+/// An error returned when parsing a file with
+/// [`MergeOptions::parse_conflicts`](crate::MergeOptions::parse_conflicts) fails.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseConflictsError {
+    line: usize,
+    kind: ParseConflictsErrorKind,
+}
+
+impl ParseConflictsError {
+    /// Returns the line number (starting at 1) of the marker which couldn't be parsed
+    pub fn line(&self) -> usize {
+        self.line
+    }
+}
+
+impl fmt::Display for ParseConflictsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "error parsing conflict markers at line {}: ", self.line)?;
+        match self.kind {
+            ParseConflictsErrorKind::NestedConflict => write!(f, "nested conflict"),
+            ParseConflictsErrorKind::UnexpectedMarker(marker) => {
+                write!(f, "unexpected `{}` marker", marker as char)
+            }
+            ParseConflictsErrorKind::UnterminatedConflict => write!(f, "unterminated conflict"),
+        }
+    }
+}
+
+impl core::error::Error for ParseConflictsError {}
+
+/// The kind of error that occurred when parsing conflict markers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ParseConflictsErrorKind {
+    /// A `<<<<<<<` marker appeared inside of a conflict.
+    NestedConflict,
+
+    /// A marker appeared where it wasn't expected, like `>>>>>>>` before `=======`.
+    UnexpectedMarker(u8),
+
+    /// A conflict has no `>>>>>>>` marker.
+    UnterminatedConflict,
+}
@@ -1,15 +1,34 @@
+use crate::apply::ApplyOptions;
+use crate::apply::three_way_images;
 use crate::diff::DiffOptions;
+use crate::patch::Patch;
 use crate::range::DiffRange;
 use crate::range::Range;
 use crate::range::SliceLike;
-use crate::utils::Classifier;
+use crate::utils::LineEnding;
+use crate::utils::Text;
+use crate::utils::Whitespace;
+use alloc::collections::BTreeMap;
 use alloc::fmt;
 use alloc::string::String;
 use alloc::vec::Vec;
 use core::cmp;
 
+mod conflicts;
+mod result;
 #[cfg(test)]
 mod tests;
+mod tree;
+
+pub use conflicts::ConflictedChunk;
+pub use conflicts::ConflictedFile;
+pub use conflicts::ParseConflictsError;
+pub use result::MergeChunk;
+pub use result::MergeConflict;
+pub use result::MergeResult;
+pub use result::MergeSide;
+pub use tree::TreeMergeResult;
+pub use tree::TreeMergeStatus;
 
 const DEFAULT_CONFLICT_MARKER_LENGTH: usize = 7;
 
@@ -113,6 +132,60 @@ pub enum ConflictStyle {
     /// >>>>>>> B
     /// ```
     Diff3,
+
+    /// Renders conflicts like [`Diff3`](Self::Diff3), but with lines at the start and end of
+    /// the conflict which are the same in both files moved outside the conflict markers.
+    ///
+    /// This matches git's `zdiff3` conflict style.
+    ///
+    /// ```console
+    /// lines at the start of both files A and B
+    /// <<<<<<< A
+    /// remaining lines in file A
+    /// ||||||| Original
+    /// lines in Original file
+    /// =======
+    /// remaining lines in file B
+    /// >>>>>>> B
+    /// lines at the end of both files A and B
+    /// ```
+    ZealousDiff3,
+}
+
+/// How hard a merge tries to reduce the size of conflicts
+///
+/// These correspond to the merge levels used by git's merge machinery.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum MergeLevel {
+    /// Only resolves regions which were changed by one side. Regions which both sides changed in
+    /// the same way are conflicts.
+    Minimal,
+
+    /// Also resolves regions which both sides changed in the same way.
+    Eager,
+
+    /// Also splits conflicts into smaller conflicts by diffing both sides of each conflict,
+    /// leaving the lines they agree on outside of the conflicts, and then combines conflicts
+    /// separated by at most three lines.
+    Zealous,
+
+    /// Like [`Zealous`](Self::Zealous), but also combines conflicts separated only by lines
+    /// without any letters or digits, such as blank lines or closing braces.
+    ZealousAlnum,
+}
+
+/// How to automatically resolve conflicts, instead of marking them with conflict markers
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum MergeFavor {
+    /// Resolves conflicts using the lines from `ours`, like `git merge-file --ours`.
+    Ours,
+
+    /// Resolves conflicts using the lines from `theirs`, like `git merge-file --theirs`.
+    Theirs,
+
+    /// Resolves conflicts using the lines from `ours` followed by the lines from `theirs`, like
+    /// `git merge-file --union`.
+    Union,
 }
 
 /// A collection of options for modifying the way a merge is performed
@@ -145,6 +218,14 @@ pub enum ConflictStyle {
 pub struct MergeOptions {
     conflict_marker_length: usize,
     style: ConflictStyle,
+    diff_options: DiffOptions,
+    level: MergeLevel,
+    favor: Option<MergeFavor>,
+    rename_threshold: Option<usize>,
+    unidiff_zero: bool,
+    ours_label: String,
+    original_label: String,
+    theirs_label: String,
 }
 
 impl MergeOptions {
@@ -153,10 +234,26 @@ impl MergeOptions {
     /// ## Defaults
     /// * conflict_marker_length = 7
     /// * style = ConflictStyle::Diff3
+    /// * diff_options = DiffOptions::new()
+    /// * level = MergeLevel::Eager
+    /// * favor = None
+    /// * rename_threshold = None
+    /// * unidiff_zero = false
+    /// * ours_label = "ours"
+    /// * original_label = "original"
+    /// * theirs_label = "theirs"
     pub fn new() -> Self {
         Self {
             conflict_marker_length: DEFAULT_CONFLICT_MARKER_LENGTH,
             style: ConflictStyle::Diff3,
+            diff_options: DiffOptions::new(),
+            level: MergeLevel::Eager,
+            favor: None,
+            rename_threshold: None,
+            unidiff_zero: false,
+            ours_label: String::from("ours"),
+            original_label: String::from("original"),
+            theirs_label: String::from("theirs"),
         }
     }
 
@@ -172,6 +269,272 @@ impl MergeOptions {
         self
     }
 
+    /// Treat lines ending in `\r\n` and `\n` as equal when merging
+    ///
+    /// The merged result, including any conflict markers, uses the line ending style of `ours`,
+    /// so that changes made on a checkout with different line endings can be merged cleanly.
+    ///
+    /// ```
+    /// use diffy::MergeOptions;
+    ///
+    /// let mut options = MergeOptions::new();
+    /// options.set_ignore_cr_at_eol(true);
+    ///
+    /// let merged = options
+    ///     .merge("a\nb\nc\n", "A\r\nb\r\nc\r\n", "a\nb\nC\n")
+    ///     .unwrap();
+    /// assert_eq!(merged, "A\r\nb\r\nC\r\n");
+    /// ```
+    pub fn set_ignore_cr_at_eol(&mut self, ignore_cr_at_eol: bool) -> &mut Self {
+        self.diff_options.set_ignore_cr_at_eol(ignore_cr_at_eol);
+        self
+    }
+
+    /// Treat lines which only differ in the amount of whitespace as equal when merging
+    ///
+    /// Whitespace at the end of lines is ignored, and all other runs of whitespace are treated
+    /// as equal, like the `ignore-space-change` option of git's merge strategies.
+    ///
+    /// Where lines are equal apart from their whitespace, our lines are used, so whitespace
+    /// changes only made by `theirs` are dropped. Lines which `theirs` changed in other ways are
+    /// taken from `theirs`, even if `ours` changed their whitespace.
+    ///
+    /// ```
+    /// use diffy::MergeOptions;
+    ///
+    /// let original = "fn main() {\n  run();\n}\n";
+    /// let ours = "fn main() {\n    run();  \n}\n";
+    /// let theirs = "fn main() {\n  run(1);\n}\n";
+    ///
+    /// let mut options = MergeOptions::new();
+    /// assert!(options.merge(original, ours, theirs).is_err());
+    ///
+    /// options.set_ignore_space_change(true);
+    /// assert_eq!(
+    ///     options.merge(original, ours, theirs).unwrap(),
+    ///     "fn main() {\n  run(1);\n}\n"
+    /// );
+    /// ```
+    pub fn set_ignore_space_change(&mut self, ignore_space_change: bool) -> &mut Self {
+        self.diff_options
+            .set_ignore_space_change(ignore_space_change);
+        self
+    }
+
+    /// Ignore all whitespace when comparing lines while merging
+    ///
+    /// This is like the `ignore-all-space` option of git's merge strategies, and otherwise
+    /// works like [`set_ignore_space_change`](Self::set_ignore_space_change), which it takes
+    /// precedence over.
+    pub fn set_ignore_all_space(&mut self, ignore_all_space: bool) -> &mut Self {
+        self.diff_options.set_ignore_all_space(ignore_all_space);
+        self
+    }
+
+    /// Set the options used to diff the common ancestor against each side of the merge
+    ///
+    /// Where conflicts are placed depends heavily on these diffs, so choosing a different
+    /// [`DiffAlgorithm`](crate::DiffAlgorithm) can produce cleaner merges. The algorithm,
+    /// compaction and the handling of line endings and whitespace are taken from `diff_options`,
+    /// and the rest of its settings are ignored. This replaces any line ending or whitespace
+    /// handling set with [`set_ignore_cr_at_eol`](Self::set_ignore_cr_at_eol),
+    /// [`set_ignore_space_change`](Self::set_ignore_space_change) or
+    /// [`set_ignore_all_space`](Self::set_ignore_all_space).
+    ///
+    /// ```
+    /// use diffy::DiffAlgorithm;
+    /// use diffy::DiffOptions;
+    /// use diffy::MergeOptions;
+    ///
+    /// let mut diff_options = DiffOptions::new();
+    /// diff_options.set_algorithm(DiffAlgorithm::Histogram);
+    ///
+    /// let mut options = MergeOptions::new();
+    /// options.set_diff_options(diff_options);
+    ///
+    /// let merged = options
+    ///     .merge("a\nb\nc\n", "A\nb\nc\n", "a\nb\nC\n")
+    ///     .unwrap();
+    /// assert_eq!(merged, "A\nb\nC\n");
+    /// ```
+    pub fn set_diff_options(&mut self, diff_options: DiffOptions) -> &mut Self {
+        self.diff_options = diff_options;
+        self
+    }
+
+    /// Set how hard the merge tries to reduce the size of conflicts
+    ///
+    /// See [`MergeLevel`] for the available levels. `git merge` uses
+    /// [`MergeLevel::Zealous`], so it produces smaller conflicts than the default.
+    ///
+    /// ```
+    /// use diffy::ConflictStyle;
+    /// use diffy::MergeLevel;
+    /// use diffy::MergeOptions;
+    ///
+    /// let original = "fn main() {\n}\n";
+    /// let ours = "fn main() {\n    let a = 1;\n    run(a);\n}\n";
+    /// let theirs = "fn main() {\n    let a = 2;\n    run(a);\n}\n";
+    ///
+    /// let mut options = MergeOptions::new();
+    /// options
+    ///     .set_conflict_style(ConflictStyle::Merge)
+    ///     .set_level(MergeLevel::Zealous);
+    /// assert_eq!(
+    ///     options.merge(original, ours, theirs).unwrap_err(),
+    ///     "\
+    /// fn main() {
+    /// <<<<<<< ours
+    ///     let a = 1;
+    /// =======
+    ///     let a = 2;
+    /// >>>>>>> theirs
+    ///     run(a);
+    /// }
+    /// "
+    /// );
+    /// ```
+    pub fn set_level(&mut self, level: MergeLevel) -> &mut Self {
+        self.level = level;
+        self
+    }
+
+    /// Automatically resolve conflicts by favoring one or both sides
+    ///
+    /// When set, conflicting regions are resolved as described by [`MergeFavor`] rather than
+    /// being marked with conflict markers, so the merge always succeeds. The number of conflicts
+    /// which were resolved this way is reported by
+    /// [`MergeResult::auto_resolved_conflicts`].
+    ///
+    /// ```
+    /// use diffy::MergeFavor;
+    /// use diffy::MergeOptions;
+    ///
+    /// let original = "# Changelog\n";
+    /// let ours = "# Changelog\n- Fix parsing\n";
+    /// let theirs = "# Changelog\n- Add merging\n";
+    ///
+    /// let mut options = MergeOptions::new();
+    /// options.set_favor(Some(MergeFavor::Union));
+    /// assert_eq!(
+    ///     options.merge(original, ours, theirs).unwrap(),
+    ///     "# Changelog\n- Fix parsing\n- Add merging\n"
+    /// );
+    ///
+    /// let result = options.structured_merge(original, ours, theirs);
+    /// assert_eq!(result.auto_resolved_conflicts(), 1);
+    /// ```
+    pub fn set_favor(&mut self, favor: Option<MergeFavor>) -> &mut Self {
+        self.favor = favor;
+        self
+    }
+
+    /// Set how similar a file must be to a deleted file to be detected as a rename of it, as a
+    /// percentage, when merging trees with [`merge_trees`](Self::merge_trees)
+    ///
+    /// The similarity of two files is the number of lines they have in common, relative to the
+    /// number of lines of the larger file. Renames aren't detected if this is `None`. This is
+    /// similar to the `find-renames` option of git's `ort` merge strategy, which defaults to
+    /// 50%.
+    ///
+    /// ```
+    /// use std::collections::BTreeMap;
+    ///
+    /// use diffy::MergeOptions;
+    ///
+    /// let ancestor = BTreeMap::from([("a.rs", "fn a() {}\nfn b() {}\n")]);
+    /// let ours = BTreeMap::from([("b.rs", "fn a() {}\nfn b() {}\n")]);
+    /// let theirs = BTreeMap::from([("a.rs", "fn a() {}\nfn b() { b(); }\n")]);
+    ///
+    /// let mut options = MergeOptions::new();
+    /// options.set_rename_threshold(Some(50));
+    ///
+    /// let result = options.merge_trees(&ancestor, &ours, &theirs);
+    /// assert!(!result.has_conflicts());
+    /// assert_eq!(result.renamed_from(&"b.rs"), Some(&"a.rs"));
+    ///
+    /// let merged: Vec<_> = result.merged().iter().collect();
+    /// assert_eq!(
+    ///     merged,
+    ///     [(&"b.rs", &b"fn a() {}\nfn b() { b(); }\n".to_vec())]
+    /// );
+    /// ```
+    pub fn set_rename_threshold(&mut self, rename_threshold: Option<usize>) -> &mut Self {
+        self.rename_threshold = rename_threshold;
+        self
+    }
+
+    /// Apply hunks without context lines at the line numbers in their headers when applying a
+    /// patch with [`apply_three_way`]
+    ///
+    /// See [`ApplyOptions::set_unidiff_zero`] for details. Hunks which don't apply at that
+    /// position are lined up with the base image there for the three-way merge.
+    ///
+    /// [`apply_three_way`]: MergeOptions::apply_three_way
+    /// [`ApplyOptions::set_unidiff_zero`]: crate::ApplyOptions::set_unidiff_zero
+    pub fn set_unidiff_zero(&mut self, unidiff_zero: bool) -> &mut Self {
+        self.unidiff_zero = unidiff_zero;
+        self
+    }
+
+    /// Set the labels written after the `<<<<<<<`, `|||||||` and `>>>>>>>` conflict markers
+    ///
+    /// This is similar to passing `-L` to `git merge-file` three times. An empty label leaves
+    /// its conflict marker on its own.
+    ///
+    /// ```
+    /// use diffy::MergeOptions;
+    ///
+    /// let mut options = MergeOptions::new();
+    /// options.set_labels("HEAD", "base", "feature/login");
+    ///
+    /// let conflict = options.merge("value\n", "ours\n", "theirs\n").unwrap_err();
+    ///
+    /// assert_eq!(
+    ///     conflict,
+    ///     "\
+    /// <<<<<<< HEAD
+    /// ours
+    /// ||||||| base
+    /// value
+    /// =======
+    /// theirs
+    /// >>>>>>> feature/login
+    /// ",
+    /// );
+    /// ```
+    pub fn set_labels(
+        &mut self,
+        ours: impl Into<String>,
+        original: impl Into<String>,
+        theirs: impl Into<String>,
+    ) -> &mut Self {
+        self.set_ours_label(ours)
+            .set_original_label(original)
+            .set_theirs_label(theirs)
+    }
+
+    /// Set the label written after the `<<<<<<<` conflict marker
+    pub fn set_ours_label(&mut self, label: impl Into<String>) -> &mut Self {
+        self.ours_label = label.into();
+        self
+    }
+
+    /// Set the label written after the `|||||||` conflict marker
+    ///
+    /// This marker is only written with [`ConflictStyle::Diff3`] and
+    /// [`ConflictStyle::ZealousDiff3`].
+    pub fn set_original_label(&mut self, label: impl Into<String>) -> &mut Self {
+        self.original_label = label.into();
+        self
+    }
+
+    /// Set the label written after the `>>>>>>>` conflict marker
+    pub fn set_theirs_label(&mut self, label: impl Into<String>) -> &mut Self {
+        self.theirs_label = label.into();
+        self
+    }
+
     /// Merge two files, given a common ancestor, based on the configured options
     pub fn merge<'a>(
         &self,
@@ -179,28 +542,7 @@ impl MergeOptions {
         ours: &'a str,
         theirs: &'a str,
     ) -> Result<String, String> {
-        let mut classifier = Classifier::default();
-        let (ancestor_lines, ancestor_ids) = classifier.classify_lines(ancestor);
-        let (our_lines, our_ids) = classifier.classify_lines(ours);
-        let (their_lines, their_ids) = classifier.classify_lines(theirs);
-
-        let opts = DiffOptions::default();
-        let our_solution = opts.diff_slice(&ancestor_ids, &our_ids);
-        let their_solution = opts.diff_slice(&ancestor_ids, &their_ids);
-
-        let merged = merge_solutions(&our_solution, &their_solution);
-        let mut merge = diff3_range_to_merge_range(&merged);
-
-        cleanup_conflicts(&mut merge);
-
-        output_result(
-            &ancestor_lines,
-            &our_lines,
-            &their_lines,
-            &merge,
-            self.conflict_marker_length,
-            self.style,
-        )
+        self.structured_merge(ancestor, ours, theirs).render(self)
     }
 
     /// Perform a 3-way merge between potentially non-utf8 texts
@@ -210,28 +552,137 @@ impl MergeOptions {
         ours: &'a [u8],
         theirs: &'a [u8],
     ) -> Result<Vec<u8>, Vec<u8>> {
-        let mut classifier = Classifier::default();
+        self.structured_merge(ancestor, ours, theirs).render(self)
+    }
+
+    /// Merge two files, given a common ancestor, without rendering conflicts
+    ///
+    /// Returns the merged file as a sequence of chunks which merged cleanly and conflicts, which
+    /// can be rendered with [`MergeResult::render`].
+    /// See [`MergeResult`] for an example.
+    pub fn structured_merge<'a, T: Text + ?Sized>(
+        &self,
+        ancestor: &'a T,
+        ours: &'a T,
+        theirs: &'a T,
+    ) -> MergeResult<'a, T> {
+        let opts = &self.diff_options;
+        let ignore_cr_at_eol = opts.ignore_cr_at_eol();
+        let mut classifier = opts.classifier();
         let (ancestor_lines, ancestor_ids) = classifier.classify_lines(ancestor);
         let (our_lines, our_ids) = classifier.classify_lines(ours);
         let (their_lines, their_ids) = classifier.classify_lines(theirs);
 
-        let opts = DiffOptions::default();
         let our_solution = opts.diff_slice(&ancestor_ids, &our_ids);
         let their_solution = opts.diff_slice(&ancestor_ids, &their_ids);
 
         let merged = merge_solutions(&our_solution, &their_solution);
-        let mut merge = diff3_range_to_merge_range(&merged);
+        let merge = diff3_range_to_merge_range(&merged);
+        let mut merge = locate_ranges(&merge, &ancestor_ids, &our_ids, &their_ids);
 
-        cleanup_conflicts(&mut merge);
+        cleanup_conflicts(&mut merge, self.level);
+        if let MergeLevel::Zealous | MergeLevel::ZealousAlnum = self.level {
+            merge = refine_conflicts(opts, &merge, &our_lines, self.level);
+        }
 
-        output_result_bytes(
+        let mut result = merge_result(
             &ancestor_lines,
             &our_lines,
             &their_lines,
             &merge,
-            self.conflict_marker_length,
-            self.style,
-        )
+            ignore_cr_at_eol || opts.whitespace() != Whitespace::Exact,
+            ignore_cr_at_eol.then(|| LineEnding::detect(ours)),
+        );
+        if let Some(favor) = self.favor {
+            result.resolve_conflicts(favor);
+        }
+        result
+    }
+
+    /// Merge trees of files, given a common ancestor
+    ///
+    /// Each tree maps paths to the contents of the files at those paths. A file which only one
+    /// side changed, added or deleted is taken from that side, and files which both sides changed
+    /// are merged with [`merge_bytes`](Self::merge_bytes), based on the configured options. Files
+    /// added by both sides are merged as if they were added to an empty file, and binary files
+    /// which both sides changed are never merged. See [`TreeMergeStatus`] for how each kind of
+    /// conflict is written to the merged tree, and [`TreeMergeResult`] for an example.
+    ///
+    /// If a [rename threshold](Self::set_rename_threshold) is set, files which one side renamed
+    /// are merged with the other side's version of the file at its old path, and written to the
+    /// new path.
+    pub fn merge_trees<K, V>(
+        &self,
+        ancestor: &BTreeMap<K, V>,
+        ours: &BTreeMap<K, V>,
+        theirs: &BTreeMap<K, V>,
+    ) -> TreeMergeResult<K>
+    where
+        K: Ord + Clone,
+        V: AsRef<[u8]>,
+    {
+        tree::merge_trees(self, ancestor, ours, theirs)
+    }
+
+    /// Parse a file containing conflict markers, like those written by a failed merge
+    ///
+    /// Conflict markers are recognized by their length, which must match the configured
+    /// conflict marker length. The rest of the options are ignored, and any labels after the
+    /// markers are skipped. See [`ConflictedFile`] for an example.
+    pub fn parse_conflicts<'a, T: Text + ?Sized>(
+        &self,
+        text: &'a T,
+    ) -> Result<ConflictedFile<'a, T>, ParseConflictsError> {
+        ConflictedFile::parse(text, self.conflict_marker_length)
+    }
+
+    /// Apply a `Patch` to a base image, falling back to a three-way merge, based on the
+    /// configured options, for hunks which fail to apply cleanly
+    ///
+    /// See [`apply_three_way`] for details.
+    ///
+    /// [`apply_three_way`]: crate::apply_three_way
+    pub fn apply_three_way(
+        &self,
+        base_image: &str,
+        patch: &Patch<'_, str>,
+    ) -> Result<String, String> {
+        let options = self.apply_options();
+        if let Ok(patched) = options.apply(base_image, patch) {
+            return Ok(patched);
+        }
+
+        let (ancestor, theirs) = three_way_images(base_image, patch, &options);
+        self.merge(&ancestor.concat(), base_image, &theirs.concat())
+    }
+
+    /// Apply a non-utf8 `Patch` to a base image, falling back to a three-way merge, based on the
+    /// configured options, for hunks which fail to apply cleanly
+    ///
+    /// See [`apply_three_way`] for details.
+    ///
+    /// [`apply_three_way`]: crate::apply_three_way
+    pub fn apply_three_way_bytes(
+        &self,
+        base_image: &[u8],
+        patch: &Patch<'_, [u8]>,
+    ) -> Result<Vec<u8>, Vec<u8>> {
+        let options = self.apply_options();
+        if let Ok(patched) = options.apply_bytes(base_image, patch) {
+            return Ok(patched);
+        }
+
+        let (ancestor, theirs) = three_way_images(base_image, patch, &options);
+        self.merge_bytes(&ancestor.concat(), base_image, &theirs.concat())
+    }
+
+    // The options used to apply a patch before falling back to a three-way merge
+    fn apply_options(&self) -> ApplyOptions {
+        let mut options = ApplyOptions::new();
+        options
+            .set_ignore_cr_at_eol(self.diff_options.ignore_cr_at_eol())
+            .set_unidiff_zero(self.unidiff_zero);
+        options
     }
 }
 
@@ -524,18 +975,60 @@ fn create_merge_range<'ancestor, 'ours, 'theirs, T: ?Sized + SliceLike>(
     }
 }
 
+// Gives each range of `solution` the offset at which it appears in its file. Empty ranges are
+// otherwise created without one, but refining conflicts needs to join neighbouring ranges.
+fn locate_ranges<'a>(
+    solution: &[MergeRange<'_, '_, '_, [u64]>],
+    ancestor: &'a [u64],
+    ours: &'a [u64],
+    theirs: &'a [u64],
+) -> Vec<MergeRange<'a, 'a, 'a, [u64]>> {
+    // The index of the line following the last range seen of each file
+    let (mut ancestor_pos, mut ours_pos, mut theirs_pos) = (0, 0, 0);
+    let locate = |pos: &mut usize, ids: &'a [u64], range: Range<'_, [u64]>| {
+        let start = if range.is_empty() {
+            *pos
+        } else {
+            range.offset()
+        };
+        *pos = start + range.len();
+        Range::new(ids, start..*pos)
+    };
+
+    solution
+        .iter()
+        .map(|&merge_range| match merge_range {
+            MergeRange::Equal(a, o, t) | MergeRange::Conflict(a, o, t) => {
+                let a = locate(&mut ancestor_pos, ancestor, a);
+                let o = locate(&mut ours_pos, ours, o);
+                let t = locate(&mut theirs_pos, theirs, t);
+                if let MergeRange::Equal(..) = merge_range {
+                    MergeRange::Equal(a, o, t)
+                } else {
+                    MergeRange::Conflict(a, o, t)
+                }
+            }
+            MergeRange::Ours(o) => MergeRange::Ours(locate(&mut ours_pos, ours, o)),
+            MergeRange::Theirs(t) => MergeRange::Theirs(locate(&mut theirs_pos, theirs, t)),
+            MergeRange::Both(o, t) => MergeRange::Both(
+                locate(&mut ours_pos, ours, o),
+                locate(&mut theirs_pos, theirs, t),
+            ),
+        })
+        .collect()
+}
+
 fn cleanup_conflicts<'ancestor, 'ours, 'theirs, T: ?Sized + SliceLike + PartialEq>(
     solution: &mut [MergeRange<'ancestor, 'ours, 'theirs, T>],
+    level: MergeLevel,
 ) {
     let mut pointer = 0;
 
-    // TODO this could probably be more sophisticated:
-    // e.g. run the diff algorithm on the conflict area
     while let Some(&merge) = solution.get(pointer) {
         if let MergeRange::Conflict(ancestor, ours, theirs) = merge {
             // If the ranges in the conflict end up being the same on both sides then we can
             // eliminate the conflict
-            if ours.as_slice() == theirs.as_slice() {
+            if ours.as_slice() == theirs.as_slice() && level != MergeLevel::Minimal {
                 solution[pointer] = MergeRange::Both(ours, theirs);
             // If either ours or theirs exactly matches ancestor then we can also eliminate the
             // conflict
@@ -549,148 +1042,190 @@ fn cleanup_conflicts<'ancestor, 'ours, 'theirs, T: ?Sized + SliceLike + PartialE
     }
 }
 
-fn output_result<'a, T: ?Sized>(
-    ancestor: &[&'a str],
-    ours: &[&'a str],
-    theirs: &[&'a str],
-    merge: &[MergeRange<T>],
-    marker_len: usize,
-    style: ConflictStyle,
-) -> Result<String, String> {
-    let mut conflicts = 0;
-    let mut output = String::new();
-
-    for merge_range in merge {
+// Splits each conflict into the regions where ours and theirs differ, separated by the lines they
+// agree on, and then combines conflicts which are only separated by a few lines
+fn refine_conflicts<'a, T: ?Sized + Text>(
+    opts: &DiffOptions,
+    solution: &[MergeRange<'a, 'a, 'a, [u64]>],
+    our_lines: &[&T],
+    level: MergeLevel,
+) -> Vec<MergeRange<'a, 'a, 'a, [u64]>> {
+    let mut refined = Vec::with_capacity(solution.len());
+    for &merge_range in solution {
         match merge_range {
-            MergeRange::Equal(range, ..) => {
-                output.extend(ancestor[range.range()].iter().copied());
+            // There's nothing to refine if one side is empty
+            MergeRange::Conflict(ancestor, ours, theirs)
+                if !ours.is_empty() && !theirs.is_empty() =>
+            {
+                refine_conflict(opts, &mut refined, ancestor, ours, theirs)
             }
-            MergeRange::Conflict(ancestor_range, ours_range, theirs_range) => {
-                add_conflict_marker(&mut output, '<', marker_len, Some("ours"));
-                output.extend(ours[ours_range.range()].iter().copied());
+            _ => refined.push(merge_range),
+        }
+    }
 
-                if let ConflictStyle::Diff3 = style {
-                    add_conflict_marker(&mut output, '|', marker_len, Some("original"));
-                    output.extend(ancestor[ancestor_range.range()].iter().copied());
+    let mut combined: Vec<MergeRange<'a, 'a, 'a, [u64]>> = Vec::with_capacity(refined.len());
+    for merge_range in refined {
+        // Join a conflict onto the previous one if only lines which are the same on both sides
+        // separate them, and there are few enough of them
+        if let MergeRange::Conflict(ancestor, ours, theirs) = merge_range {
+            let gap = combined
+                .iter()
+                .rev()
+                .take_while(|range| matches!(range, MergeRange::Equal(..) | MergeRange::Both(..)))
+                .count();
+            if let Some(&MergeRange::Conflict(prev_ancestor, prev_ours, prev_theirs)) =
+                combined.iter().rev().nth(gap)
+            {
+                let lines = &our_lines[prev_ours.offset() + prev_ours.len()..ours.offset()];
+                let contains_alnum = || {
+                    lines
+                        .iter()
+                        .any(|line| line.as_bytes().iter().any(u8::is_ascii_alphanumeric))
+                };
+                if lines.len() <= 3 || (level == MergeLevel::ZealousAlnum && !contains_alnum()) {
+                    combined.truncate(combined.len() - gap - 1);
+                    combined.push(MergeRange::Conflict(
+                        join_ranges(prev_ancestor, ancestor),
+                        join_ranges(prev_ours, ours),
+                        join_ranges(prev_theirs, theirs),
+                    ));
+                    continue;
                 }
-
-                add_conflict_marker(&mut output, '=', marker_len, None);
-                output.extend(theirs[theirs_range.range()].iter().copied());
-                add_conflict_marker(&mut output, '>', marker_len, Some("theirs"));
-                conflicts += 1;
-            }
-            MergeRange::Ours(range) => {
-                output.extend(ours[range.range()].iter().copied());
-            }
-            MergeRange::Theirs(range) => {
-                output.extend(theirs[range.range()].iter().copied());
-            }
-            MergeRange::Both(range, _) => {
-                output.extend(ours[range.range()].iter().copied());
             }
         }
+        combined.push(merge_range);
     }
 
-    if conflicts != 0 {
-        Err(output)
-    } else {
-        Ok(output)
-    }
+    combined
 }
 
-fn add_conflict_marker(
-    output: &mut String,
-    marker: char,
-    marker_len: usize,
-    filename: Option<&str>,
+// Splits a conflict into smaller conflicts by diffing ours against theirs
+fn refine_conflict<'a>(
+    opts: &DiffOptions,
+    refined: &mut Vec<MergeRange<'a, 'a, 'a, [u64]>>,
+    ancestor: Range<'a, [u64]>,
+    ours: Range<'a, [u64]>,
+    theirs: Range<'a, [u64]>,
 ) {
-    for _ in 0..marker_len {
-        output.push(marker);
-    }
+    // The lines of ancestor and ours which match, used to find the lines of the ancestor which
+    // each smaller conflict replaces
+    let matches: Vec<(usize, usize)> = opts
+        .diff_slice(ancestor.as_slice(), ours.as_slice())
+        .into_iter()
+        .filter_map(|range| match range {
+            DiffRange::Equal(a, o) => Some(a.range().zip(o.range())),
+            DiffRange::Delete(_) | DiffRange::Insert(_) => None,
+        })
+        .flatten()
+        .collect();
+
+    let diff = opts.diff_slice(ours.as_slice(), theirs.as_slice());
+    let (mut our_pos, mut their_pos, mut ancestor_pos) = (0, 0, 0);
+    // Where the current run of differing lines started in ours and theirs
+    let mut start = None;
+    for range in diff.iter().map(Some).chain([None]) {
+        // A run of differing lines ends at the next equal lines or the end of the diff
+        let run = match range {
+            None | Some(DiffRange::Equal(..)) => start.take(),
+            Some(DiffRange::Delete(_) | DiffRange::Insert(_)) => None,
+        };
+        if let Some((our_start, their_start)) = run {
+            let ancestor_start = matches
+                .iter()
+                .rev()
+                .find(|&&(_, o)| o < our_start)
+                .map_or(0, |&(a, _)| a + 1)
+                .max(ancestor_pos);
+            ancestor_pos = matches
+                .iter()
+                .find(|&&(_, o)| o >= our_pos)
+                .map_or(ancestor.len(), |&(a, _)| a)
+                .max(ancestor_start);
+            refined.push(MergeRange::Conflict(
+                ancestor.slice(ancestor_start..ancestor_pos),
+                ours.slice(our_start..our_pos),
+                theirs.slice(their_start..their_pos),
+            ));
+        }
 
-    if let Some(filename) = filename {
-        output.push(' ');
-        output.push_str(filename);
+        match range {
+            Some(DiffRange::Equal(our_range, their_range)) => {
+                refined.push(MergeRange::Both(
+                    ours.slice(our_range.range()),
+                    theirs.slice(their_range.range()),
+                ));
+                our_pos += our_range.len();
+                their_pos += their_range.len();
+            }
+            Some(DiffRange::Delete(range)) => {
+                start.get_or_insert((our_pos, their_pos));
+                our_pos += range.len();
+            }
+            Some(DiffRange::Insert(range)) => {
+                start.get_or_insert((our_pos, their_pos));
+                their_pos += range.len();
+            }
+            None => {}
+        }
     }
-    output.push('\n');
 }
 
-fn output_result_bytes<'a, T: ?Sized>(
-    ancestor: &[&'a [u8]],
-    ours: &[&'a [u8]],
-    theirs: &[&'a [u8]],
-    merge: &[MergeRange<T>],
-    marker_len: usize,
-    style: ConflictStyle,
-) -> Result<Vec<u8>, Vec<u8>> {
-    let mut conflicts = 0;
-    let mut output: Vec<u8> = Vec::new();
+// Joins two ranges of the same file, and everything between them
+fn join_ranges<'a>(first: Range<'a, [u64]>, second: Range<'a, [u64]>) -> Range<'a, [u64]> {
+    Range::new(
+        first.inner(),
+        first.offset()..second.offset() + second.len(),
+    )
+}
 
-    for merge_range in merge {
-        match merge_range {
-            MergeRange::Equal(range, ..) => {
-                ancestor[range.range()]
-                    .iter()
-                    .for_each(|line| output.extend_from_slice(line));
+// Collects the lines of each range of a merge into chunks
+//
+// If lines were normalized before being compared, lines which are equal are taken from ours.
+//
+// The ranges must have been located with `locate_ranges`.
+fn merge_result<'a, T: ?Sized + Text>(
+    ancestor: &[&'a T],
+    ours: &[&'a T],
+    theirs: &[&'a T],
+    merge: &[MergeRange<[u64]>],
+    normalized: bool,
+    line_ending: Option<LineEnding>,
+) -> MergeResult<'a, T> {
+    let mut chunks = Vec::new();
+    for &merge_range in merge {
+        let chunk = match merge_range {
+            MergeRange::Equal(ancestor_range, ours_range, _) => {
+                // Lines are only equal modulo their line endings so use our lines to preserve
+                // our line ending style
+                if normalized {
+                    MergeChunk::Resolved(MergeSide::Ours, ours[ours_range.range()].to_vec())
+                } else {
+                    MergeChunk::Resolved(
+                        MergeSide::Ancestor,
+                        ancestor[ancestor_range.range()].to_vec(),
+                    )
+                }
             }
             MergeRange::Conflict(ancestor_range, ours_range, theirs_range) => {
-                add_conflict_marker_bytes(&mut output, b'<', marker_len, Some(b"ours"));
-                ours[ours_range.range()]
-                    .iter()
-                    .for_each(|line| output.extend_from_slice(line));
-
-                if let ConflictStyle::Diff3 = style {
-                    add_conflict_marker_bytes(&mut output, b'|', marker_len, Some(b"original"));
-                    ancestor[ancestor_range.range()]
-                        .iter()
-                        .for_each(|line| output.extend_from_slice(line));
-                }
-
-                add_conflict_marker_bytes(&mut output, b'=', marker_len, None);
-                theirs[theirs_range.range()]
-                    .iter()
-                    .for_each(|line| output.extend_from_slice(line));
-                add_conflict_marker_bytes(&mut output, b'>', marker_len, Some(b"theirs"));
-                conflicts += 1;
+                let conflict = MergeConflict::new(
+                    (
+                        ancestor_range.offset(),
+                        ancestor[ancestor_range.range()].to_vec(),
+                    ),
+                    (ours_range.offset(), ours[ours_range.range()].to_vec()),
+                    (theirs_range.offset(), theirs[theirs_range.range()].to_vec()),
+                );
+                MergeChunk::Conflict(conflict)
             }
-            MergeRange::Ours(range) => {
-                ours[range.range()]
-                    .iter()
-                    .for_each(|line| output.extend_from_slice(line));
+            MergeRange::Ours(range) | MergeRange::Both(range, _) => {
+                MergeChunk::Resolved(MergeSide::Ours, ours[range.range()].to_vec())
             }
             MergeRange::Theirs(range) => {
-                theirs[range.range()]
-                    .iter()
-                    .for_each(|line| output.extend_from_slice(line));
-            }
-            MergeRange::Both(range, _) => {
-                ours[range.range()]
-                    .iter()
-                    .for_each(|line| output.extend_from_slice(line));
+                MergeChunk::Resolved(MergeSide::Theirs, theirs[range.range()].to_vec())
             }
-        }
-    }
-
-    if conflicts != 0 {
-        Err(output)
-    } else {
-        Ok(output)
-    }
-}
-
-fn add_conflict_marker_bytes(
-    output: &mut Vec<u8>,
-    marker: u8,
-    marker_len: usize,
-    filename: Option<&[u8]>,
-) {
-    for _ in 0..marker_len {
-        output.push(marker);
+        };
+        chunks.push(chunk);
     }
 
-    if let Some(filename) = filename {
-        output.push(b' ');
-        output.extend_from_slice(filename);
-    }
-    output.push(b'\n');
+    MergeResult::new(chunks, line_ending)
 }
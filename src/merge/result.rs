@@ -0,0 +1,337 @@
+//! The structured result of a three-way merge.
+
+use alloc::vec::Vec;
+use core::mem;
+
+use super::ConflictStyle;
+use super::MergeFavor;
+use super::MergeOptions;
+use crate::utils::LineEnding;
+use crate::utils::Text;
+use crate::utils::split_line_ending;
+
+/// The result of a three-way merge, made up of chunks which merged cleanly and conflicts.
+///
+/// Returned by [`MergeOptions::structured_merge`]. Unlike [`MergeOptions::merge`], which renders
+/// conflicts with conflict markers, this leaves the lines of each side of a conflict separate so
+/// that they can be inspected or resolved. [`render`](Self::render) produces the same output as
+/// `merge` with any [`ConflictStyle`].
+///
+/// ```
+/// use diffy::ConflictStyle;
+/// use diffy::MergeOptions;
+///
+/// let original = "alpha\nbeta\ngamma\n";
+/// let ours = "alpha\nBeta\ngamma\n";
+/// let theirs = "alpha\nBETA\ngamma\n";
+///
+/// let result = MergeOptions::new().structured_merge(original, ours, theirs);
+/// assert!(result.has_conflicts());
+///
+/// let conflict = result.conflicts().next().unwrap();
+/// assert_eq!(conflict.ancestor(), ["beta\n"]);
+/// assert_eq!(conflict.ours(), ["Beta\n"]);
+/// assert_eq!(conflict.theirs(), ["BETA\n"]);
+/// assert_eq!(conflict.ours_start(), 2);
+///
+/// let mut options = MergeOptions::new();
+/// options.set_conflict_style(ConflictStyle::Merge);
+/// assert_eq!(
+///     result.render(&options).unwrap_err(),
+///     "\
+/// alpha
+/// <<<<<<< ours
+/// Beta
+/// =======
+/// BETA
+/// >>>>>>> theirs
+/// gamma
+/// "
+/// );
+/// ```
+#[derive(Debug, PartialEq, Eq)]
+pub struct MergeResult<'a, T: ?Sized> {
+    chunks: Vec<MergeChunk<'a, T>>,
+    /// The number of conflicts resolved by favoring one or both sides
+    auto_resolved: usize,
+    /// The line ending to convert lines which aren't from `ours` to, if line endings were
+    /// ignored when merging
+    line_ending: Option<LineEnding>,
+}
+
+impl<'a, T: ?Sized> MergeResult<'a, T> {
+    pub(crate) fn new(chunks: Vec<MergeChunk<'a, T>>, line_ending: Option<LineEnding>) -> Self {
+        Self {
+            chunks,
+            auto_resolved: 0,
+            line_ending,
+        }
+    }
+
+    /// Replaces each conflict with the lines of the side or sides favored by `favor`
+    pub(crate) fn resolve_conflicts(&mut self, favor: MergeFavor) {
+        for chunk in mem::take(&mut self.chunks) {
+            let MergeChunk::Conflict(conflict) = chunk else {
+                self.chunks.push(chunk);
+                continue;
+            };
+
+            self.auto_resolved += 1;
+            let MergeConflict { ours, theirs, .. } = conflict;
+            if matches!(favor, MergeFavor::Ours | MergeFavor::Union) && !ours.lines.is_empty() {
+                self.chunks
+                    .push(MergeChunk::Resolved(MergeSide::Ours, ours.lines));
+            }
+            if matches!(favor, MergeFavor::Theirs | MergeFavor::Union) && !theirs.lines.is_empty() {
+                self.chunks
+                    .push(MergeChunk::Resolved(MergeSide::Theirs, theirs.lines));
+            }
+        }
+    }
+
+    /// Returns the chunks of the merged file, in order
+    ///
+    /// Consecutive chunks may both be resolved, for example when lines only changed by `ours`
+    /// are followed by lines which are the same in all three files.
+    pub fn chunks(&self) -> &[MergeChunk<'a, T>] {
+        &self.chunks
+    }
+
+    /// Returns an iterator over the conflicts of the merge
+    pub fn conflicts(&self) -> impl Iterator<Item = &MergeConflict<'a, T>> + '_ {
+        self.chunks.iter().filter_map(|chunk| match chunk {
+            MergeChunk::Resolved(..) => None,
+            MergeChunk::Conflict(conflict) => Some(conflict),
+        })
+    }
+
+    /// Returns `true` if the merge has any conflicts
+    pub fn has_conflicts(&self) -> bool {
+        self.conflicts().next().is_some()
+    }
+
+    /// Returns the number of conflicts which were resolved automatically
+    ///
+    /// See [`MergeOptions::set_favor`].
+    pub fn auto_resolved_conflicts(&self) -> usize {
+        self.auto_resolved
+    }
+}
+
+impl<T: Text + ?Sized> MergeResult<'_, T> {
+    /// Renders the merged file, marking conflicts with conflict markers
+    ///
+    /// The conflict style, conflict marker length and labels are taken from `options`, and the
+    /// rest of its settings are ignored.
+    ///
+    /// Returns `Ok` if there were no conflicts, or `Err` with the conflicting regions marked
+    /// otherwise, like [`MergeOptions::merge`].
+    pub fn render(&self, options: &MergeOptions) -> Result<T::Owned, T::Owned> {
+        let mut output = Vec::new();
+
+        for chunk in &self.chunks {
+            match chunk {
+                MergeChunk::Resolved(MergeSide::Ours, lines) => lines
+                    .iter()
+                    .for_each(|line| output.extend_from_slice(line.as_bytes())),
+                MergeChunk::Resolved(_, lines) => self.extend_lines(&mut output, lines),
+                MergeChunk::Conflict(conflict) => {
+                    self.render_conflict(&mut output, conflict, options)
+                }
+            }
+        }
+
+        let output = T::owned_from_bytes(output).expect("merged lines and labels are valid text");
+        if self.has_conflicts() {
+            Err(output)
+        } else {
+            Ok(output)
+        }
+    }
+
+    fn render_conflict(
+        &self,
+        output: &mut Vec<u8>,
+        conflict: &MergeConflict<'_, T>,
+        options: &MergeOptions,
+    ) {
+        let (mut ours, mut theirs) = (conflict.ours(), conflict.theirs());
+
+        // Move the lines both sides start and end with outside of the conflict markers
+        let (mut prefix, mut suffix): (&[&T], &[&T]) = (&[], &[]);
+        if let ConflictStyle::ZealousDiff3 = options.style {
+            let prefix_len = ours
+                .iter()
+                .zip(theirs)
+                .take_while(|(a, b)| self.lines_equal(a, b))
+                .count();
+            (prefix, ours) = ours.split_at(prefix_len);
+            theirs = &theirs[prefix_len..];
+
+            let suffix_len = ours
+                .iter()
+                .rev()
+                .zip(theirs.iter().rev())
+                .take_while(|(a, b)| self.lines_equal(a, b))
+                .count();
+            (ours, suffix) = ours.split_at(ours.len() - suffix_len);
+            theirs = &theirs[..theirs.len() - suffix_len];
+        }
+
+        let marker_len = options.conflict_marker_length;
+        prefix
+            .iter()
+            .for_each(|line| output.extend_from_slice(line.as_bytes()));
+
+        self.add_conflict_marker(output, b'<', marker_len, &options.ours_label);
+        ours.iter()
+            .for_each(|line| output.extend_from_slice(line.as_bytes()));
+
+        if let ConflictStyle::Diff3 | ConflictStyle::ZealousDiff3 = options.style {
+            self.add_conflict_marker(output, b'|', marker_len, &options.original_label);
+            self.extend_lines(output, conflict.ancestor());
+        }
+
+        self.add_conflict_marker(output, b'=', marker_len, "");
+        self.extend_lines(output, theirs);
+        self.add_conflict_marker(output, b'>', marker_len, &options.theirs_label);
+
+        suffix
+            .iter()
+            .for_each(|line| output.extend_from_slice(line.as_bytes()));
+    }
+
+    fn lines_equal(&self, a: &T, b: &T) -> bool {
+        if self.line_ending.is_some() {
+            split_line_ending(a) == split_line_ending(b)
+        } else {
+            a == b
+        }
+    }
+
+    // Appends lines which aren't from `ours`, converting them to our line endings if needed
+    fn extend_lines(&self, output: &mut Vec<u8>, lines: &[&T]) {
+        match self.line_ending {
+            Some(line_ending) => {
+                for line in lines {
+                    line_ending.push_line_bytes(output, line.as_bytes());
+                }
+            }
+            None => lines
+                .iter()
+                .for_each(|line| output.extend_from_slice(line.as_bytes())),
+        }
+    }
+
+    fn add_conflict_marker(
+        &self,
+        output: &mut Vec<u8>,
+        marker: u8,
+        marker_len: usize,
+        label: &str,
+    ) {
+        for _ in 0..marker_len {
+            output.push(marker);
+        }
+
+        if !label.is_empty() {
+            output.push(b' ');
+            output.extend_from_slice(label.as_bytes());
+        }
+        output.extend_from_slice(self.line_ending.map_or("\n", LineEnding::as_str).as_bytes());
+    }
+}
+
+/// A chunk of a [`MergeResult`].
+#[derive(Debug, PartialEq, Eq)]
+pub enum MergeChunk<'a, T: ?Sized> {
+    /// Lines which merged cleanly, and the file they were taken from.
+    Resolved(MergeSide, Vec<&'a T>),
+
+    /// Lines which were changed differently by both sides.
+    Conflict(MergeConflict<'a, T>),
+}
+
+/// One of the three files of a merge.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MergeSide {
+    /// The common ancestor of `ours` and `theirs`.
+    Ancestor,
+
+    /// Our side of the merge.
+    Ours,
+
+    /// Their side of the merge.
+    Theirs,
+}
+
+/// A region which was changed differently by both sides of a merge.
+#[derive(Debug, PartialEq, Eq)]
+pub struct MergeConflict<'a, T: ?Sized> {
+    ancestor: ConflictLines<'a, T>,
+    ours: ConflictLines<'a, T>,
+    theirs: ConflictLines<'a, T>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct ConflictLines<'a, T: ?Sized> {
+    start: usize,
+    lines: Vec<&'a T>,
+}
+
+impl<'a, T: ?Sized> MergeConflict<'a, T> {
+    /// Constructs a conflict from the lines of each file and the indexes (starting at 0) of the
+    /// first of them
+    pub(crate) fn new(
+        ancestor: (usize, Vec<&'a T>),
+        ours: (usize, Vec<&'a T>),
+        theirs: (usize, Vec<&'a T>),
+    ) -> Self {
+        let lines = |(index, lines): (usize, Vec<&'a T>)| ConflictLines {
+            start: index + 1,
+            lines,
+        };
+        Self {
+            ancestor: lines(ancestor),
+            ours: lines(ours),
+            theirs: lines(theirs),
+        }
+    }
+
+    /// Returns the lines of the common ancestor in the conflicting region
+    pub fn ancestor(&self) -> &[&'a T] {
+        &self.ancestor.lines
+    }
+
+    /// Returns our lines in the conflicting region
+    pub fn ours(&self) -> &[&'a T] {
+        &self.ours.lines
+    }
+
+    /// Returns their lines in the conflicting region
+    pub fn theirs(&self) -> &[&'a T] {
+        &self.theirs.lines
+    }
+
+    /// Returns the line number (starting at 1) of the first line of the region in the common
+    /// ancestor
+    ///
+    /// If the region is empty, this is the line number of the line which follows it.
+    pub fn ancestor_start(&self) -> usize {
+        self.ancestor.start
+    }
+
+    /// Returns the line number (starting at 1) of the first line of the region in `ours`
+    ///
+    /// If the region is empty, this is the line number of the line which follows it.
+    pub fn ours_start(&self) -> usize {
+        self.ours.start
+    }
+
+    /// Returns the line number (starting at 1) of the first line of the region in `theirs`
+    ///
+    /// If the region is empty, this is the line number of the line which follows it.
+    pub fn theirs_start(&self) -> usize {
+        self.theirs.start
+    }
+}
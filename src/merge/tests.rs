@@ -1,4 +1,10 @@
 use super::*;
+use crate::DiffAlgorithm;
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::string::ToString;
+use alloc::vec;
+use alloc::vec::Vec;
 
 macro_rules! assert_merge {
     ($original:ident, $ours:ident, $theirs:ident, $kind:ident($expected:expr_2021), $msg:literal $(,)?) => {
@@ -404,3 +410,993 @@ fn delete_and_insert_conflict() {
         "MergeRange (Theirs::delete, Ours::insert) conflict"
     );
 }
+
+#[test]
+fn ignore_cr_at_eol() {
+    let original = "alpha\nbeta\ngamma\n";
+    let ours = "alpha\r\nbeta\r\ngamma\r\n";
+    let theirs = "alpha\nBETA\ngamma\n";
+
+    // Without normalization every line conflicts
+    assert!(merge(original, ours, theirs).is_err());
+
+    let mut options = MergeOptions::new();
+    options.set_ignore_cr_at_eol(true);
+    assert_eq!(
+        options.merge(original, ours, theirs).unwrap(),
+        "alpha\r\nBETA\r\ngamma\r\n"
+    );
+    assert_eq!(
+        options
+            .merge_bytes(original.as_bytes(), ours.as_bytes(), theirs.as_bytes())
+            .unwrap(),
+        b"alpha\r\nBETA\r\ngamma\r\n"
+    );
+
+    let ours = "alpha\r\nBeta\r\ngamma\r\n";
+    let expected = "\
+alpha\r
+<<<<<<< ours\r
+Beta\r
+||||||| original\r
+beta\r
+=======\r
+BETA\r
+>>>>>>> theirs\r
+gamma\r
+";
+    assert_eq!(options.merge(original, ours, theirs).unwrap_err(), expected);
+    assert_eq!(
+        options
+            .merge_bytes(original.as_bytes(), ours.as_bytes(), theirs.as_bytes())
+            .unwrap_err(),
+        expected.as_bytes()
+    );
+}
+
+#[test]
+fn conflict_labels() {
+    let original = "alpha\nbeta\ngamma\n";
+    let ours = "alpha\nBeta\ngamma\n";
+    let theirs = "alpha\nBETA\ngamma\n";
+
+    let mut options = MergeOptions::new();
+    options.set_labels("HEAD", "base", "feature/login");
+    let expected = "\
+alpha
+<<<<<<< HEAD
+Beta
+||||||| base
+beta
+=======
+BETA
+>>>>>>> feature/login
+gamma
+";
+    assert_eq!(options.merge(original, ours, theirs).unwrap_err(), expected);
+    assert_eq!(
+        options
+            .merge_bytes(original.as_bytes(), ours.as_bytes(), theirs.as_bytes())
+            .unwrap_err(),
+        expected.as_bytes()
+    );
+
+    options
+        .set_conflict_style(ConflictStyle::Merge)
+        .set_ours_label("")
+        .set_theirs_label("other");
+    let expected = "\
+alpha
+<<<<<<<
+Beta
+=======
+BETA
+>>>>>>> other
+gamma
+";
+    assert_eq!(options.merge(original, ours, theirs).unwrap_err(), expected);
+    assert_eq!(
+        options
+            .merge_bytes(original.as_bytes(), ours.as_bytes(), theirs.as_bytes())
+            .unwrap_err(),
+        expected.as_bytes()
+    );
+}
+
+#[test]
+fn zealous_diff3() {
+    let original = "alpha\nbeta\ngamma\n";
+    let ours = "alpha\nfirst\nBeta\nmiddle\nlast\ngamma\n";
+    let theirs = "alpha\nfirst\nBETA\nmiddle\nlast\ngamma\n";
+
+    let mut options = MergeOptions::new();
+    options.set_conflict_style(ConflictStyle::ZealousDiff3);
+    let expected = "\
+alpha
+first
+<<<<<<< ours
+Beta
+||||||| original
+beta
+=======
+BETA
+>>>>>>> theirs
+middle
+last
+gamma
+";
+    assert_eq!(options.merge(original, ours, theirs).unwrap_err(), expected);
+    assert_eq!(
+        options
+            .merge_bytes(original.as_bytes(), ours.as_bytes(), theirs.as_bytes())
+            .unwrap_err(),
+        expected.as_bytes()
+    );
+
+    // The whole conflict is kept with diff3
+    options.set_conflict_style(ConflictStyle::Diff3);
+    let expected = "\
+alpha
+<<<<<<< ours
+first
+Beta
+middle
+last
+||||||| original
+beta
+=======
+first
+BETA
+middle
+last
+>>>>>>> theirs
+gamma
+";
+    assert_eq!(options.merge(original, ours, theirs).unwrap_err(), expected);
+}
+
+#[test]
+fn zealous_diff3_one_side_is_prefix() {
+    let original = "alpha\nbeta\ngamma\n";
+    let ours = "alpha\nshared\ngamma\n";
+    let theirs = "alpha\nshared\nextra\ngamma\n";
+
+    let mut options = MergeOptions::new();
+    options.set_conflict_style(ConflictStyle::ZealousDiff3);
+    let expected = "\
+alpha
+shared
+<<<<<<< ours
+||||||| original
+beta
+=======
+extra
+>>>>>>> theirs
+gamma
+";
+    assert_eq!(options.merge(original, ours, theirs).unwrap_err(), expected);
+}
+
+#[test]
+fn structured_merge() {
+    let original = "a\nb\nc\nd\n";
+    let ours = "A\nb\nd\n";
+    let theirs = "a\nb\nC\nd\ne\n";
+
+    let result = MergeOptions::new().structured_merge(original, ours, theirs);
+    let conflict = MergeConflict::new((2, vec!["c\n"]), (2, vec![]), (2, vec!["C\n"]));
+    assert_eq!(
+        result.chunks(),
+        [
+            MergeChunk::Resolved(MergeSide::Ours, vec!["A\n"]),
+            MergeChunk::Resolved(MergeSide::Ancestor, vec!["b\n"]),
+            MergeChunk::Conflict(conflict),
+            MergeChunk::Resolved(MergeSide::Ancestor, vec!["d\n"]),
+            MergeChunk::Resolved(MergeSide::Theirs, vec!["e\n"]),
+        ]
+    );
+
+    let conflict = result.conflicts().next().unwrap();
+    assert_eq!(conflict.ancestor_start(), 3);
+    assert_eq!(conflict.ours_start(), 3);
+    assert_eq!(conflict.theirs_start(), 3);
+    assert!(conflict.ours().is_empty());
+}
+
+#[test]
+fn structured_merge_renders_like_merge() {
+    let cases = [
+        ("a\nb\nc\n", "a\nB\nc\n", "a\nb\nC\n"),
+        ("a\nb\nc\n", "a\nB\nc\n", "a\nBB\nc\n"),
+        ("a\nb\nc\n", "x\ny\nb\nz\n", "x\ny\nB\nz\n"),
+        ("", "a\n", "b\n"),
+        ("a\r\nb\r\n", "a\r\nB\r\n", "A\nb\n"),
+    ];
+    let styles = [
+        ConflictStyle::Merge,
+        ConflictStyle::Diff3,
+        ConflictStyle::ZealousDiff3,
+    ];
+
+    for (original, ours, theirs) in cases {
+        for style in styles {
+            for ignore_cr_at_eol in [false, true] {
+                let mut options = MergeOptions::new();
+                options
+                    .set_conflict_style(style)
+                    .set_ignore_cr_at_eol(ignore_cr_at_eol);
+
+                let result = options.structured_merge(original, ours, theirs);
+                assert_eq!(
+                    result.render(&options),
+                    options.merge(original, ours, theirs)
+                );
+                assert_eq!(
+                    result.has_conflicts(),
+                    options.merge(original, ours, theirs).is_err()
+                );
+
+                let result = options.structured_merge(
+                    original.as_bytes(),
+                    ours.as_bytes(),
+                    theirs.as_bytes(),
+                );
+                assert_eq!(
+                    result.render(&options),
+                    options.merge_bytes(original.as_bytes(), ours.as_bytes(), theirs.as_bytes())
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn favor() {
+    let original = "a\nb\nc\nd\ne\n";
+    let ours = "a\nB\nc\nd\n";
+    let theirs = "a\nBB\nc\nd\nE\n";
+
+    let cases = [
+        (MergeFavor::Ours, "a\nB\nc\nd\n"),
+        (MergeFavor::Theirs, "a\nBB\nc\nd\nE\n"),
+        (MergeFavor::Union, "a\nB\nBB\nc\nd\nE\n"),
+    ];
+    for (favor, expected) in cases {
+        let mut options = MergeOptions::new();
+        options.set_favor(Some(favor));
+        assert_eq!(options.merge(original, ours, theirs).unwrap(), expected);
+        assert_eq!(
+            options
+                .merge_bytes(original.as_bytes(), ours.as_bytes(), theirs.as_bytes())
+                .unwrap(),
+            expected.as_bytes()
+        );
+
+        let result = options.structured_merge(original, ours, theirs);
+        assert!(!result.has_conflicts());
+        assert_eq!(result.auto_resolved_conflicts(), 2);
+    }
+
+    // Clean merges don't resolve any conflicts
+    let mut options = MergeOptions::new();
+    options.set_favor(Some(MergeFavor::Union));
+    let result = options.structured_merge(original, ours, original);
+    assert_eq!(result.auto_resolved_conflicts(), 0);
+    assert_eq!(result.render(&options).unwrap(), ours);
+
+    let result = MergeOptions::new().structured_merge(original, ours, theirs);
+    assert_eq!(result.auto_resolved_conflicts(), 0);
+    assert_eq!(result.conflicts().count(), 2);
+}
+
+#[test]
+fn level_minimal() {
+    let original = "a\nb\nc\n";
+    let ours = "a\nB\nc\n";
+
+    let mut options = MergeOptions::new();
+    options
+        .set_conflict_style(ConflictStyle::Merge)
+        .set_level(MergeLevel::Minimal);
+    assert_eq!(
+        options.merge(original, ours, ours).unwrap_err(),
+        "a\n<<<<<<< ours\nB\n=======\nB\n>>>>>>> theirs\nc\n"
+    );
+
+    options.set_level(MergeLevel::Eager);
+    assert_eq!(options.merge(original, ours, ours).unwrap(), ours);
+}
+
+#[test]
+fn level_zealous() {
+    let original = "a\nb\nc\nd\ne\n";
+    let ours = "a\nB\nc\nd\nE\n";
+    let theirs = "a\nb2\nc\nd\ne2\n";
+
+    // The conflicts at b and e are only separated by two lines, so they are combined
+    let mut options = MergeOptions::new();
+    let result = options.structured_merge(original, ours, theirs);
+    assert_eq!(result.conflicts().count(), 2);
+    options.set_level(MergeLevel::Zealous);
+    let result = options.structured_merge(original, ours, theirs);
+    let conflicts: Vec<_> = result.conflicts().collect();
+    assert_eq!(conflicts.len(), 1);
+    assert_eq!(conflicts[0].ancestor(), ["b\n", "c\n", "d\n", "e\n"]);
+    assert_eq!(conflicts[0].ours(), ["B\n", "c\n", "d\n", "E\n"]);
+
+    let original = "a\nz\n";
+    let ours = "a\n1\n2\n3\n4\nB\nz\n";
+    let theirs = "a\n1\n2\n3\n4\nC\nz\n";
+
+    options.set_level(MergeLevel::Eager);
+    let result = options.structured_merge(original, ours, theirs);
+    let conflict = result.conflicts().next().unwrap();
+    assert_eq!(conflict.ours(), ["1\n", "2\n", "3\n", "4\n", "B\n"]);
+
+    options.set_level(MergeLevel::Zealous);
+    let result = options.structured_merge(original, ours, theirs);
+    assert_eq!(
+        result.chunks(),
+        [
+            MergeChunk::Resolved(MergeSide::Ancestor, vec!["a\n"]),
+            MergeChunk::Resolved(MergeSide::Ours, vec!["1\n", "2\n", "3\n", "4\n"]),
+            MergeChunk::Conflict(MergeConflict::new(
+                (1, vec![]),
+                (5, vec!["B\n"]),
+                (5, vec!["C\n"])
+            )),
+            MergeChunk::Resolved(MergeSide::Ancestor, vec!["z\n"]),
+        ]
+    );
+}
+
+#[test]
+fn level_zealous_keeps_ancestor_lines() {
+    let original = "x\na\ny\nb\nz\n";
+    let ours = "x\nA\ny\nB\nz\n";
+    let theirs = "x\nAA\ny\nBB\nz\n";
+
+    // Both conflicts are kept together as they are separated by one line
+    let mut options = MergeOptions::new();
+    options.set_level(MergeLevel::Zealous);
+    let result = options.structured_merge(original, ours, theirs);
+    let conflicts: Vec<_> = result.conflicts().collect();
+    assert_eq!(conflicts.len(), 1);
+    assert_eq!(conflicts[0].ancestor(), ["a\n", "y\n", "b\n"]);
+
+    // Split one conflict which spans the whole file
+    let original = "a\nb\nc\nd\ne\nf\ng\n";
+    let ours = "A\nb\nc!\nd!\ne!\nf\nG\n";
+    let theirs = "AA\nb\nc!\nd!\ne!\nf\nGG\n";
+
+    let result = options.structured_merge(original, ours, theirs);
+    let conflicts: Vec<_> = result.conflicts().collect();
+    assert_eq!(conflicts.len(), 2);
+    assert_eq!(conflicts[0].ancestor(), ["a\n"]);
+    assert_eq!(conflicts[0].ours(), ["A\n"]);
+    assert_eq!(conflicts[1].ancestor(), ["g\n"]);
+    assert_eq!(conflicts[1].ancestor_start(), 7);
+    assert_eq!(conflicts[1].theirs(), ["GG\n"]);
+    assert_eq!(conflicts[1].theirs_start(), 7);
+}
+
+#[test]
+fn level_zealous_alnum() {
+    let original = "a\n}\n\n}\n\nb\n";
+    let ours = "A\n}\n\n}\n\nB\n";
+    let theirs = "AA\n}\n\n}\n\nBB\n";
+
+    let mut options = MergeOptions::new();
+    options.set_level(MergeLevel::Zealous);
+    let count = |options: &MergeOptions| {
+        options
+            .structured_merge(original, ours, theirs)
+            .conflicts()
+            .count()
+    };
+    assert_eq!(count(&options), 2);
+
+    options.set_level(MergeLevel::ZealousAlnum);
+    assert_eq!(count(&options), 1);
+}
+
+#[test]
+fn levels_random_edits() {
+    struct Rng(u64);
+    impl Rng {
+        fn next(&mut self, n: u64) -> u64 {
+            self.0 = self
+                .0
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (self.0 >> 33) % n
+        }
+
+        fn edit(&mut self, text: &str) -> String {
+            let mut lines: Vec<String> = text.lines().map(|l| l.into()).collect();
+            for _ in 0..=self.next(6) {
+                let pos = self.next(lines.len() as u64 + 1) as usize;
+                match self.next(3) {
+                    0 => lines.insert(pos, alloc::format!("new {}", self.next(4))),
+                    1 if pos < lines.len() => {
+                        lines.remove(pos);
+                    }
+                    _ if pos < lines.len() => lines[pos].push('!'),
+                    _ => {}
+                }
+            }
+            lines.into_iter().map(|l| l + "\n").collect()
+        }
+    }
+
+    let original: String = (0..30).map(|i| alloc::format!("line {i}\n")).collect();
+    let original_lines: Vec<&str> = original.lines().collect();
+    let mut rng = Rng(7);
+    let levels = [
+        MergeLevel::Minimal,
+        MergeLevel::Eager,
+        MergeLevel::Zealous,
+        MergeLevel::ZealousAlnum,
+    ];
+
+    for _ in 0..300 {
+        let ours = rng.edit(&original);
+        let theirs = rng.edit(&original);
+        let our_lines: Vec<&str> = ours.lines().collect();
+        let their_lines: Vec<&str> = theirs.lines().collect();
+
+        for favor in [MergeFavor::Ours, MergeFavor::Theirs] {
+            let mut expected = None;
+            for level in levels {
+                let mut options = MergeOptions::new();
+                options.set_level(level);
+
+                // Every conflict is at the right lines of each file
+                let result = options.structured_merge(&*original, &ours, &theirs);
+                let mut ancestor_end = 0;
+                for conflict in result.conflicts() {
+                    let check = |lines: &[&str], start: usize, expected: &[&str]| {
+                        let lines: Vec<_> = lines[start - 1..][..expected.len()]
+                            .iter()
+                            .map(|line| alloc::format!("{line}\n"))
+                            .collect();
+                        assert_eq!(lines, expected);
+                    };
+                    check(
+                        &original_lines,
+                        conflict.ancestor_start(),
+                        conflict.ancestor(),
+                    );
+                    check(&our_lines, conflict.ours_start(), conflict.ours());
+                    check(&their_lines, conflict.theirs_start(), conflict.theirs());
+                    assert!(conflict.ancestor_start() > ancestor_end);
+                    ancestor_end = conflict.ancestor_start() + conflict.ancestor().len() - 1;
+                }
+
+                // Refining conflicts doesn't change what each side of them is
+                options.set_favor(Some(favor));
+                let merged = options.merge(&original, &ours, &theirs).unwrap();
+                assert_eq!(merged, *expected.get_or_insert_with(|| merged.clone()));
+            }
+        }
+    }
+}
+
+#[test]
+fn parse_conflicts() {
+    let text = "\
+a
+<<<<<<< ours
+B
+=======
+BB
+>>>>>>> theirs
+c
+<<<<<<< ours
+||||||| original
+d
+=======
+D
+>>>>>>> theirs
+=======
+";
+    let file = MergeOptions::new().parse_conflicts(text).unwrap();
+    assert_eq!(
+        file.chunks(),
+        [
+            ConflictedChunk::Resolved(vec!["a\n"]),
+            ConflictedChunk::Conflict(MergeConflict::new(
+                (1, vec![]),
+                (1, vec!["B\n"]),
+                (1, vec!["BB\n"])
+            )),
+            ConflictedChunk::Resolved(vec!["c\n"]),
+            ConflictedChunk::Conflict(MergeConflict::new(
+                (2, vec!["d\n"]),
+                (3, vec![]),
+                (3, vec!["D\n"])
+            )),
+            ConflictedChunk::Resolved(vec!["=======\n"]),
+        ]
+    );
+    assert_eq!(file.ours(), "a\nB\nc\n=======\n");
+    assert_eq!(file.theirs(), "a\nBB\nc\nD\n=======\n");
+
+    let file = MergeOptions::new().parse_conflicts("a\nb\n").unwrap();
+    assert!(!file.has_conflicts());
+    assert_eq!(file.ours(), "a\nb\n");
+}
+
+#[test]
+fn parse_conflicts_marker_length() {
+    let text = "<<<<< ours\r\na\r\n=====\r\n<<<<<<< b\r\n>>>>>\r\n";
+
+    let mut options = MergeOptions::new();
+    options.set_conflict_marker_length(5);
+    let file = options.parse_conflicts(text).unwrap();
+    assert_eq!(file.ours(), "a\r\n");
+    assert_eq!(file.theirs(), "<<<<<<< b\r\n");
+
+    let file = options.parse_conflicts(text.as_bytes()).unwrap();
+    assert_eq!(file.theirs(), b"<<<<<<< b\r\n");
+
+    let error = MergeOptions::new().parse_conflicts(text).unwrap_err();
+    assert_eq!(error.line(), 4);
+    assert_eq!(
+        error.to_string(),
+        "error parsing conflict markers at line 4: unterminated conflict"
+    );
+}
+
+#[test]
+fn parse_conflicts_errors() {
+    let cases = [
+        ("<<<<<<<\na\n<<<<<<<\n", 3, "nested conflict"),
+        ("<<<<<<<\na\n>>>>>>>\n", 3, "unexpected `>` marker"),
+        ("<<<<<<<\n=======\n|||||||\n", 3, "unexpected `|` marker"),
+        ("a\n<<<<<<<\n=======\n", 2, "unterminated conflict"),
+    ];
+
+    for (text, line, message) in cases {
+        let error = MergeOptions::new().parse_conflicts(text).unwrap_err();
+        assert_eq!(error.line(), line, "{text:?}");
+        assert_eq!(
+            error.to_string(),
+            alloc::format!("error parsing conflict markers at line {line}: {message}")
+        );
+    }
+}
+
+#[test]
+fn parse_rendered_conflicts() {
+    let original = "a\nb\nc\nd\ne\nf\ng\n";
+    let ours = "a\nB\nc\nd\ne\nF\ng\n";
+    let theirs = "a\nBB\nc\nd\ne\nFF\ng\n";
+
+    let styles = [
+        ConflictStyle::Merge,
+        ConflictStyle::Diff3,
+        ConflictStyle::ZealousDiff3,
+    ];
+    for style in styles {
+        let mut options = MergeOptions::new();
+        options
+            .set_conflict_style(style)
+            .set_conflict_marker_length(9)
+            .set_labels("HEAD", "", "feature");
+        let rendered = options.merge(original, ours, theirs).unwrap_err();
+
+        let file = options.parse_conflicts(&*rendered).unwrap();
+        assert_eq!(file.ours(), ours);
+        assert_eq!(file.theirs(), theirs);
+
+        let result = options.structured_merge(original, ours, theirs);
+        for (parsed, merged) in file.conflicts().zip(result.conflicts()) {
+            assert_eq!(parsed.ours(), merged.ours());
+            assert_eq!(parsed.theirs(), merged.theirs());
+            assert_eq!(parsed.ours_start(), merged.ours_start());
+            assert_eq!(parsed.theirs_start(), merged.theirs_start());
+            if let ConflictStyle::Diff3 = style {
+                assert_eq!(parsed.ancestor(), merged.ancestor());
+                assert_eq!(parsed.ancestor_start(), merged.ancestor_start());
+            }
+        }
+        assert_eq!(file.conflicts().count(), 2);
+    }
+}
+
+#[test]
+fn ignore_whitespace() {
+    let original = "fn main() {\n  a();\n  b();\n}\n";
+    let ours = "fn main() {\n    a();\n    b();\n}\n";
+    let theirs = "fn main() {\n  a();\n  b(1);\n}\n";
+
+    let mut options = MergeOptions::new();
+    assert!(options.merge(original, ours, theirs).is_err());
+
+    options.set_ignore_space_change(true);
+    let expected = "fn main() {\n    a();\n  b(1);\n}\n";
+    assert_eq!(options.merge(original, ours, theirs).unwrap(), expected);
+    assert_eq!(
+        options
+            .merge_bytes(original.as_bytes(), ours.as_bytes(), theirs.as_bytes())
+            .unwrap(),
+        expected.as_bytes()
+    );
+
+    // Whitespace changes made by theirs are dropped
+    assert_eq!(options.merge(original, theirs, ours).unwrap(), theirs);
+
+    // Indentation added to an unindented line isn't a change in the amount of whitespace
+    let ours = "fn main() {\n  a();\n  b();\n  }\n";
+    let theirs = "fn main() {\n  a();\n  b(1);\n}\n";
+    assert!(options.merge(original, ours, theirs).is_err());
+    options.set_ignore_all_space(true);
+    assert_eq!(
+        options.merge(original, ours, theirs).unwrap(),
+        "fn main() {\n  a();\n  b(1);\n  }\n"
+    );
+}
+
+#[test]
+fn diff_algorithm() {
+    let original = r#"#include <stdio.h>
+
+// Frobs foo heartily
+int frobnitz(int foo)
+{
+    int i;
+    for(i = 0; i < 10; i++)
+    {
+        printf("Your answer is: ");
+        printf("%d\n", foo);
+    }
+}
+
+int fact(int n)
+{
+    if(n > 1)
+    {
+        return fact(n-1) * n;
+    }
+    return 1;
+}
+
+int main(int argc, char **argv)
+{
+    frobnitz(fact(10));
+}
+"#;
+    let ours = r#"#include <stdio.h>
+
+int fib(int n)
+{
+    if(n > 2)
+    {
+        return fib(n-1) + fib(n-2);
+    }
+    return 1;
+}
+
+// Frobs foo heartily
+int frobnitz(int foo)
+{
+    int i;
+    for(i = 0; i < 10; i++)
+    {
+        printf("%d\n", foo);
+    }
+}
+
+int main(int argc, char **argv)
+{
+    frobnitz(fib(10));
+}
+"#;
+    let theirs = original.replace("int i;", "int j;");
+    let expected = ours.replace("int i;", "int j;");
+
+    // Myers' algorithm matches up the lines of frobnitz in ours with those of fact
+    let mut options = MergeOptions::new();
+    assert!(options.merge(original, ours, &theirs).is_err());
+
+    for algorithm in [DiffAlgorithm::Patience, DiffAlgorithm::Histogram] {
+        let mut diff_options = DiffOptions::new();
+        diff_options.set_algorithm(algorithm);
+        options.set_diff_options(diff_options);
+        assert_eq!(
+            options.merge(original, ours, &theirs).unwrap(),
+            expected,
+            "{algorithm:?}"
+        );
+    }
+
+    // Whitespace handling is taken from the diff options
+    let mut diff_options = DiffOptions::new();
+    diff_options.set_ignore_all_space(true);
+    options.set_diff_options(diff_options);
+    assert_eq!(
+        options.merge("a\nb\n", "a \nb\n", "a\nB\n").unwrap(),
+        "a \nB\n"
+    );
+}
+
+#[test]
+fn merge_trees() {
+    let ancestor: BTreeMap<&str, &[u8]> = BTreeMap::from([
+        ("unchanged", &b"a\n"[..]),
+        ("ours", b"a\n"),
+        ("theirs", b"a\n"),
+        ("merged", b"a\nb\nc\n"),
+        ("conflict", b"a\n"),
+        ("deleted_by_both", b"a\n"),
+        ("deleted_by_ours", b"a\n"),
+        ("deleted_by_theirs", b"a\n"),
+        ("binary", b"a\0\n"),
+    ]);
+    let ours: BTreeMap<&str, &[u8]> = BTreeMap::from([
+        ("unchanged", &b"a\n"[..]),
+        ("ours", b"A\n"),
+        ("theirs", b"a\n"),
+        ("merged", b"A\nb\nc\n"),
+        ("conflict", b"A\n"),
+        ("deleted_by_theirs", b"A\n"),
+        ("binary", b"A\0\n"),
+        ("added_by_both", b"x\n"),
+        ("added_by_ours", b"x\n"),
+        ("add_add", b"x\n"),
+    ]);
+    let theirs: BTreeMap<&str, &[u8]> = BTreeMap::from([
+        ("unchanged", &b"a\n"[..]),
+        ("ours", b"a\n"),
+        ("theirs", b"A\n"),
+        ("merged", b"a\nb\nC\n"),
+        ("conflict", b"a!\n"),
+        ("deleted_by_ours", b"A\n"),
+        ("binary", b"a\0!\n"),
+        ("added_by_both", b"x\n"),
+        ("add_add", b"y\n"),
+    ]);
+
+    let mut options = MergeOptions::new();
+    options.set_conflict_style(ConflictStyle::Merge);
+    let result = options.merge_trees(&ancestor, &ours, &theirs);
+
+    let statuses: Vec<_> = result
+        .statuses()
+        .iter()
+        .map(|(&path, &status)| (path, status))
+        .collect();
+    assert_eq!(
+        statuses,
+        [
+            ("add_add", TreeMergeStatus::AddAdd),
+            ("added_by_both", TreeMergeStatus::Both),
+            ("added_by_ours", TreeMergeStatus::Ours),
+            ("binary", TreeMergeStatus::Binary),
+            ("conflict", TreeMergeStatus::Conflict),
+            ("deleted_by_both", TreeMergeStatus::Both),
+            ("deleted_by_ours", TreeMergeStatus::DeletedByOurs),
+            ("deleted_by_theirs", TreeMergeStatus::DeletedByTheirs),
+            ("merged", TreeMergeStatus::Merged),
+            ("ours", TreeMergeStatus::Ours),
+            ("theirs", TreeMergeStatus::Theirs),
+            ("unchanged", TreeMergeStatus::Unchanged),
+        ]
+    );
+
+    let merged: Vec<_> = result
+        .merged()
+        .iter()
+        .map(|(&path, contents)| (path, String::from_utf8_lossy(contents).into_owned()))
+        .collect();
+    let expected = [
+        ("add_add", "<<<<<<< ours\nx\n=======\ny\n>>>>>>> theirs\n"),
+        ("added_by_both", "x\n"),
+        ("added_by_ours", "x\n"),
+        ("binary", "A\0\n"),
+        ("conflict", "<<<<<<< ours\nA\n=======\na!\n>>>>>>> theirs\n"),
+        ("deleted_by_ours", "A\n"),
+        ("deleted_by_theirs", "A\n"),
+        ("merged", "A\nb\nC\n"),
+        ("ours", "A\n"),
+        ("theirs", "A\n"),
+        ("unchanged", "a\n"),
+    ]
+    .map(|(path, contents)| (path, contents.to_string()));
+    assert_eq!(merged, expected);
+
+    assert!(result.has_conflicts());
+    let conflicts: Vec<_> = result.conflicts().map(|(&path, _)| path).collect();
+    assert_eq!(
+        conflicts,
+        [
+            "add_add",
+            "binary",
+            "conflict",
+            "deleted_by_ours",
+            "deleted_by_theirs"
+        ]
+    );
+
+    // Conflicts in text files can still be resolved automatically
+    options.set_favor(Some(MergeFavor::Ours));
+    let result = options.merge_trees(&ancestor, &ours, &theirs);
+    assert_eq!(result.status(&"conflict"), Some(TreeMergeStatus::Merged));
+    assert_eq!(result.status(&"add_add"), Some(TreeMergeStatus::Merged));
+    assert_eq!(result.merged()[&"add_add"], b"x\n");
+    assert_eq!(result.status(&"binary"), Some(TreeMergeStatus::Binary));
+}
+
+#[test]
+fn merge_trees_renames() {
+    let lines = |n: usize, changed: &str| -> String {
+        (0..n)
+            .map(|i| alloc::format!("line {i}{}\n", if i == 5 { changed } else { "" }))
+            .collect()
+    };
+    let (original, edited, conflicting) = (lines(10, ""), lines(10, "!"), lines(10, "?"));
+
+    let ancestor = BTreeMap::from([
+        ("renamed_by_ours", original.clone()),
+        ("renamed_by_theirs", original.replace("line", "LINE")),
+        ("rename_rename", original.replace("line", "row")),
+        ("rename_conflict", original.replace("line", "ROW")),
+        ("renamed_deleted", original.replace("line", "item")),
+        ("moved_deleted", original.replace("line", "entry")),
+        ("deleted_moved", original.replace("line", "field")),
+        ("collision", original.replace("line", "ITEM")),
+        ("binary", "\0binary\n".to_string()),
+    ]);
+    let ours = BTreeMap::from([
+        ("ours_new_path", original.clone()),
+        ("renamed_by_theirs", edited.replace("line", "LINE")),
+        ("rename_rename_ours", original.replace("line", "row")),
+        ("rename_conflict", conflicting.replace("line", "ROW")),
+        ("renamed_deleted_new", edited.replace("line", "item")),
+        ("moved_deleted_new", original.replace("line", "entry")),
+        ("collision_new", original.replace("line", "ITEM")),
+        ("binary_new", "\0binary\n".to_string()),
+    ]);
+    let theirs = BTreeMap::from([
+        ("renamed_by_ours", edited.clone()),
+        ("theirs_new_path", original.replace("line", "LINE")),
+        ("rename_rename_theirs", edited.replace("line", "row")),
+        ("rename_conflict_new", edited.replace("line", "ROW")),
+        ("deleted_moved_new", original.replace("line", "field")),
+        ("collision", original.replace("line", "ITEM")),
+        ("collision_new", "unrelated\n".to_string()),
+        ("binary", "\0binary\n".to_string()),
+    ]);
+
+    let mut options = MergeOptions::new();
+    options.set_rename_threshold(Some(50));
+    let result = options.merge_trees(&ancestor, &ours, &theirs);
+
+    let statuses: Vec<_> = result
+        .statuses()
+        .iter()
+        .map(|(&path, &status)| (path, status, result.renamed_from(&path).copied()))
+        .collect();
+    assert_eq!(
+        statuses,
+        [
+            ("binary_new", TreeMergeStatus::Unchanged, Some("binary")),
+            ("collision", TreeMergeStatus::Ours, None),
+            ("collision_new", TreeMergeStatus::AddAdd, None),
+            (
+                "deleted_moved_new",
+                TreeMergeStatus::RenameDelete,
+                Some("deleted_moved")
+            ),
+            (
+                "moved_deleted_new",
+                TreeMergeStatus::RenameDelete,
+                Some("moved_deleted")
+            ),
+            (
+                "ours_new_path",
+                TreeMergeStatus::Theirs,
+                Some("renamed_by_ours")
+            ),
+            (
+                "rename_conflict_new",
+                TreeMergeStatus::Conflict,
+                Some("rename_conflict")
+            ),
+            (
+                "rename_rename_ours",
+                TreeMergeStatus::RenameRename,
+                Some("rename_rename")
+            ),
+            (
+                "rename_rename_theirs",
+                TreeMergeStatus::RenameRename,
+                Some("rename_rename")
+            ),
+            (
+                "renamed_deleted_new",
+                TreeMergeStatus::RenameDelete,
+                Some("renamed_deleted")
+            ),
+            (
+                "theirs_new_path",
+                TreeMergeStatus::Ours,
+                Some("renamed_by_theirs")
+            ),
+        ]
+    );
+
+    let merged = result.merged();
+    assert_eq!(merged.len(), 10);
+    assert_eq!(merged[&"ours_new_path"], edited.as_bytes());
+    assert_eq!(
+        merged[&"theirs_new_path"],
+        edited.replace("line", "LINE").as_bytes()
+    );
+    assert_eq!(
+        merged[&"rename_rename_ours"],
+        edited.replace("line", "row").as_bytes()
+    );
+    assert_eq!(
+        merged[&"rename_rename_theirs"],
+        edited.replace("line", "row").as_bytes()
+    );
+    assert!(
+        String::from_utf8_lossy(&merged[&"rename_conflict_new"]).contains("<<<<<<< ours\nROW 5?")
+    );
+    assert_eq!(
+        merged[&"renamed_deleted_new"],
+        edited.replace("line", "item").as_bytes()
+    );
+    assert_eq!(
+        merged[&"moved_deleted_new"],
+        original.replace("line", "entry").as_bytes()
+    );
+    assert_eq!(
+        merged[&"deleted_moved_new"],
+        original.replace("line", "field").as_bytes()
+    );
+    assert_eq!(merged[&"binary_new"], b"\0binary\n");
+    assert!(!merged.contains_key(&"renamed_by_ours"));
+    assert!(!merged.contains_key(&"moved_deleted"));
+
+    // Files which aren't similar enough aren't renames
+    options.set_rename_threshold(Some(95));
+    let result = options.merge_trees(&ancestor, &ours, &theirs);
+    assert_eq!(
+        result.status(&"renamed_deleted"),
+        Some(TreeMergeStatus::Both)
+    );
+    assert_eq!(
+        result.status(&"renamed_deleted_new"),
+        Some(TreeMergeStatus::Ours)
+    );
+    assert_eq!(result.renamed_from(&"renamed_deleted_new"), None);
+    assert_eq!(
+        result.renamed_from(&"ours_new_path"),
+        Some(&"renamed_by_ours")
+    );
+
+    options.set_rename_threshold(None);
+    let result = options.merge_trees(&ancestor, &ours, &theirs);
+    assert_eq!(result.renamed_from(&"binary_new"), None);
+}
+
+#[test]
+fn merge_trees_renamed_by_both() {
+    let ancestor = BTreeMap::from([("old", "a\nb\nc\n"), ("other", "x\ny\nz\n")]);
+    let ours = BTreeMap::from([("new", "A\nb\nc\n"), ("other", "x\ny\nz\n")]);
+    let theirs = BTreeMap::from([("new", "a\nb\nC\n")]);
+
+    let mut options = MergeOptions::new();
+    options.set_rename_threshold(Some(50));
+    let result = options.merge_trees(&ancestor, &ours, &theirs);
+
+    assert!(!result.has_conflicts());
+    assert_eq!(result.status(&"new"), Some(TreeMergeStatus::Merged));
+    assert_eq!(result.renamed_from(&"new"), Some(&"old"));
+    assert_eq!(result.status(&"other"), Some(TreeMergeStatus::Theirs));
+    let merged: Vec<_> = result.into_merged().into_iter().collect();
+    assert_eq!(merged, [("new", b"A\nb\nC\n".to_vec())]);
+}
@@ -0,0 +1,436 @@
+//! Three-way merges of whole trees of files.
+
+use alloc::collections::BTreeMap;
+use alloc::collections::BTreeSet;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use super::MergeOptions;
+use crate::range::DiffRange;
+
+/// The number of bytes at the start of a file searched for a NUL byte to detect binary files,
+/// the same as git
+const BINARY_DETECTION_LEN: usize = 8000;
+
+/// The result of merging trees of files with [`MergeOptions::merge_trees`].
+///
+/// Contains the merged tree, where files with conflicts are written with conflict markers like
+/// [`MergeOptions::merge_bytes`] when possible, and how each path was merged.
+///
+/// ```
+/// use std::collections::BTreeMap;
+///
+/// use diffy::MergeOptions;
+/// use diffy::TreeMergeStatus;
+///
+/// let ancestor = BTreeMap::from([("a.txt", "alpha\n"), ("b.txt", "beta\n")]);
+/// let ours = BTreeMap::from([("a.txt", "ALPHA\n"), ("b.txt", "beta\n")]);
+/// let theirs = BTreeMap::from([("a.txt", "alpha\n"), ("c.txt", "gamma\n")]);
+///
+/// let result = MergeOptions::new().merge_trees(&ancestor, &ours, &theirs);
+/// assert!(!result.has_conflicts());
+/// assert_eq!(result.status(&"a.txt"), Some(TreeMergeStatus::Ours));
+/// assert_eq!(result.status(&"b.txt"), Some(TreeMergeStatus::Theirs));
+///
+/// let merged: Vec<_> = result.merged().iter().collect();
+/// assert_eq!(
+///     merged,
+///     [
+///         (&"a.txt", &b"ALPHA\n".to_vec()),
+///         (&"c.txt", &b"gamma\n".to_vec())
+///     ]
+/// );
+/// ```
+///
+/// [`MergeOptions::merge_bytes`]: crate::MergeOptions::merge_bytes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TreeMergeResult<K> {
+    merged: BTreeMap<K, Vec<u8>>,
+    statuses: BTreeMap<K, TreeMergeStatus>,
+    /// The path in the ancestor of each file of the merged tree which was renamed
+    renames: BTreeMap<K, K>,
+}
+
+impl<K: Ord> TreeMergeResult<K> {
+    /// Returns the merged tree
+    pub fn merged(&self) -> &BTreeMap<K, Vec<u8>> {
+        &self.merged
+    }
+
+    /// Consumes the result, returning the merged tree
+    pub fn into_merged(self) -> BTreeMap<K, Vec<u8>> {
+        self.merged
+    }
+
+    /// Returns how each path of the ancestor, ours or theirs was merged, ordered by path
+    ///
+    /// Files which were renamed are only listed under their new path.
+    pub fn statuses(&self) -> &BTreeMap<K, TreeMergeStatus> {
+        &self.statuses
+    }
+
+    /// Returns how `path` was merged, if it is a path of the ancestor, ours or theirs
+    pub fn status(&self, path: &K) -> Option<TreeMergeStatus> {
+        self.statuses.get(path).copied()
+    }
+
+    /// Returns the path in the ancestor of the file at `path` in the merged tree, if it was
+    /// renamed
+    ///
+    /// See [`MergeOptions::set_rename_threshold`].
+    ///
+    /// [`MergeOptions::set_rename_threshold`]: crate::MergeOptions::set_rename_threshold
+    pub fn renamed_from(&self, path: &K) -> Option<&K> {
+        self.renames.get(path)
+    }
+
+    /// Returns an iterator over the paths with conflicts and their statuses, ordered by path
+    pub fn conflicts(&self) -> impl Iterator<Item = (&K, TreeMergeStatus)> + '_ {
+        self.statuses
+            .iter()
+            .map(|(path, &status)| (path, status))
+            .filter(|(_, status)| status.is_conflict())
+    }
+
+    /// Returns `true` if any path has a conflict
+    pub fn has_conflicts(&self) -> bool {
+        self.conflicts().next().is_some()
+    }
+}
+
+/// How a single path was merged by [`MergeOptions::merge_trees`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TreeMergeStatus {
+    /// Neither side changed the file.
+    Unchanged,
+
+    /// Only `ours` changed the file, so it was taken from `ours`, or removed if `ours` deleted
+    /// it.
+    Ours,
+
+    /// Only `theirs` changed the file, so it was taken from `theirs`, or removed if `theirs`
+    /// deleted it.
+    Theirs,
+
+    /// Both sides made the same change, such as deleting the file or adding the same contents.
+    Both,
+
+    /// Both sides changed the file, and the changes merged cleanly.
+    Merged,
+
+    /// Both sides changed the same lines of the file differently. The merged file contains
+    /// conflict markers.
+    Conflict,
+
+    /// Both sides added the file with different contents. The merged file contains conflict
+    /// markers, as if it was merged with an empty ancestor.
+    AddAdd,
+
+    /// `ours` deleted the file and `theirs` changed it. The merged file is taken from `theirs`.
+    DeletedByOurs,
+
+    /// `theirs` deleted the file and `ours` changed it. The merged file is taken from `ours`.
+    DeletedByTheirs,
+
+    /// Both sides changed or added the file differently and at least one version is binary, so
+    /// it couldn't be merged. The merged file is taken from `ours`.
+    Binary,
+
+    /// Both sides renamed the file to different paths. The file is merged as usual, and written
+    /// to both paths.
+    RenameRename,
+
+    /// One side renamed the file and the other deleted it. The merged file is taken from the
+    /// side which renamed it, and written to its new path.
+    RenameDelete,
+}
+
+impl TreeMergeStatus {
+    /// Returns `true` if the path has a conflict which needs to be resolved by hand
+    pub fn is_conflict(self) -> bool {
+        match self {
+            Self::Unchanged | Self::Ours | Self::Theirs | Self::Both | Self::Merged => false,
+            Self::Conflict
+            | Self::AddAdd
+            | Self::DeletedByOurs
+            | Self::DeletedByTheirs
+            | Self::Binary
+            | Self::RenameRename
+            | Self::RenameDelete => true,
+        }
+    }
+}
+
+pub(crate) fn merge_trees<K, V>(
+    options: &MergeOptions,
+    ancestor: &BTreeMap<K, V>,
+    ours: &BTreeMap<K, V>,
+    theirs: &BTreeMap<K, V>,
+) -> TreeMergeResult<K>
+where
+    K: Ord + Clone,
+    V: AsRef<[u8]>,
+{
+    let (our_renames, their_renames) = match options.rename_threshold {
+        Some(threshold) => {
+            let our_renames = find_renames(options, threshold, ancestor, ours);
+            let their_renames = find_renames(options, threshold, ancestor, theirs);
+            (
+                without_collisions(&our_renames, &their_renames, theirs),
+                without_collisions(&their_renames, &our_renames, ours),
+            )
+        }
+        None => (BTreeMap::new(), BTreeMap::new()),
+    };
+    let our_targets: BTreeSet<&K> = our_renames.values().copied().collect();
+    let their_targets: BTreeSet<&K> = their_renames.values().copied().collect();
+
+    let mut result = TreeMergeResult {
+        merged: BTreeMap::new(),
+        statuses: BTreeMap::new(),
+        renames: BTreeMap::new(),
+    };
+    for path in ancestor.keys() {
+        let our_path = our_renames.get(path).copied().unwrap_or(path);
+        let their_path = their_renames.get(path).copied().unwrap_or(path);
+        result.merge_file(
+            options,
+            file(ancestor, path),
+            file(ours, our_path),
+            file(theirs, their_path),
+        );
+    }
+
+    // Files added by ours, possibly also added by theirs
+    let added =
+        |path: &K, targets: &BTreeSet<&K>| !ancestor.contains_key(path) && !targets.contains(path);
+    for (path, contents) in ours {
+        if added(path, &our_targets) {
+            let their_file = file(theirs, path).filter(|_| added(path, &their_targets));
+            result.merge_file(options, None, Some((path, contents.as_ref())), their_file);
+        }
+    }
+    for (path, contents) in theirs {
+        if added(path, &their_targets) && !ours.contains_key(path) {
+            result.merge_file(options, None, None, Some((path, contents.as_ref())));
+        }
+    }
+
+    result
+}
+
+/// The path and contents of a file
+type File<'t, K> = (&'t K, &'t [u8]);
+
+fn file<'t, K: Ord, V: AsRef<[u8]>>(tree: &'t BTreeMap<K, V>, path: &K) -> Option<File<'t, K>> {
+    tree.get_key_value(path).map(file_ref)
+}
+
+fn file_ref<'t, K, V: AsRef<[u8]>>((path, contents): (&'t K, &'t V)) -> File<'t, K> {
+    (path, contents.as_ref())
+}
+
+impl<K: Ord + Clone> TreeMergeResult<K> {
+    // Merges the versions of a file, given the path and contents of each version which exists,
+    // and adds it to the result
+    fn merge_file<'t>(
+        &mut self,
+        options: &MergeOptions,
+        ancestor: Option<File<'t, K>>,
+        ours: Option<File<'t, K>>,
+        theirs: Option<File<'t, K>>,
+    ) {
+        let ancestor_path = ancestor.map(|(path, _)| path);
+        let renamed = |file: Option<File<'t, K>>| {
+            file.map(|(path, _)| path)
+                .filter(|&path| ancestor_path.is_some_and(|ancestor| ancestor != path))
+        };
+
+        let contents = |file: Option<File<'t, K>>| file.map(|(_, contents)| contents);
+        let (merged, mut status) = match (ours, theirs) {
+            // The file is kept at its new path even if it wasn't changed, like git does
+            (Some((_, contents)), None) if renamed(ours).is_some() => {
+                (Some(contents.to_vec()), TreeMergeStatus::RenameDelete)
+            }
+            (None, Some((_, contents))) if renamed(theirs).is_some() => {
+                (Some(contents.to_vec()), TreeMergeStatus::RenameDelete)
+            }
+            _ => merge_file(
+                options,
+                contents(ancestor),
+                contents(ours),
+                contents(theirs),
+            ),
+        };
+
+        let paths = match (renamed(ours), renamed(theirs)) {
+            (Some(ours), Some(theirs)) if ours != theirs => {
+                status = TreeMergeStatus::RenameRename;
+                vec![ours, theirs]
+            }
+            (Some(path), _) | (None, Some(path)) => vec![path],
+            (None, None) => {
+                let (path, _) = ancestor.or(ours).or(theirs).expect("file exists in a tree");
+                vec![path]
+            }
+        };
+
+        for path in paths {
+            if let Some(merged) = &merged {
+                self.merged.insert(path.clone(), merged.clone());
+                if let Some(ancestor_path) = ancestor_path.filter(|&ancestor| ancestor != path) {
+                    self.renames.insert(path.clone(), ancestor_path.clone());
+                }
+            }
+            self.statuses.insert(path.clone(), status);
+        }
+    }
+}
+
+// Finds the files of the ancestor which `side` renamed, returning the new path of each one
+//
+// Files are renamed if they were deleted by `side` and are similar enough to a file it added.
+// The most similar pairs of files are matched up first.
+fn find_renames<'t, K: Ord, V: AsRef<[u8]>>(
+    options: &MergeOptions,
+    threshold: usize,
+    ancestor: &'t BTreeMap<K, V>,
+    side: &'t BTreeMap<K, V>,
+) -> BTreeMap<&'t K, &'t K> {
+    // Empty files aren't considered, since they are all the same
+    let candidates = |from: &'t BTreeMap<K, V>, to: &BTreeMap<K, V>| -> Vec<File<'t, K>> {
+        from.iter()
+            .map(file_ref)
+            .filter(|(path, contents)| !to.contains_key(path) && !contents.is_empty())
+            .collect()
+    };
+    let deleted = candidates(ancestor, side);
+    let added = candidates(side, ancestor);
+    if deleted.is_empty() || added.is_empty() {
+        return BTreeMap::new();
+    }
+
+    let mut classifier = options.diff_options.classifier();
+    let mut lines = |files: &[File<'t, K>]| -> Vec<Vec<u64>> {
+        files
+            .iter()
+            .map(|(_, contents)| classifier.classify_lines(*contents).1)
+            .collect()
+    };
+    let (deleted_lines, added_lines) = (lines(&deleted), lines(&added));
+
+    let mut pairs = Vec::new();
+    for (i, (_, old)) in deleted.iter().enumerate() {
+        for (j, (_, new)) in added.iter().enumerate() {
+            let score = if old == new {
+                100
+            } else if is_binary(old) || is_binary(new) {
+                0
+            } else {
+                similarity(options, &deleted_lines[i], &added_lines[j], threshold)
+            };
+            if score >= threshold {
+                pairs.push((score, i, j));
+            }
+        }
+    }
+    pairs.sort_by(|a, b| b.0.cmp(&a.0).then((a.1, a.2).cmp(&(b.1, b.2))));
+
+    let mut renames = BTreeMap::new();
+    let (mut deleted_used, mut added_used) = (vec![false; deleted.len()], vec![false; added.len()]);
+    for (_, i, j) in pairs {
+        if !deleted_used[i] && !added_used[j] {
+            (deleted_used[i], added_used[j]) = (true, true);
+            renames.insert(deleted[i].0, added[j].0);
+        }
+    }
+    renames
+}
+
+// Returns the percentage of lines `old` and `new` have in common, relative to the larger of
+// them, or 0 if it is clearly below `threshold`
+fn similarity(options: &MergeOptions, old: &[u64], new: &[u64], threshold: usize) -> usize {
+    let max_len = old.len().max(new.len());
+    if max_len == 0 || old.len().min(new.len()) * 100 < threshold * max_len {
+        return 0;
+    }
+
+    let common: usize = options
+        .diff_options
+        .diff_slice(old, new)
+        .iter()
+        .map(|range| match range {
+            DiffRange::Equal(range, _) => range.len(),
+            DiffRange::Delete(_) | DiffRange::Insert(_) => 0,
+        })
+        .sum();
+    common * 100 / max_len
+}
+
+// Drops the renames to paths where the other side added a file, unless the other side renamed
+// the same file there too
+fn without_collisions<'t, K: Ord, V>(
+    renames: &BTreeMap<&'t K, &'t K>,
+    other_renames: &BTreeMap<&'t K, &'t K>,
+    other: &BTreeMap<K, V>,
+) -> BTreeMap<&'t K, &'t K> {
+    renames
+        .iter()
+        .filter(|&(from, to)| !other.contains_key(*to) || other_renames.get(from) == Some(to))
+        .map(|(&from, &to)| (from, to))
+        .collect()
+}
+
+// Merges the versions of a single file, where `None` means the file doesn't exist
+fn merge_file(
+    options: &MergeOptions,
+    ancestor: Option<&[u8]>,
+    ours: Option<&[u8]>,
+    theirs: Option<&[u8]>,
+) -> (Option<Vec<u8>>, TreeMergeStatus) {
+    let to_vec = |contents: Option<&[u8]>| contents.map(<[u8]>::to_vec);
+
+    if ours == theirs {
+        let status = if ours == ancestor {
+            TreeMergeStatus::Unchanged
+        } else {
+            TreeMergeStatus::Both
+        };
+        return (to_vec(ours), status);
+    }
+    if ours == ancestor {
+        return (to_vec(theirs), TreeMergeStatus::Theirs);
+    }
+    if theirs == ancestor {
+        return (to_vec(ours), TreeMergeStatus::Ours);
+    }
+
+    // Both sides changed the file differently
+    let (ours, theirs) = match (ours, theirs) {
+        (Some(ours), Some(theirs)) => (ours, theirs),
+        (None, theirs) => return (to_vec(theirs), TreeMergeStatus::DeletedByOurs),
+        (ours, None) => return (to_vec(ours), TreeMergeStatus::DeletedByTheirs),
+    };
+    if [ancestor, Some(ours), Some(theirs)]
+        .into_iter()
+        .flatten()
+        .any(is_binary)
+    {
+        return (Some(ours.to_vec()), TreeMergeStatus::Binary);
+    }
+
+    match (
+        ancestor,
+        options.merge_bytes(ancestor.unwrap_or_default(), ours, theirs),
+    ) {
+        (_, Ok(merged)) => (Some(merged), TreeMergeStatus::Merged),
+        (Some(_), Err(merged)) => (Some(merged), TreeMergeStatus::Conflict),
+        (None, Err(merged)) => (Some(merged), TreeMergeStatus::AddAdd),
+    }
+}
+
+// Returns `true` if `contents` looks like the contents of a binary file, which is the case if it
+// has a NUL byte near the start like git checks
+fn is_binary(contents: &[u8]) -> bool {
+    contents[..contents.len().min(BINARY_DETECTION_LEN)].contains(&0)
+}
@@ -0,0 +1,193 @@
+//! Combine sequential patches without the files they apply to.
+
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use core::fmt;
+use core::ops::Range;
+
+use super::Hunk;
+use super::HunkRange;
+use super::Line;
+use super::hunk_lines_count;
+
+/// An error returned when composing two patches with [`Patch::compose`] fails.
+///
+/// [`Patch::compose`]: crate::Patch::compose
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ComposeError {
+    line: usize,
+}
+
+impl ComposeError {
+    /// Returns the line number in the intermediate file (1-based)
+    /// on which the two patches disagree.
+    pub fn line(&self) -> usize {
+        self.line
+    }
+}
+
+impl fmt::Display for ComposeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "error composing patches: line {} of the intermediate file differs between patches",
+            self.line
+        )
+    }
+}
+
+impl core::error::Error for ComposeError {}
+
+/// The lines of the intermediate file which one of the patches knows about.
+///
+/// For the first patch the intermediate file is its modified file,
+/// for the second patch it is its original file.
+struct Side<'a, T: ?Sized> {
+    /// The known lines, and whether the patch keeps them in its other file
+    lines: BTreeMap<usize, (&'a T, bool)>,
+    /// Lines only present in the other file, by the intermediate line they come before
+    gaps: BTreeMap<usize, Vec<&'a T>>,
+}
+
+impl<'a, T: ?Sized> Side<'a, T> {
+    // Collects the known lines of the intermediate file. `reverse` is set for the second patch,
+    // so that the intermediate file is on the modified side of each hunk.
+    fn new(hunks: &[Hunk<'a, T>], reverse: bool, intervals: &mut Vec<Interval<'a, T>>) -> Self {
+        let mut lines = BTreeMap::new();
+        let mut gaps: BTreeMap<usize, Vec<&'a T>> = BTreeMap::new();
+
+        for hunk in hunks {
+            let (other, intermediate) = if reverse {
+                (hunk.new_range, hunk.old_range)
+            } else {
+                (hunk.old_range, hunk.new_range)
+            };
+            let start = intermediate.preceding();
+            intervals.push(Interval {
+                range: start..start + intermediate.len,
+                first: !reverse,
+                delta: intermediate.len as isize - other.len as isize,
+                function_context: hunk.function_context,
+            });
+
+            let mut pos = start;
+            for line in &hunk.lines {
+                let line = if reverse { line.reverse() } else { *line };
+                match line {
+                    Line::Context(l) => {
+                        lines.insert(pos, (l, true));
+                        pos += 1;
+                    }
+                    Line::Insert(l) => {
+                        lines.insert(pos, (l, false));
+                        pos += 1;
+                    }
+                    Line::Delete(l) => gaps.entry(pos).or_default().push(l),
+                }
+            }
+        }
+
+        Self { lines, gaps }
+    }
+
+    fn gap(&self, pos: usize) -> &[&'a T] {
+        self.gaps.get(&pos).map_or(&[], Vec::as_slice)
+    }
+}
+
+/// The lines of the intermediate file covered by a hunk of one of the patches
+struct Interval<'a, T: ?Sized> {
+    range: Range<usize>,
+    /// Whether the hunk is from the first patch
+    first: bool,
+    /// The number of lines the intermediate file has more than the hunk's other file
+    delta: isize,
+    function_context: Option<&'a T>,
+}
+
+/// Composes the hunks of a patch from `v1` to `v2` with the hunks of a patch from `v2` to `v3`
+/// into the hunks of a patch from `v1` to `v3`.
+pub(crate) fn compose<'a, T: PartialEq + ?Sized>(
+    first: &[Hunk<'a, T>],
+    second: &[Hunk<'a, T>],
+) -> Result<Vec<Hunk<'a, T>>, ComposeError> {
+    let mut intervals = Vec::new();
+    let first = Side::new(first, false, &mut intervals);
+    let second = Side::new(second, true, &mut intervals);
+    intervals.sort_by_key(|interval| interval.range.start);
+
+    let mut hunks = Vec::new();
+    // The number of lines `v2` has more than `v1` and `v3` before the current region
+    let mut first_delta = 0isize;
+    let mut second_delta = 0isize;
+
+    let mut intervals = intervals.into_iter().peekable();
+    while let Some(interval) = intervals.next() {
+        // Compose the hunks in a region of overlapping or adjacent hunks
+        let start = interval.range.start;
+        let mut end = interval.range.end;
+        let function_context = interval.function_context;
+        let (mut region_first_delta, mut region_second_delta) = (0, 0);
+        let mut add_delta = |interval: &Interval<'a, T>| {
+            if interval.first {
+                region_first_delta += interval.delta;
+            } else {
+                region_second_delta += interval.delta;
+            }
+        };
+        add_delta(&interval);
+        while let Some(next) = intervals.next_if(|next| next.range.start <= end) {
+            end = end.max(next.range.end);
+            add_delta(&next);
+        }
+
+        let mut lines = Vec::new();
+        for pos in start..=end {
+            lines.extend(first.gap(pos).iter().map(|&l| Line::Delete(l)));
+            lines.extend(second.gap(pos).iter().map(|&l| Line::Insert(l)));
+            if pos == end {
+                break;
+            }
+
+            let (line, in_v1, in_v3) = match (first.lines.get(&pos), second.lines.get(&pos)) {
+                (Some(&(a, in_v1)), Some(&(b, in_v3))) => {
+                    if a != b {
+                        return Err(ComposeError { line: pos + 1 });
+                    }
+                    (a, in_v1, in_v3)
+                }
+                (Some(&(a, in_v1)), None) => (a, in_v1, true),
+                (None, Some(&(b, in_v3))) => (b, true, in_v3),
+                (None, None) => unreachable!("every line in a region is covered by a hunk"),
+            };
+            match (in_v1, in_v3) {
+                (true, true) => lines.push(Line::Context(line)),
+                (true, false) => lines.push(Line::Delete(line)),
+                (false, true) => lines.push(Line::Insert(line)),
+                (false, false) => {}
+            }
+        }
+
+        // Within each run of changes, list the deleted lines before the inserted ones
+        for run in lines.split_mut(|line| matches!(line, Line::Context(_))) {
+            run.sort_by_key(|line| matches!(line, Line::Insert(_)));
+        }
+
+        if lines.iter().any(|line| !matches!(line, Line::Context(_))) {
+            let (old_len, new_len) = hunk_lines_count(&lines);
+            let old_start = start.saturating_add_signed(-first_delta);
+            let new_start = start.saturating_add_signed(-second_delta);
+            hunks.push(Hunk::new(
+                HunkRange::following(old_start, old_len),
+                HunkRange::following(new_start, new_len),
+                function_context,
+                lines,
+            ));
+        }
+
+        first_delta += region_first_delta;
+        second_delta += region_second_delta;
+    }
+
+    Ok(hunks)
+}
@@ -0,0 +1,83 @@
+//! Compare two patches to the same original file.
+
+use alloc::vec::Vec;
+
+use super::Hunk;
+use super::Line;
+use super::compose::ComposeError;
+use super::compose::compose;
+use super::group_hunks;
+use crate::diff::DiffOptions;
+use crate::range::DiffRange;
+
+/// The number of context lines kept around each change of an interdiff
+const CONTEXT_LEN: usize = 3;
+
+/// Computes the hunks of a patch from the result of applying `first` to the result of applying
+/// `second`, where both apply to the same original file.
+pub(crate) fn interdiff<'a, T: PartialEq + ?Sized>(
+    first: &[Hunk<'a, T>],
+    second: &[Hunk<'a, T>],
+) -> Result<Vec<Hunk<'a, T>>, ComposeError> {
+    let reversed: Vec<_> = first.iter().map(Hunk::reverse).collect();
+    let composed = compose(&reversed, second)?;
+
+    let mut hunks = Vec::new();
+    for hunk in composed {
+        let preceding = (hunk.old_range.preceding(), hunk.new_range.preceding());
+        hunks.extend(group_hunks(
+            &refine(&hunk.lines),
+            preceding,
+            CONTEXT_LEN,
+            false,
+            |_| hunk.function_context,
+        ));
+    }
+    Ok(hunks)
+}
+
+// Re-diffs the deleted and inserted lines of each run of changes, so that changes made by both
+// patches become context.
+fn refine<'a, T: PartialEq + ?Sized>(lines: &[Line<'a, T>]) -> Vec<Line<'a, T>> {
+    let opts = DiffOptions::default();
+    let mut refined = Vec::with_capacity(lines.len());
+
+    let mut i = 0;
+    while i < lines.len() {
+        if let Line::Context(l) = lines[i] {
+            refined.push(Line::Context(l));
+            i += 1;
+            continue;
+        }
+
+        let len = lines[i..]
+            .iter()
+            .position(|line| matches!(line, Line::Context(_)))
+            .unwrap_or(lines.len() - i);
+        let (mut deleted, mut inserted) = (Vec::new(), Vec::new());
+        for line in &lines[i..i + len] {
+            match *line {
+                Line::Delete(l) => deleted.push(l),
+                Line::Insert(l) => inserted.push(l),
+                Line::Context(_) => unreachable!(),
+            }
+        }
+
+        for range in opts.diff_slice_myers(&deleted, &inserted) {
+            match range {
+                DiffRange::Equal(range, _) => {
+                    refined.extend(range.as_slice().iter().map(|&l| Line::Context(l)))
+                }
+                DiffRange::Delete(range) => {
+                    refined.extend(range.as_slice().iter().map(|&l| Line::Delete(l)))
+                }
+                DiffRange::Insert(range) => {
+                    refined.extend(range.as_slice().iter().map(|&l| Line::Insert(l)))
+                }
+            }
+        }
+        i += len;
+    }
+
+    refined
+}
@@ -0,0 +1,88 @@
+//! Map line numbers between the original and modified files of a patch.
+
+use super::Hunk;
+use super::Line;
+
+/// Where a line of one file of a [`Patch`] ends up in the other file.
+///
+/// Returned by [`Patch::map_original_line`] and [`Patch::map_modified_line`].
+/// Line numbers start at 1.
+///
+/// [`Patch`]: crate::Patch
+/// [`Patch::map_original_line`]: crate::Patch::map_original_line
+/// [`Patch::map_modified_line`]: crate::Patch::map_modified_line
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineMapping {
+    /// The line isn't part of any hunk, and is at this line of the other file.
+    Unchanged(usize),
+
+    /// The line is a context line of a hunk, and is at this line of the other file.
+    ///
+    /// It is kept by the patch, but lies in a region the patch changes.
+    Context(usize),
+
+    /// The line isn't in the other file.
+    ///
+    /// This is the line of the other file at which the changed region continues: the first
+    /// line which replaces the missing line, or else the line which follows it.
+    Deleted(usize),
+}
+
+/// Maps `line` of the original file of `hunks` to the modified file,
+/// or the other way around if `reverse` is set.
+pub(crate) fn map_line<T: ?Sized>(
+    hunks: &[Hunk<'_, T>],
+    line: usize,
+    reverse: bool,
+) -> LineMapping {
+    assert!(line > 0, "line numbers start at 1");
+
+    // The number of lines the other file has more than this one before the current hunk
+    let mut delta = 0isize;
+    for hunk in hunks {
+        let (this, other) = if reverse {
+            (hunk.new_range, hunk.old_range)
+        } else {
+            (hunk.old_range, hunk.new_range)
+        };
+        let (this_pos, other_pos) = (this.preceding(), other.preceding());
+        if line <= this_pos {
+            break;
+        }
+        if line > this_pos + this.len {
+            delta = (other_pos + other.len) as isize - (this_pos + this.len) as isize;
+            continue;
+        }
+
+        let (mut this_pos, mut other_pos) = (this_pos, other_pos);
+        // The line of the other file at which the current run of changes starts
+        let mut run_start = None;
+        for l in &hunk.lines {
+            let l = if reverse { l.reverse() } else { *l };
+            if matches!(l, Line::Context(_)) {
+                run_start = None;
+            } else {
+                run_start.get_or_insert(other_pos + 1);
+            }
+            match l {
+                Line::Context(_) => {
+                    this_pos += 1;
+                    other_pos += 1;
+                    if this_pos == line {
+                        return LineMapping::Context(other_pos);
+                    }
+                }
+                Line::Delete(_) => {
+                    this_pos += 1;
+                    if this_pos == line {
+                        return LineMapping::Deleted(run_start.unwrap());
+                    }
+                }
+                Line::Insert(_) => other_pos += 1,
+            }
+        }
+        unreachable!("the hunk covers the line");
+    }
+
+    LineMapping::Unchanged(line.saturating_add_signed(delta))
+}
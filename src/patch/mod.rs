@@ -1,20 +1,31 @@
+mod compose;
 pub(crate) mod error;
 mod format;
+mod interdiff;
+mod line_map;
 pub(crate) mod parse;
+mod rebase;
 #[cfg(feature = "color")]
 mod style;
 #[cfg(test)]
 mod tests;
 
+pub use compose::ComposeError;
 pub use error::ParsePatchError;
 pub use format::PatchFormatter;
+pub use line_map::LineMapping;
+pub use rebase::RebaseError;
 
 use alloc::borrow::Cow;
 use alloc::borrow::ToOwned;
 use alloc::fmt;
+use alloc::vec;
 use alloc::vec::Vec;
 use core::ops;
 
+use crate::ApplyError;
+use crate::utils::LineIter;
+use crate::utils::Text;
 use crate::utils::byte_needs_quoting;
 use crate::utils::fmt_escaped_byte;
 #[cfg(feature = "std")]
@@ -88,7 +99,7 @@ impl<'a, T: ToOwned + ?Sized> Patch<'a, T> {
     }
 
     /// Returns a patch that transforms the modified text back into the original text.
-    pub fn reverse(&self) -> Patch<'_, T> {
+    pub fn reverse(&self) -> Patch<'a, T> {
         let hunks = self.hunks.iter().map(Hunk::reverse).collect();
         Patch {
             original: self.modified.clone(),
@@ -96,6 +107,428 @@ impl<'a, T: ToOwned + ?Sized> Patch<'a, T> {
             hunks,
         }
     }
+
+    /// Combines this patch with a patch which applies to its result into a single patch
+    ///
+    /// Given `self`, a patch from `v1` to `v2`, and `next`, a patch from `v2` to `v3`, this
+    /// returns a patch from `v1` to `v3` without needing the contents of any of the files.
+    ///
+    /// Hunks from both patches which overlap or are adjacent in `v2` are combined into a single
+    /// hunk. Composing fails only if the patches disagree on the contents of a line of `v2`, in
+    /// which case `next` doesn't apply to the result of `self`.
+    ///
+    /// ```
+    /// use diffy::apply;
+    /// use diffy::create_patch;
+    ///
+    /// let v1 = "a\nb\nc\nd\n";
+    /// let v2 = "a\nB\nc\nd\n";
+    /// let v3 = "a\nB\nc\nD\n";
+    ///
+    /// let patch = create_patch(v1, v2).compose(&create_patch(v2, v3)).unwrap();
+    /// assert_eq!(apply(v1, &patch).unwrap(), v3);
+    /// ```
+    pub fn compose(&self, next: &Patch<'a, T>) -> Result<Patch<'a, T>, ComposeError>
+    where
+        T: PartialEq,
+    {
+        Ok(Patch {
+            original: self.original.clone(),
+            modified: next.modified.clone(),
+            hunks: compose::compose(&self.hunks, &next.hunks)?,
+        })
+    }
+
+    /// Rewrites this patch to apply on top of another patch to the same original text
+    ///
+    /// Given `self` and `onto`, two patches which both apply to `v1`, this returns a patch with
+    /// the changes of `self` which applies to the result of applying `onto` to `v1`. The line
+    /// numbers of each hunk are adjusted for the lines added and removed by `onto`, and context
+    /// lines which `onto` changes are replaced with the lines it changes them to, so the hunks
+    /// keep their context.
+    ///
+    /// Rebasing fails if a change in `self` overlaps a change in `onto`, or if both insert lines
+    /// at the same place, as the order of the changes would be ambiguous.
+    ///
+    /// ```
+    /// use diffy::apply;
+    /// use diffy::create_patch;
+    ///
+    /// let original = "a\nb\nc\nd\ne\nf\ng\nh\ni\nj\n";
+    /// let ours = create_patch(original, "a\nb\nc\nd\ne\nf\ng\nh\ni\nj\nk\n");
+    /// let theirs = create_patch(original, "0\na\nb\nc\nd\ne\nf\ng\nh\ni\nj\n");
+    ///
+    /// let rebased = ours.rebase(&theirs).unwrap();
+    /// assert_eq!(rebased.hunks()[0].old_range().start(), 9);
+    ///
+    /// let patched = apply(original, &theirs).unwrap();
+    /// assert_eq!(
+    ///     apply(&patched, &rebased).unwrap(),
+    ///     "0\na\nb\nc\nd\ne\nf\ng\nh\ni\nj\nk\n"
+    /// );
+    ///
+    /// let conflicting = create_patch(original, "a\nb\nc\nd\ne\nf\ng\nh\ni\nj\nK\n");
+    /// assert_eq!(ours.rebase(&conflicting).unwrap_err().hunk(), 1);
+    /// ```
+    pub fn rebase(&self, onto: &Patch<'a, T>) -> Result<Patch<'a, T>, RebaseError> {
+        Ok(Patch {
+            original: self.original.clone(),
+            modified: self.modified.clone(),
+            hunks: rebase::rebase(&self.hunks, &onto.hunks)?,
+        })
+    }
+
+    /// Swaps the order of this patch and a patch which applies to its result
+    ///
+    /// Given `self`, a patch from `v1` to `v2`, and `next`, a patch from `v2` to `v3`, this
+    /// returns a pair of patches with the same changes in the opposite order: the first applies
+    /// the changes of `next` to `v1`, and the second applies the changes of `self` on top of it,
+    /// resulting in `v3`.
+    ///
+    /// This fails if the patches depend on each other, for example if `next` changes lines added
+    /// by `self`. The error refers to the hunks of `next`, or of `self` if `next` was rebased
+    /// successfully.
+    ///
+    /// ```
+    /// use diffy::apply;
+    /// use diffy::create_patch;
+    ///
+    /// let v1 = "a\nb\nc\nd\ne\nf\ng\nh\ni\nj\n";
+    /// let v2 = "a\nb\nc\nd\ne\nf\ng\nh\ni\nJ\n";
+    /// let v3 = "A\nb\nc\nd\ne\nf\ng\nh\ni\nJ\n";
+    ///
+    /// let (first, second) = create_patch(v1, v2).commute(&create_patch(v2, v3)).unwrap();
+    ///
+    /// let patched = apply(v1, &first).unwrap();
+    /// assert_eq!(patched, "A\nb\nc\nd\ne\nf\ng\nh\ni\nj\n");
+    /// assert_eq!(apply(&patched, &second).unwrap(), v3);
+    /// ```
+    pub fn commute(
+        &self,
+        next: &Patch<'a, T>,
+    ) -> Result<(Patch<'a, T>, Patch<'a, T>), RebaseError> {
+        let first = next.rebase(&self.reverse())?;
+        let second = self.rebase(&first)?;
+        Ok((first, second))
+    }
+
+    /// Compares this patch with another patch to the same original text
+    ///
+    /// Given `self`, a patch from `v1` to `v2`, and `other`, a patch from `v1` to `v3`, this
+    /// returns a patch from `v2` to `v3` without needing the contents of any of the files. This
+    /// is useful for reviewing what changed between two revisions of a patch.
+    ///
+    /// Changes made by both patches don't appear in the result, and each hunk has up to three
+    /// lines of context around its changes. This fails if the patches disagree on the contents of
+    /// a line of `v1`, in which case they don't apply to the same original text.
+    ///
+    /// ```
+    /// use diffy::apply;
+    /// use diffy::create_patch;
+    ///
+    /// let original = "a\nb\nc\nd\n";
+    /// let first = create_patch(original, "a\nB\nc\nd\n");
+    /// let second = create_patch(original, "a\nB\nc\nD\n");
+    ///
+    /// let interdiff = first.interdiff(&second).unwrap();
+    /// assert_eq!(
+    ///     interdiff.to_string(),
+    ///     "\
+    /// --- modified
+    /// +++ modified
+    /// @@ -1,4 +1,4 @@
+    ///  a
+    ///  B
+    ///  c
+    /// -d
+    /// +D
+    /// "
+    /// );
+    /// assert_eq!(apply("a\nB\nc\nd\n", &interdiff).unwrap(), "a\nB\nc\nD\n");
+    /// ```
+    pub fn interdiff(&self, other: &Patch<'a, T>) -> Result<Patch<'a, T>, ComposeError>
+    where
+        T: PartialEq,
+    {
+        Ok(Patch {
+            original: self.modified.clone(),
+            modified: other.modified.clone(),
+            hunks: interdiff::interdiff(&self.hunks, &other.hunks)?,
+        })
+    }
+
+    /// Maps a line number of the original text to the modified text
+    ///
+    /// This can be used to carry annotations on the original text, like review comments,
+    /// forward to the modified text. Line numbers start at 1.
+    ///
+    /// ```
+    /// use diffy::LineMapping;
+    /// use diffy::create_patch;
+    ///
+    /// let original = "a\nb\nc\nd\ne\nf\ng\nh\ni\nj\n";
+    /// let modified = "new\na\nb\nc\nd\ne\nf\nh\ni\nj\n";
+    /// let patch = create_patch(original, modified);
+    ///
+    /// assert_eq!(patch.map_original_line(5), LineMapping::Context(6));
+    /// assert_eq!(patch.map_original_line(7), LineMapping::Deleted(8));
+    /// assert_eq!(patch.map_original_line(10), LineMapping::Context(10));
+    /// assert_eq!(patch.map_modified_line(1), LineMapping::Deleted(1));
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if `line` is 0.
+    pub fn map_original_line(&self, line: usize) -> LineMapping {
+        line_map::map_line(&self.hunks, line, false)
+    }
+
+    /// Maps a line number of the modified text to the original text
+    ///
+    /// This is the inverse of [`map_original_line`](Patch::map_original_line).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `line` is 0.
+    pub fn map_modified_line(&self, line: usize) -> LineMapping {
+        line_map::map_line(&self.hunks, line, true)
+    }
+
+    /// Rebuilds the hunks of this patch with a different number of context lines
+    ///
+    /// `original` is the text the patch applies to, from which context lines are taken. The
+    /// returned patch makes the same changes, with up to `context_len` lines of context around
+    /// them, like a patch created by [`DiffOptions`](crate::DiffOptions) with the same context
+    /// length. Hunks are merged where their context lines would overlap, and split where they
+    /// have at least `2 * context_len` unchanged lines between their changes, or one more before
+    /// lines appended to the end of the file.
+    ///
+    /// The hunks are located in `original` the same way [`apply`](crate::apply()) does, so this
+    /// fails if the patch doesn't apply to it.
+    ///
+    /// ```
+    /// use diffy::DiffOptions;
+    ///
+    /// let original = "a\nb\nc\nd\ne\n";
+    /// let modified = "a\nb\nC\nd\ne\n";
+    /// let patch = DiffOptions::new()
+    ///     .set_context_len(0)
+    ///     .create_patch(original, modified);
+    ///
+    /// assert_eq!(
+    ///     patch.recontext(original, 1).unwrap().to_string(),
+    ///     "\
+    /// --- original
+    /// +++ modified
+    /// @@ -2,3 +2,3 @@
+    ///  b
+    /// -c
+    /// +C
+    ///  d
+    /// "
+    /// );
+    /// ```
+    pub fn recontext(&self, original: &'a T, context_len: usize) -> Result<Patch<'a, T>, ApplyError>
+    where
+        T: Text,
+    {
+        let positions = crate::apply::locate_hunks(original, self)?;
+        let original_lines: Vec<_> = LineIter::new(original).collect();
+
+        let mut order: Vec<usize> = (0..self.hunks.len()).collect();
+        order.sort_by_key(|&i| positions[i]);
+
+        // Every line of the file, with the function context of the hunk each change comes from
+        let mut lines = Vec::with_capacity(original_lines.len());
+        let mut function_contexts = Vec::with_capacity(original_lines.len());
+        let mut pos = 0;
+        for i in order {
+            let hunk = &self.hunks[i];
+            let start = positions[i].max(pos);
+            lines.extend(original_lines[pos..start].iter().map(|&l| Line::Context(l)));
+            lines.extend_from_slice(&hunk.lines);
+            function_contexts.resize(lines.len(), hunk.function_context);
+            pos = start + hunk_lines_count(&hunk.lines).0;
+        }
+        lines.extend(original_lines[pos..].iter().map(|&l| Line::Context(l)));
+
+        Ok(Patch {
+            original: self.original.clone(),
+            modified: self.modified.clone(),
+            hunks: group_hunks(&lines, (0, 0), context_len, true, |i| function_contexts[i]),
+        })
+    }
+
+    /// Returns a copy of the patch with the hunk at `index` split into smaller hunks
+    ///
+    /// See [`Hunk::split`] for how the hunk is split. This is useful for interactively selecting
+    /// changes, similar to the `s` command of `git add -p`.
+    ///
+    /// The resulting hunks overlap where they share context lines, so the patch should be passed
+    /// through [`partition`](Patch::partition) before it is applied.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of bounds.
+    pub fn split_hunk(&self, index: usize) -> Patch<'a, T> {
+        let mut hunks = self.hunks[..index].to_vec();
+        hunks.extend(self.hunks[index].split());
+        hunks.extend_from_slice(&self.hunks[index + 1..]);
+        Patch {
+            original: self.original.clone(),
+            modified: self.modified.clone(),
+            hunks,
+        }
+    }
+
+    /// Partitions the hunks of the patch into two patches based on a predicate
+    ///
+    /// `select` is called with the index of each hunk and the hunk itself. The first patch
+    /// returned contains the selected hunks and applies to the original text. The second patch
+    /// contains the remaining hunks and applies to the result of applying the first patch, so
+    /// that applying both in turn is equivalent to applying `self`.
+    ///
+    /// The line numbers of each hunk are adjusted for the hunks which are left out, and adjacent
+    /// hunks which share context lines (e.g. those produced by [`split_hunk`]) are merged back
+    /// together.
+    ///
+    /// [`split_hunk`]: Patch::split_hunk
+    ///
+    /// ```
+    /// use diffy::apply;
+    /// use diffy::create_patch;
+    ///
+    /// let original = "a\nb\nc\nd\ne\nf\n";
+    /// let modified = "a\nB\nc\nd\nE\nf\n";
+    ///
+    /// // The changes are close enough to be in the same hunk, so split it
+    /// let patch = create_patch(original, modified).split_hunk(0);
+    /// assert_eq!(patch.hunks().len(), 2);
+    ///
+    /// let (selected, remaining) = patch.partition(|i, _| i == 1);
+    ///
+    /// let partially_patched = apply(original, &selected).unwrap();
+    /// assert_eq!(partially_patched, "a\nb\nc\nd\nE\nf\n");
+    /// assert_eq!(apply(&partially_patched, &remaining).unwrap(), modified);
+    /// ```
+    pub fn partition<F>(&self, mut select: F) -> (Patch<'a, T>, Patch<'a, T>)
+    where
+        F: FnMut(usize, &Hunk<'a, T>) -> bool,
+    {
+        let mut selected = Vec::new();
+        let mut remaining = Vec::new();
+        // The number of lines added by the selected hunks so far
+        let mut offset = 0isize;
+
+        for (i, hunk) in self.hunks.iter().enumerate() {
+            if select(i, hunk) {
+                offset += hunk.new_range.len as isize - hunk.old_range.len as isize;
+                selected.push((hunk.old_range.preceding(), hunk));
+            } else {
+                let preceding = hunk.old_range.preceding().saturating_add_signed(offset);
+                remaining.push((preceding, hunk));
+            }
+        }
+
+        let patch = |hunks| Patch {
+            original: self.original.clone(),
+            modified: self.modified.clone(),
+            hunks: merge_hunks(hunks),
+        };
+        (patch(selected), patch(remaining))
+    }
+}
+
+// Build hunks with up to `context_len` context lines around each run of changes in `lines`, which
+// start after the given number of lines of the original and modified files. Context lines further
+// away from any change are dropped. `function_context` is called with the index of the first
+// change of each hunk. `to_eof` is set if `lines` run to the end of the original file.
+fn group_hunks<'a, T: ?Sized>(
+    lines: &[Line<'a, T>],
+    (old_preceding, new_preceding): (usize, usize),
+    context_len: usize,
+    to_eof: bool,
+    function_context: impl Fn(usize) -> Option<&'a T>,
+) -> Vec<Hunk<'a, T>> {
+    let is_change = |line: &Line<'a, T>| !matches!(line, Line::Context(_));
+
+    // The indices at which the changes of each hunk start and end
+    let mut groups: Vec<(usize, usize)> = Vec::new();
+    let mut i = 0;
+    while let Some(start) = lines[i..].iter().position(is_change) {
+        let start = i + start;
+        let end = lines[start..]
+            .iter()
+            .position(|line| !is_change(line))
+            .map_or(lines.len(), |len| start + len);
+
+        // Like `DiffOptions`, lines appended to the end of the file are also grouped with the
+        // previous change when exactly `2 * context_len` lines separate them
+        let appends = to_eof
+            && end == lines.len()
+            && lines[start..]
+                .iter()
+                .all(|line| matches!(line, Line::Insert(_)));
+        let max_gap = 2 * context_len + usize::from(appends);
+        match groups.last_mut() {
+            Some(last) if start - last.1 < max_gap => last.1 = end,
+            _ => groups.push((start, end)),
+        }
+        i = end;
+    }
+
+    let mut hunks = Vec::with_capacity(groups.len());
+    for (first, end) in groups {
+        let start = first.saturating_sub(context_len);
+        let end = (end + context_len).min(lines.len());
+        let (old_before, new_before) = hunk_lines_count(&lines[..start]);
+        let hunk_lines = lines[start..end].to_vec();
+        let (old_len, new_len) = hunk_lines_count(&hunk_lines);
+        hunks.push(Hunk::new(
+            HunkRange::following(old_preceding + old_before, old_len),
+            HunkRange::following(new_preceding + new_before, new_len),
+            function_context(first),
+            hunk_lines,
+        ));
+    }
+    hunks
+}
+
+// Build hunks from `(preceding, hunk)` pairs, where `preceding` is the number of lines before the
+// hunk in the file it applies to. Hunks whose old ranges overlap are merged, and new ranges are
+// computed from the changes made by the previous hunks.
+fn merge_hunks<'a, T: ?Sized>(hunks: Vec<(usize, &Hunk<'a, T>)>) -> Vec<Hunk<'a, T>> {
+    let mut merged: Vec<(usize, Hunk<'a, T>)> = Vec::new();
+
+    for (preceding, hunk) in hunks {
+        if let Some((last_preceding, last)) = merged.last_mut() {
+            let last_end = *last_preceding + hunk_lines_count(&last.lines).0;
+            if preceding < last_end {
+                // The overlapping lines are context shared by both hunks
+                let overlap = last_end - preceding;
+                debug_assert!(
+                    hunk.lines[..overlap]
+                        .iter()
+                        .all(|line| matches!(line, Line::Context(_)))
+                );
+                last.lines.extend_from_slice(&hunk.lines[overlap..]);
+                continue;
+            }
+        }
+        merged.push((preceding, hunk.clone()));
+    }
+
+    let mut offset = 0isize;
+    merged
+        .into_iter()
+        .map(|(preceding, hunk)| {
+            let (old_len, new_len) = hunk_lines_count(&hunk.lines);
+            let old_range = HunkRange::following(preceding, old_len);
+            let new_range = HunkRange::following(preceding.saturating_add_signed(offset), new_len);
+            offset += new_len as isize - old_len as isize;
+            Hunk::new(old_range, new_range, hunk.function_context, hunk.lines)
+        })
+        .collect()
 }
 
 #[cfg(feature = "std")]
@@ -138,6 +571,34 @@ impl<'a> Patch<'a, str> {
     pub fn from_str(s: &'a str) -> Result<Patch<'a, str>, ParsePatchError> {
         parse::parse(s)
     }
+
+    /// Parse a `Patch` from a string, ignoring the line counts in hunk headers
+    ///
+    /// This is the equivalent of `git apply --recount` and is useful for hand-edited patches,
+    /// whose hunk headers are often out of date. Each hunk ends at the first line which can't be
+    /// part of it, and the line counts of its [`HunkRange`]s are derived from its lines.
+    ///
+    /// ```
+    /// use diffy::Patch;
+    ///
+    /// let s = "\
+    /// --- a/ideals
+    /// +++ b/ideals
+    /// @@ -1,3 +1,3 @@
+    ///  First:
+    ///      Life before death,
+    /// +    strength before weakness,
+    /// ";
+    ///
+    /// assert!(Patch::from_str(s).is_err());
+    ///
+    /// let patch = Patch::from_str_recount(s).unwrap();
+    /// assert_eq!(patch.hunks()[0].old_range().len(), 2);
+    /// assert_eq!(patch.hunks()[0].new_range().len(), 3);
+    /// ```
+    pub fn from_str_recount(s: &'a str) -> Result<Patch<'a, str>, ParsePatchError> {
+        parse::parse_recount(s)
+    }
 }
 
 impl<'a> Patch<'a, [u8]> {
@@ -145,6 +606,13 @@ impl<'a> Patch<'a, [u8]> {
     pub fn from_bytes(s: &'a [u8]) -> Result<Patch<'a, [u8]>, ParsePatchError> {
         parse::parse_bytes(s)
     }
+
+    /// Parse a `Patch` from bytes, ignoring the line counts in hunk headers
+    ///
+    /// See [`Patch::from_str_recount`].
+    pub fn from_bytes_recount(s: &'a [u8]) -> Result<Patch<'a, [u8]>, ParsePatchError> {
+        parse::parse_bytes_recount(s)
+    }
 }
 
 impl<T: ToOwned + ?Sized> Clone for Patch<'_, T> {
@@ -312,6 +780,65 @@ impl<'a, T: ?Sized> Hunk<'a, T> {
         &self.lines
     }
 
+    /// Splits the hunk into smaller hunks, one for each group of changed lines
+    ///
+    /// Like the `s` command of `git add -p`, the hunk is split at the context lines between
+    /// groups of inserted and deleted lines. The context lines between two groups are included in
+    /// both of the resulting hunks. A hunk with a single group of changes is returned as is.
+    ///
+    /// The line numbers of each resulting hunk assume that the hunks before it are applied too.
+    /// Use [`Patch::partition`] to adjust them when only some of the hunks are applied.
+    ///
+    /// ```
+    /// use diffy::create_patch;
+    ///
+    /// let patch = create_patch("a\nb\nc\nd\n", "A\nb\nc\nD\n");
+    /// assert_eq!(patch.hunks().len(), 1);
+    ///
+    /// let hunks = patch.hunks()[0].split();
+    /// assert_eq!(hunks.len(), 2);
+    /// assert_eq!(hunks[0].old_range().range(), 1..4);
+    /// assert_eq!(hunks[1].old_range().range(), 2..5);
+    /// ```
+    pub fn split(&self) -> Vec<Hunk<'a, T>> {
+        let is_change = |line: &Line<'a, T>| !matches!(line, Line::Context(_));
+
+        // The indices at which each group of changes starts and ends
+        let mut groups = Vec::new();
+        let mut i = 0;
+        while let Some(start) = self.lines[i..].iter().position(is_change) {
+            let start = i + start;
+            let end = self.lines[start..]
+                .iter()
+                .position(|line| !is_change(line))
+                .map_or(self.lines.len(), |len| start + len);
+            groups.push((start, end));
+            i = end;
+        }
+
+        if groups.len() <= 1 {
+            return vec![self.clone()];
+        }
+
+        let old_preceding = self.old_range.preceding();
+        let new_preceding = self.new_range.preceding();
+        (0..groups.len())
+            .map(|g| {
+                let start = if g == 0 { 0 } else { groups[g - 1].1 };
+                let end = groups.get(g + 1).map_or(self.lines.len(), |next| next.0);
+                let (old_before, new_before) = hunk_lines_count(&self.lines[..start]);
+                let lines = self.lines[start..end].to_vec();
+                let (old_len, new_len) = hunk_lines_count(&lines);
+                Hunk::new(
+                    HunkRange::following(old_preceding + old_before, old_len),
+                    HunkRange::following(new_preceding + new_before, new_len),
+                    self.function_context,
+                    lines,
+                )
+            })
+            .collect()
+    }
+
     /// Creates a reverse patch for the hunk.  This is equivalent to what
     /// XDL_PATCH_REVERSE would apply in libxdiff.
     pub fn reverse(&self) -> Self {
@@ -350,6 +877,22 @@ impl HunkRange {
         Self { start, len }
     }
 
+    // Constructs the range of `len` lines following the first `preceding` lines of a file
+    fn following(preceding: usize, len: usize) -> Self {
+        // An empty range starts at the line after which lines are inserted
+        let start = if len == 0 { preceding } else { preceding + 1 };
+        Self::new(start, len)
+    }
+
+    // The number of lines in the file before the range
+    fn preceding(&self) -> usize {
+        if self.len == 0 {
+            self.start
+        } else {
+            self.start.saturating_sub(1)
+        }
+    }
+
     /// Returns the range as a `ops::Range`
     pub fn range(&self) -> ops::Range<usize> {
         self.start..self.end()
@@ -6,6 +6,7 @@ use super::Line;
 use super::NO_NEWLINE_AT_EOF;
 use super::error::ParsePatchError;
 use super::error::ParsePatchErrorKind;
+use super::hunk_lines_count;
 use crate::patch::Patch;
 use crate::utils::LineIter;
 use crate::utils::Text;
@@ -22,6 +23,7 @@ type Result<T, E = ParsePatchError> = core::result::Result<T, E>;
 pub(crate) struct ParseOpts {
     skip_preamble: bool,
     reject_orphaned_hunks: bool,
+    recount: bool,
 }
 
 impl Default for ParseOpts {
@@ -29,6 +31,7 @@ impl Default for ParseOpts {
         Self {
             skip_preamble: true,
             reject_orphaned_hunks: false,
+            recount: false,
         }
     }
 }
@@ -49,6 +52,14 @@ impl ParseOpts {
         self.reject_orphaned_hunks = true;
         self
     }
+
+    /// Ignore the line counts in hunk headers,
+    /// deriving them from the hunk body instead,
+    /// matching `git apply --recount` behavior.
+    pub(crate) fn recount(mut self, recount: bool) -> Self {
+        self.recount = recount;
+        self
+    }
 }
 
 struct Parser<'a, T: Text + ?Sized> {
@@ -68,6 +79,13 @@ impl<'a, T: Text + ?Sized> Parser<'a, T> {
         self.lines.peek()
     }
 
+    /// Returns the line after the next one, without consuming anything.
+    fn peek_second(&self) -> Option<&'a T> {
+        let mut lines = self.lines.clone();
+        lines.next();
+        lines.next()
+    }
+
     fn offset(&self) -> usize {
         self.offset
     }
@@ -102,6 +120,18 @@ pub fn parse_bytes(input: &[u8]) -> Result<Patch<'_, [u8]>> {
     result
 }
 
+pub fn parse_recount(input: &str) -> Result<Patch<'_, str>> {
+    let opts = ParseOpts::default().reject_orphaned_hunks().recount(true);
+    let (result, _consumed) = parse_one(input, opts);
+    result
+}
+
+pub fn parse_bytes_recount(input: &[u8]) -> Result<Patch<'_, [u8]>> {
+    let opts = ParseOpts::default().reject_orphaned_hunks().recount(true);
+    let (result, _consumed) = parse_one(input, opts);
+    result
+}
+
 /// Parses one patch from input.
 ///
 /// Always returns consumed bytes alongside the result
@@ -116,7 +146,7 @@ pub(crate) fn parse_one<T: Text + ?Sized>(
         Ok(h) => h,
         Err(e) => return (Err(e), parser.offset()),
     };
-    let hunks = match hunks(&mut parser) {
+    let hunks = match hunks(&mut parser, opts.recount) {
         Ok(h) => h,
         Err(e) => return (Err(e), parser.offset()),
     };
@@ -216,7 +246,10 @@ fn reject_orphaned_hunk_headers<T: Text + ?Sized>(parser: &mut Parser<'_, T>) ->
     Ok(())
 }
 
-fn hunks<'a, T: Text + ?Sized>(parser: &mut Parser<'a, T>) -> Result<Vec<Hunk<'a, T>>> {
+fn hunks<'a, T: Text + ?Sized>(
+    parser: &mut Parser<'a, T>,
+    recount: bool,
+) -> Result<Vec<Hunk<'a, T>>> {
     let mut hunks = Vec::new();
 
     // Parse hunks while we see @@ headers.
@@ -225,7 +258,7 @@ fn hunks<'a, T: Text + ?Sized>(parser: &mut Parser<'a, T>) -> Result<Vec<Hunk<'a
     // Any trailing content (including hidden @@ headers) is silently ignored.
     // This is more permissive than git apply, which errors on junk between hunks.
     while parser.peek().is_some_and(|line| line.starts_with("@@ ")) {
-        hunks.push(hunk(parser)?);
+        hunks.push(hunk(parser, recount)?);
     }
 
     // check and verify that the Hunks are in sorted order and don't overlap
@@ -236,11 +269,20 @@ fn hunks<'a, T: Text + ?Sized>(parser: &mut Parser<'a, T>) -> Result<Vec<Hunk<'a
     Ok(hunks)
 }
 
-fn hunk<'a, T: Text + ?Sized>(parser: &mut Parser<'a, T>) -> Result<Hunk<'a, T>> {
+fn hunk<'a, T: Text + ?Sized>(parser: &mut Parser<'a, T>, recount: bool) -> Result<Hunk<'a, T>> {
     let hunk_start = parser.offset();
     let header_line = parser.next()?;
     let (range1, range2, function_context) =
         hunk_header(header_line).map_err(|e| parser.error_at(e.kind, hunk_start))?;
+
+    if recount {
+        let lines = recount_hunk_lines(parser, hunk_start)?;
+        let (old_len, new_len) = hunk_lines_count(&lines);
+        let range1 = HunkRange::following(range1.preceding(), old_len);
+        let range2 = HunkRange::following(range2.preceding(), new_len);
+        return Ok(Hunk::new(range1, range2, function_context, lines));
+    }
+
     let lines = hunk_lines(parser, range1.len, range2.len, hunk_start)?;
 
     Ok(Hunk::new(range1, range2, function_context, lines))
@@ -407,6 +449,96 @@ fn hunk_lines<'a, T: Text + ?Sized>(
     Ok(lines)
 }
 
+/// Parses the lines of a hunk without knowing how many lines to expect.
+///
+/// Like `git apply --recount`, the hunk ends at the first line
+/// which can't be part of it, such as the next hunk header,
+/// the `---`/`+++` headers of the next patch,
+/// or the signature at the end of a `git format-patch` email.
+fn recount_hunk_lines<'a, T: Text + ?Sized>(
+    parser: &mut Parser<'a, T>,
+    hunk_start: usize,
+) -> Result<Vec<Line<'a, T>>> {
+    let mut lines: Vec<Line<'a, T>> = Vec::new();
+    let mut no_newline_context = false;
+    let mut no_newline_delete = false;
+    let mut no_newline_insert = false;
+
+    while let Some(&line) = parser.peek() {
+        if line.starts_with(NO_NEWLINE_AT_EOF) {
+            let last_line = lines
+                .pop()
+                .ok_or_else(|| parser.error(ParsePatchErrorKind::UnexpectedNoNewlineMarker))?;
+            let modified = match last_line {
+                Line::Context(line) => {
+                    no_newline_context = true;
+                    Line::Context(strip_newline(line)?)
+                }
+                Line::Delete(line) => {
+                    no_newline_delete = true;
+                    Line::Delete(strip_newline(line)?)
+                }
+                Line::Insert(line) => {
+                    no_newline_insert = true;
+                    Line::Insert(strip_newline(line)?)
+                }
+            };
+            lines.push(modified);
+            parser.next()?;
+            continue;
+        }
+
+        let next = parser.peek_second();
+        let line = if no_newline_context
+            || (no_newline_delete && line.starts_with("-"))
+            || (no_newline_insert && line.starts_with("+"))
+            || line.starts_with("@")
+        {
+            // Nothing can follow the last line of the old or new file
+            break;
+        } else if is_next_patch(line, next) || is_signature(line, next) {
+            break;
+        } else if let Some(line) = line.strip_prefix(" ") {
+            Line::Context(line)
+        } else if line.starts_with("\n") {
+            Line::Context(line)
+        } else if let Some(line) = line.strip_prefix("-") {
+            Line::Delete(line)
+        } else if let Some(line) = line.strip_prefix("+") {
+            Line::Insert(line)
+        } else {
+            break;
+        };
+
+        lines.push(line);
+        parser.next()?;
+    }
+
+    if lines.is_empty() {
+        return Err(parser.error_at(ParsePatchErrorKind::HunkMismatch, hunk_start));
+    }
+
+    Ok(lines)
+}
+
+// Whether `line` is the `---` header of the next patch rather than the deletion of a line
+// starting with `-- `
+fn is_next_patch<T: Text + ?Sized>(line: &T, next: Option<&T>) -> bool {
+    line.starts_with("--- ") && next.is_some_and(|next| next.starts_with("+++ "))
+}
+
+// Whether `line` is the `-- ` line which starts the signature of a `git format-patch` email,
+// rather than the deletion of a line containing `- `
+fn is_signature<T: Text + ?Sized>(line: &T, next: Option<&T>) -> bool {
+    let is_hunk_line = |line: &T| {
+        [" ", "-", "+", "\\", "\n", "@"]
+            .iter()
+            .any(|prefix| line.starts_with(prefix))
+    };
+
+    line.as_bytes() == b"-- \n" && !next.is_some_and(is_hunk_line)
+}
+
 fn strip_newline<T: Text + ?Sized>(s: &T) -> Result<&T> {
     if let Some(stripped) = s.strip_suffix("\n") {
         Ok(stripped)
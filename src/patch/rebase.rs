@@ -0,0 +1,204 @@
+//! Rebase a patch onto another patch to the same file.
+
+use alloc::vec::Vec;
+use core::fmt;
+use core::ops::Range;
+
+use super::Hunk;
+use super::HunkRange;
+use super::Line;
+use super::hunk_lines_count;
+use super::merge_hunks;
+
+/// An error returned when rebasing a patch with [`Patch::rebase`] or [`Patch::commute`] fails.
+///
+/// [`Patch::rebase`]: crate::Patch::rebase
+/// [`Patch::commute`]: crate::Patch::commute
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RebaseError {
+    hunk: usize,
+}
+
+impl RebaseError {
+    /// Returns the index (1-based) of the hunk of the patch being rebased which conflicts with
+    /// the other patch.
+    pub fn hunk(&self) -> usize {
+        self.hunk
+    }
+}
+
+impl fmt::Display for RebaseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "error rebasing patch: hunk #{} conflicts with the other patch",
+            self.hunk
+        )
+    }
+}
+
+impl core::error::Error for RebaseError {}
+
+/// A run of changed lines in a patch.
+struct Change<'a, T: ?Sized> {
+    /// The lines of the original file which are deleted or replaced.
+    /// Lines which are only inserted have an empty range at the line they're inserted before.
+    range: Range<usize>,
+    /// The lines which replace `range`
+    inserted: Vec<&'a T>,
+}
+
+impl<T: ?Sized> Change<'_, T> {
+    /// The number of lines added by the change
+    fn delta(&self) -> isize {
+        self.inserted.len() as isize - self.range.len() as isize
+    }
+
+    fn conflicts_with(&self, other: &Change<'_, T>) -> bool {
+        let (a, b) = (&self.range, &other.range);
+        match (a.is_empty(), b.is_empty()) {
+            // Lines inserted at the same place could go in either order
+            (true, true) => a.start == b.start,
+            (true, false) => b.start < a.start && a.start < b.end,
+            (false, true) => a.start < b.start && b.start < a.end,
+            (false, false) => a.start < b.end && b.start < a.end,
+        }
+    }
+}
+
+fn changes<'a, T: ?Sized>(hunks: &[Hunk<'a, T>]) -> Vec<Change<'a, T>> {
+    let mut changes = Vec::new();
+
+    for hunk in hunks {
+        let mut pos = hunk.old_range.preceding();
+        let mut change: Option<Change<'a, T>> = None;
+        for line in &hunk.lines {
+            match line {
+                Line::Context(_) => {
+                    changes.extend(change.take());
+                    pos += 1;
+                }
+                Line::Delete(_) => {
+                    change
+                        .get_or_insert(Change {
+                            range: pos..pos,
+                            inserted: Vec::new(),
+                        })
+                        .range
+                        .end += 1;
+                    pos += 1;
+                }
+                Line::Insert(line) => {
+                    change
+                        .get_or_insert(Change {
+                            range: pos..pos,
+                            inserted: Vec::new(),
+                        })
+                        .inserted
+                        .push(*line);
+                }
+            }
+        }
+        changes.extend(change);
+    }
+
+    changes
+}
+
+/// Rewrites `hunks` to apply to the result of applying `onto`,
+/// where both apply to the same original file.
+pub(crate) fn rebase<'a, T: ?Sized>(
+    hunks: &[Hunk<'a, T>],
+    onto: &[Hunk<'a, T>],
+) -> Result<Vec<Hunk<'a, T>>, RebaseError> {
+    let onto = changes(onto);
+    let is_change = |line: &Line<'a, T>| !matches!(line, Line::Context(_));
+
+    let mut rebased = Vec::new();
+    for (i, hunk) in hunks.iter().enumerate() {
+        // Each piece has a single run of changes
+        for piece in hunk.split() {
+            let lines = &piece.lines;
+            let (Some(first), Some(last)) = (
+                lines.iter().position(is_change),
+                lines.iter().rposition(is_change),
+            ) else {
+                continue;
+            };
+            let last = last + 1;
+
+            let start = piece.old_range.preceding();
+            let change = start + first..start + first + hunk_lines_count(&lines[first..last]).0;
+            let end = change.end + (lines.len() - last);
+            let change = Change {
+                range: change,
+                inserted: Vec::new(),
+            };
+            if onto.iter().any(|other| other.conflicts_with(&change)) {
+                return Err(RebaseError { hunk: i + 1 });
+            }
+
+            // The changes of `onto` to the context lines, which are replaced by their result so
+            // that the hunk keeps its context. Changes which extend past the start of the hunk
+            // are included in full.
+            let mut context_changes = onto
+                .iter()
+                .filter(|other| {
+                    let r = &other.range;
+                    if r.is_empty() {
+                        start < r.start && r.start < end
+                    } else {
+                        r.start < end && r.end > start
+                    }
+                })
+                .peekable();
+            let context_start = context_changes
+                .peek()
+                .map_or(start, |other| other.range.start.min(start));
+
+            let mut rebased_lines = Vec::with_capacity(lines.len());
+            let mut pos = start;
+            // The end of the lines deleted by `onto`
+            let mut deleted_end = start;
+            for line in lines {
+                if matches!(line, Line::Insert(_)) {
+                    rebased_lines.push(*line);
+                    continue;
+                }
+                while let Some(other) = context_changes.next_if(|other| other.range.start <= pos) {
+                    rebased_lines.extend(other.inserted.iter().map(|&l| Line::Context(l)));
+                    deleted_end = other.range.end;
+                }
+                if pos >= deleted_end {
+                    rebased_lines.push(*line);
+                }
+                pos += 1;
+            }
+            let lines = rebased_lines;
+
+            let offset: isize = onto
+                .iter()
+                .filter(|other| other.range.end <= context_start)
+                .map(Change::delta)
+                .sum();
+            let preceding = context_start.saturating_add_signed(offset);
+            let (old_len, new_len) = hunk_lines_count(&lines);
+            rebased.push((
+                preceding,
+                Hunk::new(
+                    HunkRange::following(preceding, old_len),
+                    HunkRange::following(preceding, new_len),
+                    piece.function_context,
+                    lines,
+                ),
+            ));
+        }
+    }
+
+    Ok(merge_hunks(
+        rebased
+            .iter()
+            .map(|(preceding, hunk)| (*preceding, hunk))
+            .collect(),
+    ))
+}
@@ -709,3 +709,806 @@ mod error_display {
         assert_eq!(err.kind, ParsePatchErrorKind::InvalidHunkHeader);
     }
 }
+
+mod split {
+    use crate::Patch;
+    use crate::apply;
+    use crate::create_patch;
+    use alloc::string::String;
+    use alloc::string::ToString;
+    use alloc::vec::Vec;
+
+    const ORIGINAL: &str = "a\nb\nc\nd\ne\nf\ng\nh\ni\nj\n";
+    const MODIFIED: &str = "a\nB\nc\nd\nX\ne\ng\nh\ni\nj\n";
+
+    #[test]
+    fn split_hunk() {
+        let patch = create_patch(ORIGINAL, MODIFIED);
+        assert_eq!(patch.hunks().len(), 1);
+
+        let split = patch.split_hunk(0);
+        assert_eq!(
+            split.to_string(),
+            "\
+--- original
++++ modified
+@@ -1,4 +1,4 @@
+ a
+-b
++B
+ c
+ d
+@@ -3,3 +3,4 @@
+ c
+ d
++X
+ e
+@@ -5,5 +6,4 @@
+ e
+-f
+ g
+ h
+ i
+"
+        );
+        assert_eq!(split.hunks()[0].split().len(), 1);
+
+        // Selecting every hunk merges the overlapping hunks back together
+        let (selected, remaining) = split.partition(|_, _| true);
+        assert_eq!(selected, patch);
+        assert!(remaining.hunks().is_empty());
+        assert_eq!(Patch::from_str(&selected.to_string()).unwrap(), patch);
+    }
+
+    #[test]
+    fn partition_every_subset() {
+        let patch = create_patch(ORIGINAL, MODIFIED).split_hunk(0);
+        let n = patch.hunks().len();
+        assert_eq!(n, 3);
+
+        for mask in 0..1 << n {
+            let (selected, remaining) = patch.partition(|i, _| mask & (1 << i) != 0);
+            let partial = apply(ORIGINAL, &selected).unwrap();
+            let expected: String = ORIGINAL
+                .lines()
+                .filter_map(|line| match line {
+                    "b" if mask & 1 != 0 => Some("B\n".to_string()),
+                    "d" if mask & 2 != 0 => Some("d\nX\n".to_string()),
+                    "f" if mask & 4 != 0 => None,
+                    line => Some(line.to_string() + "\n"),
+                })
+                .collect();
+            assert_eq!(partial, expected, "{mask}");
+            assert_eq!(apply(&partial, &remaining).unwrap(), MODIFIED, "{mask}");
+        }
+    }
+
+    #[test]
+    fn partition_adjusts_line_numbers() {
+        let original: String = (0..30).map(|i| i.to_string() + "\n").collect();
+        let modified = original
+            .replacen("2\n", "2\nnew\nnew\n", 1)
+            .replace("25\n", "twenty-five\n");
+        let patch = create_patch(&original, &modified);
+        assert_eq!(patch.hunks().len(), 2);
+
+        let (selected, remaining) = patch.partition(|i, _| i == 1);
+        let hunks: Vec<_> = selected
+            .hunks()
+            .iter()
+            .map(|hunk| (hunk.old_range().start(), hunk.new_range().start()))
+            .collect();
+        assert_eq!(hunks, [(23, 23)]);
+        let hunks: Vec<_> = remaining
+            .hunks()
+            .iter()
+            .map(|hunk| (hunk.old_range().start(), hunk.new_range().start()))
+            .collect();
+        assert_eq!(hunks, [(1, 1)]);
+
+        let partial = apply(&original, &selected).unwrap();
+        assert_eq!(partial, original.replace("25\n", "twenty-five\n"));
+        assert_eq!(apply(&partial, &remaining).unwrap(), modified);
+
+        let (selected, remaining) = patch.partition(|i, _| i == 0);
+        assert_eq!(remaining.hunks()[0].old_range().start(), 25);
+        assert_eq!(remaining.hunks()[0].new_range().start(), 25);
+        let partial = apply(&original, &selected).unwrap();
+        assert_eq!(apply(&partial, &remaining).unwrap(), modified);
+    }
+}
+
+mod recount {
+    use crate::Patch;
+    use crate::apply;
+    use crate::patch_set::ParseOptions;
+    use crate::patch_set::PatchSet;
+    use alloc::vec::Vec;
+
+    fn ranges(patch: &Patch<'_, str>) -> Vec<(usize, usize, usize, usize)> {
+        patch
+            .hunks()
+            .iter()
+            .map(|hunk| {
+                let (old, new) = (hunk.old_range(), hunk.new_range());
+                (old.start(), old.len(), new.start(), new.len())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn wrong_counts() {
+        let s = "\
+--- a/file.txt
++++ b/file.txt
+@@ -1,2 +1,9 @@ fn main
+ a
+-b
++B
++B2
+ c
+@@ -10 +10 @@
+ j
+-k
+";
+        assert!(Patch::from_str(s).is_err());
+
+        let patch = Patch::from_str_recount(s).unwrap();
+        assert_eq!(ranges(&patch), [(1, 3, 1, 4), (10, 2, 10, 1)]);
+        assert_eq!(patch.hunks()[0].function_context(), Some("fn main\n"));
+
+        let original = "a\nb\nc\nd\ne\nf\ng\nh\ni\nj\nk\n";
+        let expected = "a\nB\nB2\nc\nd\ne\nf\ng\nh\ni\nj\n";
+        assert_eq!(apply(original, &patch).unwrap(), expected);
+
+        let patch = Patch::from_bytes_recount(s.as_bytes()).unwrap();
+        assert_eq!(patch.hunks().len(), 2);
+    }
+
+    #[test]
+    fn empty_ranges_are_adjusted() {
+        let s = "\
+--- a/file.txt
++++ b/file.txt
+@@ -0,0 +1 @@
+ a
++b
+";
+        let patch = Patch::from_str_recount(s).unwrap();
+        assert_eq!(ranges(&patch), [(1, 1, 1, 2)]);
+
+        let s = "\
+--- a/file.txt
++++ b/file.txt
+@@ -3 +3,4 @@
++b
+";
+        let patch = Patch::from_str_recount(s).unwrap();
+        assert_eq!(ranges(&patch), [(2, 0, 3, 1)]);
+    }
+
+    #[test]
+    fn no_newline_at_eof() {
+        let s = "\
+--- a/file.txt
++++ b/file.txt
+@@ -1 +1 @@
+ a
+-b
+\\ No newline at end of file
++B
+\\ No newline at end of file
+";
+        let patch = Patch::from_str_recount(s).unwrap();
+        assert_eq!(ranges(&patch), [(1, 2, 1, 2)]);
+        assert_eq!(apply("a\nb", &patch).unwrap(), "a\nB");
+    }
+
+    #[test]
+    fn empty_hunk_is_rejected() {
+        let s = "\
+--- a/file.txt
++++ b/file.txt
+@@ -1 +1 @@
+";
+        assert!(Patch::from_str_recount(s).is_err());
+    }
+
+    #[test]
+    fn stops_at_signature() {
+        let s = "\
+--- a/file.txt
++++ b/file.txt
+@@ -1 +1 @@
+-a
++A
+-- 
+2.43.0
+
+";
+        let patch = Patch::from_str_recount(s).unwrap();
+        assert_eq!(ranges(&patch), [(1, 1, 1, 1)]);
+
+        // A deleted line containing `- ` followed by more of the hunk isn't a signature
+        let s = "\
+--- a/file.txt
++++ b/file.txt
+@@ -1 +1 @@
+-a
+-- 
++A
+";
+        let patch = Patch::from_str_recount(s).unwrap();
+        assert_eq!(ranges(&patch), [(1, 2, 1, 1)]);
+    }
+
+    #[test]
+    fn patch_set_stops_at_next_patch() {
+        let s = "\
+--- a/alpha
++++ b/alpha
+@@ -1,5 +1,5 @@
+-alpha
++ALPHA
+--- a/beta
++++ b/beta
+@@ -1 +1,3 @@
+-beta
++BETA
+";
+        let mut opts = ParseOptions::unidiff();
+        assert!(
+            PatchSet::parse(s, opts.clone())
+                .collect::<Result<Vec<_>, _>>()
+                .is_err()
+        );
+
+        opts.set_recount(true);
+        let patches = PatchSet::parse(s, opts)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let ranges: Vec<_> = patches
+            .iter()
+            .map(|patch| ranges(patch.patch().as_text().unwrap()))
+            .collect();
+        assert_eq!(ranges, [[(1, 1, 1, 1)], [(1, 1, 1, 1)]]);
+    }
+}
+
+mod compose {
+    use crate::DiffOptions;
+    use crate::Patch;
+    use crate::apply;
+    use crate::create_patch;
+    use alloc::string::String;
+    use alloc::string::ToString;
+    use alloc::vec::Vec;
+
+    /// A small deterministic generator of text edits
+    pub(super) struct Edits(pub(super) u64);
+
+    impl Edits {
+        fn next(&mut self, n: u64) -> u64 {
+            self.0 = self
+                .0
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (self.0 >> 33) % n
+        }
+
+        pub(super) fn edit(&mut self, text: &str) -> String {
+            let mut lines: Vec<String> = text.lines().map(|l| l.into()).collect();
+            for _ in 0..=self.next(4) {
+                let pos = self.next(lines.len() as u64 + 1) as usize;
+                match self.next(3) {
+                    0 => lines.insert(pos, alloc::format!("new {}", self.next(1000))),
+                    1 if pos < lines.len() => {
+                        lines.remove(pos);
+                    }
+                    _ if pos < lines.len() => lines[pos].push('!'),
+                    _ => {}
+                }
+            }
+            lines.into_iter().map(|l| l + "\n").collect()
+        }
+    }
+
+    #[test]
+    fn compose_random_edits() {
+        let v1: String = (0..40).map(|i| alloc::format!("line {i}\n")).collect();
+        let mut edits = Edits(1);
+
+        for context_len in [0, 1, 3] {
+            let mut options = DiffOptions::new();
+            options.set_context_len(context_len);
+
+            for _ in 0..200 {
+                let v2 = edits.edit(&v1);
+                let v3 = edits.edit(&v2);
+                let first = options.create_patch(&v1, &v2);
+                let second = options.create_patch(&v2, &v3);
+
+                let patch = first.compose(&second).unwrap();
+                assert_eq!(
+                    apply(&v1, &patch).unwrap(),
+                    v3,
+                    "\n{first}\n{second}\n{patch}"
+                );
+                // The composed patch is well formed
+                assert_eq!(Patch::from_str(&patch.to_string()).unwrap(), patch);
+            }
+        }
+    }
+
+    #[test]
+    fn changes_which_cancel_out() {
+        let v1 = "a\nb\nc\n";
+        let v2 = "a\nb\nB\nc\n";
+
+        let patch = create_patch(v1, v2).compose(&create_patch(v2, v1)).unwrap();
+        assert!(patch.hunks().is_empty());
+    }
+
+    #[test]
+    fn overlapping_hunks() {
+        let v1 = "a\nb\nc\nd\ne\n";
+        let v2 = "a\nB\nc\nd\ne\n";
+        let v3 = "a\nB\nC\nd\ne\n";
+
+        let patch = create_patch(v1, v2).compose(&create_patch(v2, v3)).unwrap();
+        assert_eq!(
+            patch.to_string(),
+            "\
+--- original
++++ modified
+@@ -1,5 +1,5 @@
+ a
+-b
+-c
++B
++C
+ d
+ e
+"
+        );
+    }
+
+    #[test]
+    fn disagreeing_patches() {
+        let first = create_patch("a\nb\nc\n", "a\nB\nc\n");
+        let second = create_patch("a\nb\nc\n", "a\nb\nC\n");
+
+        let err = first.compose(&second).unwrap_err();
+        assert_eq!(err.line(), 2);
+        assert_eq!(
+            err.to_string(),
+            "error composing patches: line 2 of the intermediate file differs between patches"
+        );
+    }
+}
+
+mod rebase {
+    use super::compose::Edits;
+    use crate::DiffOptions;
+    use crate::apply;
+    use crate::create_patch;
+    use alloc::string::String;
+    use alloc::string::ToString;
+
+    #[test]
+    fn rebase_random_edits() {
+        let v1: String = (0..40).map(|i| alloc::format!("line {i}\n")).collect();
+        let mut edits = Edits(2);
+        let mut rebased = 0;
+
+        for context_len in [0, 1, 3] {
+            let mut options = DiffOptions::new();
+            options.set_context_len(context_len);
+
+            for _ in 0..200 {
+                let (ours, theirs) = (edits.edit(&v1), edits.edit(&v1));
+                let ours = options.create_patch(&v1, &ours);
+                let theirs = options.create_patch(&v1, &theirs);
+
+                let (Ok(ours_rebased), Ok(theirs_rebased)) =
+                    (ours.rebase(&theirs), theirs.rebase(&ours))
+                else {
+                    continue;
+                };
+                rebased += 1;
+
+                let a = apply(&apply(&v1, &theirs).unwrap(), &ours_rebased);
+                let b = apply(&apply(&v1, &ours).unwrap(), &theirs_rebased);
+                assert_eq!(
+                    a.unwrap(),
+                    b.unwrap(),
+                    "\n{ours}\n{theirs}\n{ours_rebased}\n{theirs_rebased}"
+                );
+            }
+        }
+
+        // Make sure the test isn't vacuous
+        assert!(rebased > 100, "{rebased}");
+    }
+
+    #[test]
+    fn commute_random_edits() {
+        let v1: String = (0..40).map(|i| alloc::format!("line {i}\n")).collect();
+        let mut edits = Edits(3);
+        let mut commuted = 0;
+
+        for _ in 0..200 {
+            let v2 = edits.edit(&v1);
+            let v3 = edits.edit(&v2);
+            let first = create_patch(&v1, &v2);
+            let second = create_patch(&v2, &v3);
+
+            if let Ok((second_first, first_second)) = first.commute(&second) {
+                commuted += 1;
+                let patched = apply(&v1, &second_first).unwrap();
+                assert_eq!(apply(&patched, &first_second).unwrap(), v3);
+            }
+        }
+
+        assert!(commuted > 50, "{commuted}");
+    }
+
+    #[test]
+    fn context_changed_by_other_patch_is_replaced() {
+        let original = "a\nb\nc\nd\ne\n";
+        let ours = create_patch(original, "a\nb\nC\nd\ne\n");
+        let theirs = create_patch(original, "A\nb\nc\nd\nE\n");
+
+        let rebased = ours.rebase(&theirs).unwrap();
+        assert_eq!(
+            rebased.to_string(),
+            "\
+--- original
++++ modified
+@@ -1,5 +1,5 @@
+ A
+ b
+-c
++C
+ d
+ E
+"
+        );
+        assert_eq!(
+            apply("A\nb\nc\nd\nE\n", &rebased).unwrap(),
+            "A\nb\nC\nd\nE\n"
+        );
+    }
+
+    #[test]
+    fn all_context_changed_by_other_patch() {
+        let original = "a\nb\nc\nb\nd\n";
+        let ours = create_patch(original, "a\nb\nc\nB\nd\n");
+        let theirs = create_patch(original, "a\nb\nc\nC\nb\nD\n");
+
+        // Every context line of our hunk is changed, so the context is taken from the text
+        // `theirs` produces, which keeps the hunk from applying to the first "b"
+        let rebased = ours.rebase(&theirs).unwrap();
+        assert_eq!(
+            rebased.to_string(),
+            "\
+--- original
++++ modified
+@@ -1,6 +1,6 @@
+ a
+ b
+ c
+ C
+-b
++B
+ D
+"
+        );
+        assert_eq!(
+            apply("a\nb\nc\nC\nb\nD\n", &rebased).unwrap(),
+            "a\nb\nc\nC\nB\nD\n"
+        );
+
+        // Changes which extend past the hunk are included in full, and deleted lines are dropped
+        let original = "1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n";
+        let ours = create_patch(original, "1\n2\n3\n4\nF\n6\n7\n8\n9\n10\n");
+        let theirs = create_patch(original, "X\n4\n5\n6\n10\n");
+        let rebased = ours.rebase(&theirs).unwrap();
+        assert_eq!(
+            rebased.to_string(),
+            "\
+--- original
++++ modified
+@@ -1,4 +1,4 @@
+ X
+ 4
+-5
++F
+ 6
+"
+        );
+        assert_eq!(
+            apply("X\n4\n5\n6\n10\n", &rebased).unwrap(),
+            "X\n4\nF\n6\n10\n"
+        );
+    }
+
+    #[test]
+    fn conflicts() {
+        let original = "a\nb\nc\n";
+        let ours = create_patch(original, "a\nB\nc\n");
+
+        // Overlapping changes
+        let theirs = create_patch(original, "a\nbb\nc\n");
+        let err = ours.rebase(&theirs).unwrap_err();
+        assert_eq!(err.hunk(), 1);
+        assert_eq!(
+            err.to_string(),
+            "error rebasing patch: hunk #1 conflicts with the other patch"
+        );
+
+        // Insertions at the same place
+        let ours = create_patch(original, "a\nb\nours\nc\n");
+        let theirs = create_patch(original, "a\nb\ntheirs\nc\n");
+        assert!(ours.rebase(&theirs).is_err());
+
+        // Adjacent changes
+        let theirs = create_patch(original, "a\nb\nC\n");
+        let rebased = ours.rebase(&theirs).unwrap();
+        assert_eq!(apply("a\nb\nC\n", &rebased).unwrap(), "a\nb\nours\nC\n");
+    }
+}
+
+mod interdiff {
+    use super::compose::Edits;
+    use crate::DiffOptions;
+    use crate::Patch;
+    use crate::apply;
+    use crate::create_patch;
+    use alloc::string::String;
+    use alloc::string::ToString;
+
+    #[test]
+    fn interdiff_random_edits() {
+        let v1: String = (0..40).map(|i| alloc::format!("line {i}\n")).collect();
+        let mut edits = Edits(4);
+
+        for context_len in [0, 1, 3] {
+            let mut options = DiffOptions::new();
+            options.set_context_len(context_len);
+
+            for _ in 0..200 {
+                let v2 = edits.edit(&v1);
+                // Revise the first patch, so that the two have changes in common
+                let v3 = edits.edit(&v2);
+                let first = options.create_patch(&v1, &v2);
+                let second = options.create_patch(&v1, &v3);
+
+                let patch = first.interdiff(&second).unwrap();
+                assert_eq!(
+                    apply(&v2, &patch).unwrap(),
+                    v3,
+                    "\n{first}\n{second}\n{patch}"
+                );
+                assert_eq!(Patch::from_str(&patch.to_string()).unwrap(), patch);
+            }
+        }
+    }
+
+    #[test]
+    fn identical_patches() {
+        let patch = create_patch("a\nb\nc\n", "a\nB\nc\n");
+        assert!(patch.interdiff(&patch).unwrap().hunks().is_empty());
+    }
+
+    #[test]
+    fn changes_in_common_are_omitted() {
+        let lines = |changed: &[(usize, &str)]| -> String {
+            (0..20)
+                .map(|i| match changed.iter().find(|(n, _)| *n == i) {
+                    Some((_, line)) => alloc::format!("{line}\n"),
+                    None => alloc::format!("{i}\n"),
+                })
+                .collect()
+        };
+        let original = lines(&[]);
+        let v2 = lines(&[(2, "two")]);
+        let v3 = lines(&[(2, "two"), (10, "ten"), (18, "eighteen")]);
+        let first = create_patch(&original, &v2);
+        let second = create_patch(&original, &v3);
+
+        assert_eq!(
+            first.interdiff(&second).unwrap().to_string(),
+            "\
+--- modified
++++ modified
+@@ -8,7 +8,7 @@
+ 7
+ 8
+ 9
+-10
++ten
+ 11
+ 12
+ 13
+@@ -16,5 +16,5 @@
+ 15
+ 16
+ 17
+-18
++eighteen
+ 19
+"
+        );
+    }
+
+    #[test]
+    fn patches_to_different_originals() {
+        let first = create_patch("a\nb\nc\n", "a\nB\nc\n");
+        let second = create_patch("a\nbb\nc\n", "a\nB\nc\n");
+        assert_eq!(first.interdiff(&second).unwrap_err().line(), 2);
+    }
+}
+
+mod line_map {
+    use super::compose::Edits;
+    use crate::DiffOptions;
+    use crate::LineMapping;
+    use crate::create_patch;
+    use alloc::string::String;
+    use alloc::vec::Vec;
+
+    #[test]
+    fn map_lines_random_edits() {
+        let original: String = (0..40).map(|i| alloc::format!("line {i}\n")).collect();
+        let mut edits = Edits(5);
+
+        for context_len in [0, 1, 3] {
+            let mut options = DiffOptions::new();
+            options.set_context_len(context_len);
+
+            for _ in 0..200 {
+                let modified = edits.edit(&original);
+                let patch = options.create_patch(&original, &modified);
+                let old: Vec<_> = original.lines().collect();
+                let new: Vec<_> = modified.lines().collect();
+
+                for line in 1..=old.len() {
+                    match patch.map_original_line(line) {
+                        LineMapping::Unchanged(n) | LineMapping::Context(n) => {
+                            assert_eq!(old[line - 1], new[n - 1], "\n{patch}");
+                            assert!(matches!(
+                                patch.map_modified_line(n),
+                                LineMapping::Unchanged(l) | LineMapping::Context(l) if l == line
+                            ));
+                        }
+                        LineMapping::Deleted(n) => {
+                            assert!((1..=new.len() + 1).contains(&n), "\n{patch}");
+                            assert!(!new.contains(&old[line - 1]), "\n{patch}");
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn map_lines() {
+        let original = "a\nb\nc\nd\ne\nf\ng\nh\ni\nj\nk\nl\n";
+        let modified = "a\nb\nc\nd\ne\nF\ng\nh\ni\nj\nk\nl\nm\n";
+        let patch = create_patch(original, modified);
+
+        assert_eq!(patch.map_original_line(2), LineMapping::Unchanged(2));
+        assert_eq!(patch.map_original_line(3), LineMapping::Context(3));
+        assert_eq!(patch.map_original_line(6), LineMapping::Deleted(6));
+        assert_eq!(patch.map_original_line(12), LineMapping::Context(12));
+        assert_eq!(patch.map_modified_line(6), LineMapping::Deleted(6));
+        assert_eq!(patch.map_modified_line(13), LineMapping::Deleted(13));
+
+        // Lines past the end of the original are moved by the patch
+        let patch = create_patch("a\n", "new\na\n");
+        assert_eq!(patch.map_original_line(5), LineMapping::Unchanged(6));
+    }
+}
+
+mod recontext {
+    use super::compose::Edits;
+    use crate::DiffOptions;
+    use crate::Patch;
+    use crate::create_patch;
+    use alloc::string::String;
+    use alloc::string::ToString;
+
+    #[test]
+    fn recontext_random_edits() {
+        let original: String = (0..40).map(|i| alloc::format!("line {i}\n")).collect();
+        let mut edits = Edits(6);
+
+        for _ in 0..100 {
+            let modified = edits.edit(&original);
+            for from in [0, 1, 3, 10] {
+                let patch = DiffOptions::new()
+                    .set_context_len(from)
+                    .create_patch(&original, &modified);
+                for to in [0, 1, 3, 10] {
+                    let expected = DiffOptions::new()
+                        .set_context_len(to)
+                        .create_patch(&original, &modified);
+                    assert_eq!(
+                        patch.recontext(&original, to).unwrap(),
+                        expected,
+                        "\n{patch}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn appending_to_end_of_file() {
+        let original = "l0\nl1\nl2\nl3\nl4\nl5\nl6\n";
+        let modified = "l0\nl1\nl2\nl3!\nl5\nl6\nnew\n";
+        let patch = DiffOptions::new()
+            .set_context_len(0)
+            .create_patch(original, modified);
+        let expected = "\
+--- original
++++ modified
+@@ -3,5 +3,5 @@
+ l2
+-l3
+-l4
++l3!
+ l5
+ l6
++new
+";
+
+        let recontexted = patch.recontext(original, 1).unwrap();
+        assert_eq!(recontexted.to_string(), expected);
+        assert_eq!(
+            recontexted,
+            DiffOptions::new()
+                .set_context_len(1)
+                .create_patch(original, modified)
+        );
+    }
+
+    #[test]
+    fn drifted_line_numbers() {
+        let original = "x\ny\na\nb\nc\nd\ne\n";
+        let patch = Patch::from_str(
+            "\
+--- a
++++ b
+@@ -1,2 +1,2 @@
+ b
+-c
++C
+",
+        )
+        .unwrap();
+
+        assert_eq!(
+            patch.recontext(original, 3).unwrap().to_string(),
+            "\
+--- a
++++ b
+@@ -2,6 +2,6 @@
+ y
+ a
+ b
+-c
++C
+ d
+ e
+"
+        );
+    }
+
+    #[test]
+    fn patch_which_does_not_apply() {
+        let patch = create_patch("a\nb\nc\n", "a\nB\nc\n");
+        assert_eq!(patch.recontext("x\ny\nz\n", 3).unwrap_err().hunk(), 1);
+    }
+}
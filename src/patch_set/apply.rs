@@ -0,0 +1,1131 @@
+//! Apply multi-file patches to a store of files.
+
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+use core::iter;
+
+use super::FileMode;
+use super::FileOperation;
+use super::FilePatch;
+use super::PatchKind;
+use super::PatchSet;
+use super::PatchSetParseError;
+use crate::apply::ApplyError;
+use crate::apply::ApplyOptions;
+use crate::binary::BinaryPatchParseError;
+use PatchSetApplyErrorKind::Store;
+
+/// A store of files which file patches can be applied to.
+///
+/// Paths are the raw bytes of the paths found in a patch,
+/// after stripping any leading components (see [`PatchSetApplyOptions::set_strip`]).
+///
+/// [`MemoryStore`] provides an in-memory implementation
+/// and, with the `std` feature enabled,
+/// [`FsStore`] provides an implementation backed by a directory on disk.
+pub trait FileStore {
+    /// The error returned when an operation on the store fails.
+    type Error;
+
+    /// Returns the contents of the file at `path`.
+    fn read(&self, path: &[u8]) -> Result<Vec<u8>, Self::Error>;
+
+    /// Writes `contents` to the file at `path`, creating the file if it doesn't exist.
+    fn write(&mut self, path: &[u8], contents: Vec<u8>) -> Result<(), Self::Error>;
+
+    /// Removes the file at `path`.
+    fn remove(&mut self, path: &[u8]) -> Result<(), Self::Error>;
+
+    /// Moves the file at `from` to `to`.
+    fn rename(&mut self, from: &[u8], to: &[u8]) -> Result<(), Self::Error>;
+
+    /// Returns the mode of the file at `path`.
+    fn mode(&self, path: &[u8]) -> Result<FileMode, Self::Error>;
+
+    /// Sets the mode of the file at `path`.
+    fn set_mode(&mut self, path: &[u8], mode: FileMode) -> Result<(), Self::Error>;
+}
+
+/// A file stored in a [`MemoryStore`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MemoryFile {
+    contents: Vec<u8>,
+    mode: FileMode,
+}
+
+impl MemoryFile {
+    /// Returns the contents of the file.
+    pub fn contents(&self) -> &[u8] {
+        &self.contents
+    }
+
+    /// Returns the mode of the file.
+    pub fn mode(&self) -> FileMode {
+        self.mode
+    }
+}
+
+/// An in-memory [`FileStore`].
+///
+/// Files written without an explicit mode are [`FileMode::Regular`].
+///
+/// # Example
+///
+/// ```
+/// use diffy::patch_set::MemoryStore;
+/// use diffy::patch_set::ParseOptions;
+/// use diffy::patch_set::PatchSet;
+/// use diffy::patch_set::PatchSetApplyOptions;
+///
+/// let input = b"\
+/// diff --git a/alpha.txt b/alpha.txt
+/// --- a/alpha.txt
+/// +++ b/alpha.txt
+/// @@ -1 +1 @@
+/// -alpha
+/// +ALPHA
+/// diff --git a/alpha.txt b/beta.txt
+/// similarity index 100%
+/// copy from alpha.txt
+/// copy to beta.txt
+/// ";
+///
+/// let mut store = MemoryStore::new();
+/// store.insert("alpha.txt", "alpha\n");
+///
+/// let patches = PatchSet::parse_bytes(input, ParseOptions::gitdiff());
+/// PatchSetApplyOptions::new()
+///     .apply(&mut store, patches)
+///     .unwrap();
+///
+/// assert_eq!(store.get("alpha.txt").unwrap().contents(), b"ALPHA\n");
+/// assert_eq!(store.get("beta.txt").unwrap().contents(), b"ALPHA\n");
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MemoryStore {
+    files: BTreeMap<Vec<u8>, MemoryFile>,
+}
+
+impl MemoryStore {
+    /// Constructs a new, empty `MemoryStore`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Inserts a regular file, replacing any existing file at `path`.
+    pub fn insert(&mut self, path: impl AsRef<[u8]>, contents: impl AsRef<[u8]>) {
+        self.files.insert(
+            path.as_ref().to_vec(),
+            MemoryFile {
+                contents: contents.as_ref().to_vec(),
+                mode: FileMode::Regular,
+            },
+        );
+    }
+
+    /// Returns the file at `path`, if any.
+    pub fn get(&self, path: impl AsRef<[u8]>) -> Option<&MemoryFile> {
+        self.files.get(path.as_ref())
+    }
+
+    /// Returns an iterator over the paths and files in the store, ordered by path.
+    pub fn iter(&self) -> impl Iterator<Item = (&[u8], &MemoryFile)> {
+        self.files
+            .iter()
+            .map(|(path, file)| (path.as_slice(), file))
+    }
+
+    /// Returns the number of files in the store.
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    /// Returns `true` if the store contains no files.
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    fn get_mut(&mut self, path: &[u8]) -> Result<&mut MemoryFile, MemoryStoreError> {
+        self.files
+            .get_mut(path)
+            .ok_or_else(|| MemoryStoreError::new(path))
+    }
+}
+
+impl FileStore for MemoryStore {
+    type Error = MemoryStoreError;
+
+    fn read(&self, path: &[u8]) -> Result<Vec<u8>, Self::Error> {
+        self.files
+            .get(path)
+            .map(|file| file.contents.clone())
+            .ok_or_else(|| MemoryStoreError::new(path))
+    }
+
+    fn write(&mut self, path: &[u8], contents: Vec<u8>) -> Result<(), Self::Error> {
+        match self.files.get_mut(path) {
+            Some(file) => file.contents = contents,
+            None => {
+                self.files.insert(
+                    path.to_vec(),
+                    MemoryFile {
+                        contents,
+                        mode: FileMode::Regular,
+                    },
+                );
+            }
+        }
+        Ok(())
+    }
+
+    fn remove(&mut self, path: &[u8]) -> Result<(), Self::Error> {
+        self.files
+            .remove(path)
+            .map(|_| ())
+            .ok_or_else(|| MemoryStoreError::new(path))
+    }
+
+    fn rename(&mut self, from: &[u8], to: &[u8]) -> Result<(), Self::Error> {
+        let file = self
+            .files
+            .remove(from)
+            .ok_or_else(|| MemoryStoreError::new(from))?;
+        self.files.insert(to.to_vec(), file);
+        Ok(())
+    }
+
+    fn mode(&self, path: &[u8]) -> Result<FileMode, Self::Error> {
+        self.files
+            .get(path)
+            .map(|file| file.mode)
+            .ok_or_else(|| MemoryStoreError::new(path))
+    }
+
+    fn set_mode(&mut self, path: &[u8], mode: FileMode) -> Result<(), Self::Error> {
+        self.get_mut(path)?.mode = mode;
+        Ok(())
+    }
+}
+
+/// An error returned by a [`MemoryStore`] when a file doesn't exist.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MemoryStoreError {
+    path: Vec<u8>,
+}
+
+impl MemoryStoreError {
+    fn new(path: &[u8]) -> Self {
+        Self {
+            path: path.to_vec(),
+        }
+    }
+
+    /// Returns the path of the file which doesn't exist.
+    pub fn path(&self) -> &[u8] {
+        &self.path
+    }
+}
+
+impl fmt::Display for MemoryStoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "no such file: {}", String::from_utf8_lossy(&self.path))
+    }
+}
+
+impl core::error::Error for MemoryStoreError {}
+
+/// A [`FileStore`] backed by a directory on disk.
+///
+/// Paths are resolved relative to the root directory.
+/// Parent directories are created as needed when writing or renaming files.
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+#[derive(Debug, Clone)]
+pub struct FsStore {
+    root: std::path::PathBuf,
+}
+
+#[cfg(feature = "std")]
+impl FsStore {
+    /// Constructs a new `FsStore` rooted at the directory `root`.
+    pub fn new(root: impl Into<std::path::PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    fn path(&self, path: &[u8]) -> std::io::Result<std::path::PathBuf> {
+        #[cfg(unix)]
+        let path = {
+            use std::os::unix::ffi::OsStrExt;
+            std::path::Path::new(std::ffi::OsStr::from_bytes(path))
+        };
+        #[cfg(not(unix))]
+        let path = std::path::Path::new(
+            core::str::from_utf8(path)
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?,
+        );
+
+        Ok(self.root.join(path))
+    }
+
+    fn create_parent_dirs(path: &std::path::Path) -> std::io::Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "std")]
+impl FileStore for FsStore {
+    type Error = std::io::Error;
+
+    fn read(&self, path: &[u8]) -> Result<Vec<u8>, Self::Error> {
+        std::fs::read(self.path(path)?)
+    }
+
+    fn write(&mut self, path: &[u8], contents: Vec<u8>) -> Result<(), Self::Error> {
+        let path = self.path(path)?;
+        Self::create_parent_dirs(&path)?;
+        std::fs::write(path, contents)
+    }
+
+    fn remove(&mut self, path: &[u8]) -> Result<(), Self::Error> {
+        std::fs::remove_file(self.path(path)?)
+    }
+
+    fn rename(&mut self, from: &[u8], to: &[u8]) -> Result<(), Self::Error> {
+        let to = self.path(to)?;
+        Self::create_parent_dirs(&to)?;
+        std::fs::rename(self.path(from)?, to)
+    }
+
+    fn mode(&self, path: &[u8]) -> Result<FileMode, Self::Error> {
+        let metadata = std::fs::symlink_metadata(self.path(path)?)?;
+        if metadata.file_type().is_symlink() {
+            return Ok(FileMode::Symlink);
+        }
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+
+            if metadata.permissions().mode() & 0o111 != 0 {
+                return Ok(FileMode::Executable);
+            }
+        }
+
+        Ok(FileMode::Regular)
+    }
+
+    fn set_mode(&mut self, path: &[u8], mode: FileMode) -> Result<(), Self::Error> {
+        let path = self.path(path)?;
+        match mode {
+            #[cfg(unix)]
+            FileMode::Regular | FileMode::Executable => {
+                use std::os::unix::fs::PermissionsExt;
+
+                let mut permissions = std::fs::metadata(&path)?.permissions();
+                let executable = 0o111 & (permissions.mode() & 0o444) >> 2;
+                let mode = if mode == FileMode::Executable {
+                    permissions.mode() | executable
+                } else {
+                    permissions.mode() & !0o111
+                };
+                permissions.set_mode(mode);
+                std::fs::set_permissions(path, permissions)
+            }
+            // Executable bits aren't tracked outside of unix
+            #[cfg(not(unix))]
+            FileMode::Regular | FileMode::Executable => std::fs::metadata(path).map(|_| ()),
+            FileMode::Symlink | FileMode::Gitlink => Err(std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                "symlinks and gitlinks are not supported",
+            )),
+        }
+    }
+}
+
+/// A collection of options for modifying the way file patches are applied to a [`FileStore`].
+///
+/// Text patches are applied using [`ApplyOptions`]
+/// and binary patches using [`BinaryPatch::apply`]
+/// (which requires the `binary` feature).
+/// Creating, deleting, renaming and copying files,
+/// as well as file mode changes, are performed on the store.
+/// Like `git apply`, creating a file fails if it already exists,
+/// and deleting a file fails unless the patch removes all of its contents.
+///
+/// [`BinaryPatch::apply`]: crate::binary::BinaryPatch::apply
+#[derive(Debug)]
+pub struct PatchSetApplyOptions {
+    strip: usize,
+    options: ApplyOptions,
+}
+
+impl PatchSetApplyOptions {
+    /// Constructs a new `PatchSetApplyOptions` with default settings
+    ///
+    /// ## Defaults
+    /// * strip = 1
+    /// * options = ApplyOptions::default()
+    pub fn new() -> Self {
+        Self {
+            strip: 1,
+            options: ApplyOptions::default(),
+        }
+    }
+
+    /// Set the number of leading path components to strip from paths in the patch
+    ///
+    /// This is similar to the `-p` option in GNU patch.
+    /// The default of `1` strips the `a/` and `b/` prefixes used by `git diff`.
+    ///
+    /// Paths from git's `rename from`/`rename to` and `copy from`/`copy to` headers
+    /// never have a prefix, so they are used as is.
+    pub fn set_strip(&mut self, strip: usize) -> &mut Self {
+        self.strip = strip;
+        self
+    }
+
+    /// Set the options used to apply text patches
+    pub fn set_apply_options(&mut self, options: ApplyOptions) -> &mut Self {
+        self.options = options;
+        self
+    }
+
+    /// Apply every file patch in a [`PatchSet`] to `store`, in order.
+    ///
+    /// Stops at the first patch which fails to parse or apply.
+    /// Changes made to the store by previously applied patches are kept;
+    /// see [`apply_atomic`](Self::apply_atomic) for all-or-nothing semantics.
+    pub fn apply<S: FileStore>(
+        &self,
+        store: &mut S,
+        patches: PatchSet<'_, [u8]>,
+    ) -> Result<(), PatchSetApplyError<S::Error>> {
+        for patch in patches {
+            let patch = patch.map_err(|e| PatchSetApplyError::new(None, e.into()))?;
+            self.apply_file_patch(store, &patch)?;
+        }
+        Ok(())
+    }
+
+    /// Apply every file patch in a [`PatchSet`] to `store`, or none of them.
+    ///
+    /// Every file patch is first applied to an in-memory copy of the files it touches,
+    /// reading through to `store` without modifying it.
+    /// If any patch fails to parse or apply,
+    /// the errors for all failing files are returned and `store` is left untouched.
+    ///
+    /// Otherwise the final state of each touched file is written to `store`.
+    /// If the store fails part way through,
+    /// the files already written are restored to their original contents and modes.
+    /// Any errors encountered while restoring files are returned along with the
+    /// error which caused the rollback.
+    ///
+    /// When writing a file which didn't exist before,
+    /// any error reading it from `store` is taken to mean that it doesn't exist.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use diffy::patch_set::MemoryStore;
+    /// use diffy::patch_set::ParseOptions;
+    /// use diffy::patch_set::PatchSet;
+    /// use diffy::patch_set::PatchSetApplyOptions;
+    ///
+    /// let input = b"\
+    /// diff --git a/alpha.txt b/alpha.txt
+    /// --- a/alpha.txt
+    /// +++ b/alpha.txt
+    /// @@ -1 +1 @@
+    /// -alpha
+    /// +ALPHA
+    /// diff --git a/beta.txt b/beta.txt
+    /// --- a/beta.txt
+    /// +++ b/beta.txt
+    /// @@ -1 +1 @@
+    /// -beta
+    /// +BETA
+    /// ";
+    ///
+    /// let mut store = MemoryStore::new();
+    /// store.insert("alpha.txt", "alpha\n");
+    /// store.insert("beta.txt", "gamma\n");
+    /// let original = store.clone();
+    ///
+    /// let patches = PatchSet::parse_bytes(input, ParseOptions::gitdiff());
+    /// let errors = PatchSetApplyOptions::new()
+    ///     .apply_atomic(&mut store, patches)
+    ///     .unwrap_err();
+    ///
+    /// assert_eq!(errors.errors().len(), 1);
+    /// assert_eq!(errors.errors()[0].path(), Some(&b"beta.txt"[..]));
+    /// assert_eq!(store, original);
+    /// ```
+    pub fn apply_atomic<S: FileStore>(
+        &self,
+        store: &mut S,
+        patches: PatchSet<'_, [u8]>,
+    ) -> Result<(), PatchSetApplyErrors<S::Error>> {
+        let files = self.stage(store, patches)?;
+        commit(store, &files).map_err(|errors| PatchSetApplyErrors { errors })
+    }
+
+    /// Check whether every file patch in a [`PatchSet`] applies to `store`, without modifying it.
+    ///
+    /// This is the validation step of [`apply_atomic`](Self::apply_atomic):
+    /// the errors for all failing files are returned.
+    /// Files touched by a single file patch are checked without building their patched contents.
+    /// Later file patches are checked against the files as changed by earlier ones,
+    /// so the contents of files touched by several file patches are held in memory
+    /// until the check completes.
+    pub fn check<S: FileStore>(
+        &self,
+        store: &S,
+        patches: PatchSet<'_, [u8]>,
+    ) -> Result<(), PatchSetApplyErrors<S::Error>> {
+        let mut file_patches = Vec::new();
+        let mut parse_error = None;
+        for patch in patches {
+            match patch {
+                Ok(patch) => file_patches.push(patch),
+                Err(e) => {
+                    parse_error = Some(PatchSetApplyError::new(None, e.into()));
+                    break;
+                }
+            }
+        }
+
+        let operations: Vec<_> = file_patches
+            .iter()
+            .map(|patch| self.strip_prefix(patch.operation()))
+            .collect();
+        let mut touched: BTreeMap<&[u8], usize> = BTreeMap::new();
+        for path in operations.iter().flat_map(operation_paths) {
+            *touched.entry(path).or_default() += 1;
+        }
+
+        let mut errors = Vec::new();
+        let mut staged = Staged::new(store);
+        for (patch, operation) in file_patches.iter().zip(&operations) {
+            let result = if operation_paths(operation).all(|path| touched[path] == 1) {
+                self.check_file_patch(store, patch)
+            } else {
+                self.apply_file_patch(&mut staged, patch)
+                    .map_err(PatchSetApplyError::unstage)
+            };
+            errors.extend(result.err());
+        }
+        errors.extend(parse_error);
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(PatchSetApplyErrors { errors })
+        }
+    }
+
+    // Check a file patch against `store` the same way `apply_file_patch` applies it, without
+    // building the patched contents where possible
+    fn check_file_patch<S: FileStore>(
+        &self,
+        store: &S,
+        patch: &FilePatch<'_, [u8]>,
+    ) -> Result<(), PatchSetApplyError<S::Error>> {
+        let operation = self.strip_prefix(patch.operation());
+        let read = |path: &[u8]| store.read(path).map_err(Store);
+
+        let (path, result) = match &operation {
+            FileOperation::Create(path) => (
+                path,
+                check_not_exists(store, path)
+                    .and_then(|()| self.check_contents(patch.patch(), &[])),
+            ),
+            FileOperation::Delete(path) => (
+                path,
+                read(path)
+                    .and_then(|base| self.patch_contents(patch.patch(), &base))
+                    .and_then(|contents| {
+                        if contents.is_empty() {
+                            Ok(())
+                        } else {
+                            Err(PatchSetApplyErrorKind::LeavesContents)
+                        }
+                    }),
+            ),
+            FileOperation::Modify { original, modified } => (
+                modified,
+                read(original).and_then(|base| self.check_contents(patch.patch(), &base)),
+            ),
+            FileOperation::Rename { from, to } => (
+                to,
+                if patch
+                    .patch()
+                    .as_text()
+                    .is_some_and(|patch| patch.hunks().is_empty())
+                {
+                    store.mode(from).map(|_| ()).map_err(Store)
+                } else {
+                    read(from).and_then(|base| self.check_contents(patch.patch(), &base))
+                },
+            ),
+            FileOperation::Copy { from, to } => (
+                to,
+                read(from).and_then(|base| self.check_contents(patch.patch(), &base)),
+            ),
+        };
+
+        result.map_err(|kind| PatchSetApplyError::new(Some(path), kind))
+    }
+
+    // Apply every file patch to an in-memory copy of the files it touches
+    fn stage<S: FileStore>(
+        &self,
+        store: &S,
+        patches: PatchSet<'_, [u8]>,
+    ) -> Result<BTreeMap<Vec<u8>, StagedFile>, PatchSetApplyErrors<S::Error>> {
+        let mut errors = Vec::new();
+        let mut staged = Staged::new(store);
+
+        for patch in patches {
+            match patch {
+                Ok(patch) => {
+                    if let Err(e) = self.apply_file_patch(&mut staged, &patch) {
+                        errors.push(e.unstage());
+                    }
+                }
+                Err(e) => {
+                    errors.push(PatchSetApplyError::new(None, e.into()));
+                    break;
+                }
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(staged.files)
+        } else {
+            Err(PatchSetApplyErrors { errors })
+        }
+    }
+
+    /// Apply a single file patch to `store`.
+    pub fn apply_file_patch<S: FileStore>(
+        &self,
+        store: &mut S,
+        patch: &FilePatch<'_, [u8]>,
+    ) -> Result<(), PatchSetApplyError<S::Error>> {
+        let operation = self.strip_prefix(patch.operation());
+
+        let (path, result) = match &operation {
+            FileOperation::Create(path) => (
+                path,
+                check_not_exists(store, path)
+                    .and_then(|()| self.patch_contents(patch.patch(), &[]))
+                    .and_then(|contents| store.write(path, contents).map_err(Store))
+                    .and_then(|()| set_mode(store, path, patch.new_mode())),
+            ),
+            FileOperation::Delete(path) => (
+                path,
+                store
+                    .read(path)
+                    .map_err(Store)
+                    .and_then(|base| self.patch_contents(patch.patch(), &base))
+                    .and_then(|contents| {
+                        if contents.is_empty() {
+                            store.remove(path).map_err(Store)
+                        } else {
+                            Err(PatchSetApplyErrorKind::LeavesContents)
+                        }
+                    }),
+            ),
+            FileOperation::Modify { original, modified } => (
+                modified,
+                store
+                    .read(original)
+                    .map_err(Store)
+                    .and_then(|base| self.patch_contents(patch.patch(), &base))
+                    .and_then(|contents| store.write(modified, contents).map_err(Store))
+                    .and_then(|()| {
+                        if original != modified {
+                            store.remove(original).map_err(Store)
+                        } else {
+                            Ok(())
+                        }
+                    })
+                    .and_then(|()| set_mode(store, modified, patch.new_mode())),
+            ),
+            FileOperation::Rename { from, to } => (
+                to,
+                self.patch_file(store, patch.patch(), from)
+                    .and_then(|contents| {
+                        store.rename(from, to).map_err(Store)?;
+                        match contents {
+                            Some(contents) => store.write(to, contents).map_err(Store),
+                            None => Ok(()),
+                        }
+                    })
+                    .and_then(|()| set_mode(store, to, patch.new_mode())),
+            ),
+            FileOperation::Copy { from, to } => (
+                to,
+                store
+                    .read(from)
+                    .map_err(Store)
+                    .and_then(|base| self.patch_contents(patch.patch(), &base))
+                    .and_then(|contents| store.write(to, contents).map_err(Store))
+                    .and_then(|()| set_mode(store, to, patch.new_mode())),
+            ),
+        };
+
+        result.map_err(|kind| PatchSetApplyError::new(Some(path), kind))
+    }
+
+    fn strip_prefix<'a>(&self, operation: &'a FileOperation<'_, [u8]>) -> FileOperation<'a, [u8]> {
+        match operation {
+            // Rename/Copy paths come from git headers without a prefix
+            FileOperation::Rename { .. } | FileOperation::Copy { .. } => operation.strip_prefix(0),
+            _ => operation.strip_prefix(self.strip),
+        }
+    }
+
+    // Apply the patch to the contents of the file at `path`, returning `None` if there is nothing
+    // to apply (e.g. a pure rename)
+    fn patch_file<S: FileStore>(
+        &self,
+        store: &S,
+        patch: &PatchKind<'_, [u8]>,
+        path: &[u8],
+    ) -> Result<Option<Vec<u8>>, PatchSetApplyErrorKind<S::Error>> {
+        if patch
+            .as_text()
+            .is_some_and(|patch| patch.hunks().is_empty())
+        {
+            return Ok(None);
+        }
+
+        let base = store.read(path).map_err(Store)?;
+        self.patch_contents(patch, &base).map(Some)
+    }
+
+    fn patch_contents<E>(
+        &self,
+        patch: &PatchKind<'_, [u8]>,
+        base: &[u8],
+    ) -> Result<Vec<u8>, PatchSetApplyErrorKind<E>> {
+        match patch {
+            PatchKind::Text(patch) => self
+                .options
+                .apply_bytes(base, patch)
+                .map_err(PatchSetApplyErrorKind::Apply),
+            #[cfg(feature = "binary")]
+            PatchKind::Binary(patch) => patch.apply(base).map_err(PatchSetApplyErrorKind::Binary),
+            #[cfg(not(feature = "binary"))]
+            PatchKind::Binary(_) => Err(PatchSetApplyErrorKind::BinaryUnsupported),
+        }
+    }
+
+    fn check_contents<E>(
+        &self,
+        patch: &PatchKind<'_, [u8]>,
+        base: &[u8],
+    ) -> Result<(), PatchSetApplyErrorKind<E>> {
+        match patch {
+            PatchKind::Text(patch) => self
+                .options
+                .check_bytes(base, patch)
+                .map_err(PatchSetApplyErrorKind::Apply),
+            PatchKind::Binary(_) => self.patch_contents(patch, base).map(|_| ()),
+        }
+    }
+}
+
+// The paths a file operation reads or writes
+fn operation_paths<'a>(operation: &'a FileOperation<'_, [u8]>) -> impl Iterator<Item = &'a [u8]> {
+    let (first, second) = match operation {
+        FileOperation::Create(path) | FileOperation::Delete(path) => (path, None),
+        FileOperation::Modify {
+            original: from,
+            modified: to,
+        }
+        | FileOperation::Rename { from, to }
+        | FileOperation::Copy { from, to } => (from, Some(to).filter(|&to| to != from)),
+    };
+    iter::once(&**first).chain(second.map(|path| &**path))
+}
+
+impl Default for PatchSetApplyOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A file touched while staging changes
+struct StagedFile {
+    /// The file as it is in the underlying store, if it exists
+    original: Option<MemoryFile>,
+    /// The file after applying the staged changes, if it still exists
+    current: Option<MemoryFile>,
+}
+
+/// Changes staged in memory on top of a store, which is only ever read from
+struct Staged<'s, S> {
+    store: &'s S,
+    files: BTreeMap<Vec<u8>, StagedFile>,
+}
+
+impl<'s, S: FileStore> Staged<'s, S> {
+    fn new(store: &'s S) -> Self {
+        Self {
+            store,
+            files: BTreeMap::new(),
+        }
+    }
+
+    // Returns the staged state of the file at `path`, loading it from the store if it hasn't been
+    // touched yet. If `must_exist` is set, failing to read the file is an error, otherwise the
+    // file is taken to not exist.
+    fn load(
+        &mut self,
+        path: &[u8],
+        must_exist: bool,
+    ) -> Result<&mut StagedFile, StagedError<S::Error>> {
+        if !self.files.contains_key(path) {
+            let original = match self.store.read(path) {
+                Ok(contents) => Some(MemoryFile {
+                    contents,
+                    mode: self.store.mode(path).map_err(StagedError::Store)?,
+                }),
+                Err(e) if must_exist => return Err(StagedError::Store(e)),
+                Err(_) => None,
+            };
+            self.files.insert(
+                path.to_vec(),
+                StagedFile {
+                    current: original.clone(),
+                    original,
+                },
+            );
+        }
+
+        let file = self.files.get_mut(path).unwrap();
+        if must_exist && file.current.is_none() {
+            return Err(StagedError::NotFound);
+        }
+        Ok(file)
+    }
+}
+
+impl<S: FileStore> FileStore for Staged<'_, S> {
+    type Error = StagedError<S::Error>;
+
+    fn read(&self, path: &[u8]) -> Result<Vec<u8>, Self::Error> {
+        match self.files.get(path) {
+            Some(file) => file
+                .current
+                .as_ref()
+                .map(|file| file.contents.clone())
+                .ok_or(StagedError::NotFound),
+            None => self.store.read(path).map_err(StagedError::Store),
+        }
+    }
+
+    fn write(&mut self, path: &[u8], contents: Vec<u8>) -> Result<(), Self::Error> {
+        let file = self.load(path, false)?;
+        match &mut file.current {
+            Some(file) => file.contents = contents,
+            None => {
+                file.current = Some(MemoryFile {
+                    contents,
+                    mode: FileMode::Regular,
+                })
+            }
+        }
+        Ok(())
+    }
+
+    fn remove(&mut self, path: &[u8]) -> Result<(), Self::Error> {
+        self.load(path, true)?.current = None;
+        Ok(())
+    }
+
+    fn rename(&mut self, from: &[u8], to: &[u8]) -> Result<(), Self::Error> {
+        let file = self.load(from, true)?.current.take();
+        self.load(to, false)?.current = file;
+        Ok(())
+    }
+
+    fn mode(&self, path: &[u8]) -> Result<FileMode, Self::Error> {
+        match self.files.get(path) {
+            Some(file) => file
+                .current
+                .as_ref()
+                .map(|file| file.mode)
+                .ok_or(StagedError::NotFound),
+            None => self.store.mode(path).map_err(StagedError::Store),
+        }
+    }
+
+    fn set_mode(&mut self, path: &[u8], mode: FileMode) -> Result<(), Self::Error> {
+        if let Some(file) = &mut self.load(path, true)?.current {
+            file.mode = mode;
+        }
+        Ok(())
+    }
+}
+
+/// An error returned when staging changes
+enum StagedError<E> {
+    /// The underlying store failed
+    Store(E),
+    /// The file was removed by a staged change
+    NotFound,
+}
+
+// Write the staged files to `store`, restoring the files already written if the store fails
+fn commit<S: FileStore>(
+    store: &mut S,
+    files: &BTreeMap<Vec<u8>, StagedFile>,
+) -> Result<(), Vec<PatchSetApplyError<S::Error>>> {
+    let changed = files
+        .iter()
+        .filter(|(_, file)| file.current != file.original);
+
+    let mut written: Vec<(&Vec<u8>, &StagedFile)> = Vec::new();
+    for (path, file) in changed {
+        if let Err(e) = write_file(store, path, file.original.as_ref(), file.current.as_ref()) {
+            let mut errors = vec![PatchSetApplyError::new(Some(path), Store(e))];
+            // The file may have only been partially written. A new file which wasn't created
+            // doesn't need to be removed.
+            if let Err(e) = write_file(store, path, file.current.as_ref(), file.original.as_ref()) {
+                if file.original.is_some() || store.mode(path).is_ok() {
+                    errors.push(PatchSetApplyError::new(Some(path), Store(e)));
+                }
+            }
+            for (path, file) in written.into_iter().rev() {
+                if let Err(e) =
+                    write_file(store, path, file.current.as_ref(), file.original.as_ref())
+                {
+                    errors.push(PatchSetApplyError::new(Some(path), Store(e)));
+                }
+            }
+            return Err(errors);
+        }
+        written.push((path, file));
+    }
+
+    Ok(())
+}
+
+// Change the file at `path` from `from` to `to`, skipping writes which don't change anything
+fn write_file<S: FileStore>(
+    store: &mut S,
+    path: &[u8],
+    from: Option<&MemoryFile>,
+    to: Option<&MemoryFile>,
+) -> Result<(), S::Error> {
+    match (from, to) {
+        (_, None) => store.remove(path),
+        (from, Some(to)) => {
+            if from.is_none_or(|from| from.contents != to.contents) {
+                store.write(path, to.contents.clone())?;
+            }
+            if from.map_or(FileMode::Regular, |from| from.mode) != to.mode {
+                store.set_mode(path, to.mode)?;
+            }
+            Ok(())
+        }
+    }
+}
+
+// A file is taken to exist if the store reports its mode
+fn check_not_exists<S: FileStore>(
+    store: &S,
+    path: &[u8],
+) -> Result<(), PatchSetApplyErrorKind<S::Error>> {
+    match store.mode(path) {
+        Ok(_) => Err(PatchSetApplyErrorKind::AlreadyExists),
+        Err(_) => Ok(()),
+    }
+}
+
+fn set_mode<S: FileStore>(
+    store: &mut S,
+    path: &[u8],
+    mode: Option<&FileMode>,
+) -> Result<(), PatchSetApplyErrorKind<S::Error>> {
+    match mode {
+        Some(&mode) => store.set_mode(path, mode).map_err(Store),
+        None => Ok(()),
+    }
+}
+
+/// An error returned when applying file patches to a [`FileStore`] fails.
+#[derive(Debug)]
+pub struct PatchSetApplyError<E> {
+    path: Option<Vec<u8>>,
+    pub(crate) kind: PatchSetApplyErrorKind<E>,
+}
+
+impl<E> PatchSetApplyError<E> {
+    fn new(path: Option<&[u8]>, kind: PatchSetApplyErrorKind<E>) -> Self {
+        Self {
+            path: path.map(<[u8]>::to_vec),
+            kind,
+        }
+    }
+
+    /// Returns the path of the file which failed to be patched,
+    /// or `None` if the patch itself failed to parse.
+    pub fn path(&self) -> Option<&[u8]> {
+        self.path.as_deref()
+    }
+
+    /// Returns the error reported by the [`FileStore`], if the store operation failed.
+    pub fn store_error(&self) -> Option<&E> {
+        match &self.kind {
+            PatchSetApplyErrorKind::Store(e) => Some(e),
+            _ => None,
+        }
+    }
+
+    /// Returns the error reported when applying a text patch, if one of its hunks failed to apply.
+    ///
+    /// This identifies the failing hunk and whether it appears to already be applied.
+    pub fn apply_error(&self) -> Option<&ApplyError> {
+        match &self.kind {
+            PatchSetApplyErrorKind::Apply(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl<E: fmt::Display> fmt::Display for PatchSetApplyError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(path) = &self.path {
+            write!(
+                f,
+                "error applying patch to {}: {}",
+                String::from_utf8_lossy(path),
+                self.kind
+            )
+        } else {
+            write!(f, "error applying patches: {}", self.kind)
+        }
+    }
+}
+
+impl<E: fmt::Debug + fmt::Display> core::error::Error for PatchSetApplyError<E> {}
+
+impl<E> PatchSetApplyError<StagedError<E>> {
+    fn unstage(self) -> PatchSetApplyError<E> {
+        use PatchSetApplyErrorKind::*;
+
+        let kind = match self.kind {
+            Parse(e) => Parse(e),
+            Store(StagedError::Store(e)) => Store(e),
+            Store(StagedError::NotFound) => NotFound,
+            NotFound => NotFound,
+            AlreadyExists => AlreadyExists,
+            LeavesContents => LeavesContents,
+            Apply(e) => Apply(e),
+            Binary(e) => Binary(e),
+            BinaryUnsupported => BinaryUnsupported,
+        };
+        PatchSetApplyError {
+            path: self.path,
+            kind,
+        }
+    }
+}
+
+/// The errors returned when applying file patches with
+/// [`PatchSetApplyOptions::apply_atomic`] or [`PatchSetApplyOptions::check`] fails.
+#[derive(Debug)]
+pub struct PatchSetApplyErrors<E> {
+    errors: Vec<PatchSetApplyError<E>>,
+}
+
+impl<E> PatchSetApplyErrors<E> {
+    /// Returns the errors, in the order the failing file patches appear in the patch set.
+    ///
+    /// This is never empty.
+    pub fn errors(&self) -> &[PatchSetApplyError<E>] {
+        &self.errors
+    }
+
+    /// Consumes `self`, returning the errors.
+    pub fn into_errors(self) -> Vec<PatchSetApplyError<E>> {
+        self.errors
+    }
+}
+
+impl<E: fmt::Display> fmt::Display for PatchSetApplyErrors<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, e) in self.errors.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{e}")?;
+        }
+        Ok(())
+    }
+}
+
+impl<E: fmt::Debug + fmt::Display> core::error::Error for PatchSetApplyErrors<E> {}
+
+/// The kind of error that occurred when applying file patches.
+#[derive(Debug)]
+pub(crate) enum PatchSetApplyErrorKind<E> {
+    /// The patch set failed to parse.
+    Parse(PatchSetParseError),
+
+    /// The file store failed.
+    Store(E),
+
+    /// The file was removed by an earlier file patch.
+    NotFound,
+
+    /// The file to be created already exists.
+    AlreadyExists,
+
+    /// The file to be deleted still has contents after applying the patch.
+    LeavesContents,
+
+    /// A text patch failed to apply.
+    Apply(ApplyError),
+
+    /// A binary patch failed to apply.
+    #[cfg_attr(not(feature = "binary"), expect(dead_code))]
+    Binary(BinaryPatchParseError),
+
+    /// Binary patches require the `binary` feature.
+    #[cfg_attr(feature = "binary", expect(dead_code))]
+    BinaryUnsupported,
+}
+
+impl<E: fmt::Display> fmt::Display for PatchSetApplyErrorKind<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Parse(e) => write!(f, "{e}"),
+            Self::Store(e) => write!(f, "{e}"),
+            Self::NotFound => write!(f, "no such file"),
+            Self::AlreadyExists => write!(f, "file already exists"),
+            Self::LeavesContents => write!(f, "removal patch leaves file contents"),
+            Self::Apply(e) => write!(f, "{e}"),
+            Self::Binary(e) => write!(f, "{e}"),
+            Self::BinaryUnsupported => {
+                write!(f, "applying binary patches requires the `binary` feature")
+            }
+        }
+    }
+}
+
+impl<E> From<PatchSetParseError> for PatchSetApplyErrorKind<E> {
+    fn from(e: PatchSetParseError) -> Self {
+        Self::Parse(e)
+    }
+}
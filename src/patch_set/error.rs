@@ -27,6 +27,13 @@ impl PatchSetParseError {
     pub(crate) fn set_span(&mut self, span: Range<usize>) {
         self.span = Some(span);
     }
+
+    /// Moves the span by `offset` bytes, for input parsed from within a larger input.
+    pub(crate) fn offset_span(&mut self, offset: usize) {
+        if let Some(span) = &mut self.span {
+            *span = span.start + offset..span.end + offset;
+        }
+    }
 }
 
 impl fmt::Display for PatchSetParseError {
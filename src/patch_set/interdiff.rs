@@ -0,0 +1,347 @@
+//! Compare two revisions of a multi-file patch series.
+
+use alloc::borrow::Cow;
+use alloc::borrow::ToOwned;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::borrow::Borrow;
+use core::fmt;
+
+use super::FileMode;
+use super::FileOperation;
+use super::FilePatch;
+use super::PatchKind;
+use super::strip_path;
+use crate::ComposeError;
+use crate::Patch;
+use crate::utils::Text;
+
+/// A collection of options for comparing two revisions of a patch series.
+///
+/// Each revision is a sequence of [`FilePatch`]es which apply in order to the same original
+/// tree, for example all of the file patches of a `git format-patch` series. The file patches
+/// touching each file are combined, and the two revisions are compared file by file using
+/// [`Patch::interdiff`].
+///
+/// Binary patches and copied files aren't supported.
+///
+/// ```
+/// use diffy::patch_set::FileOperation;
+/// use diffy::patch_set::ParseOptions;
+/// use diffy::patch_set::PatchSet;
+/// use diffy::patch_set::PatchSetInterdiffOptions;
+///
+/// let v1 = "\
+/// diff --git a/file.txt b/file.txt
+/// --- a/file.txt
+/// +++ b/file.txt
+/// @@ -1,2 +1,2 @@
+///  a
+/// -b
+/// +B
+/// ";
+/// let v2 = "\
+/// diff --git a/file.txt b/file.txt
+/// --- a/file.txt
+/// +++ b/file.txt
+/// @@ -1,2 +1,2 @@
+///  a
+/// -b
+/// +bb
+/// diff --git a/new.txt b/new.txt
+/// new file mode 100644
+/// --- /dev/null
+/// +++ b/new.txt
+/// @@ -0,0 +1 @@
+/// +new
+/// ";
+///
+/// let parse = |s| {
+///     PatchSet::parse(s, ParseOptions::gitdiff())
+///         .collect::<Result<Vec<_>, _>>()
+///         .unwrap()
+/// };
+/// let interdiff = PatchSetInterdiffOptions::new()
+///     .interdiff(&parse(v1), &parse(v2))
+///     .unwrap();
+///
+/// assert_eq!(interdiff.len(), 2);
+/// assert!(interdiff[0].operation().is_modify());
+/// assert_eq!(
+///     interdiff[0].patch().as_text().unwrap().to_string(),
+///     "\
+/// --- b/file.txt
+/// +++ b/file.txt
+/// @@ -1,2 +1,2 @@
+///  a
+/// -B
+/// +bb
+/// "
+/// );
+/// assert_eq!(
+///     interdiff[1].operation(),
+///     &FileOperation::Create("new.txt".into())
+/// );
+/// ```
+#[derive(Debug, Clone)]
+pub struct PatchSetInterdiffOptions {
+    strip: usize,
+}
+
+impl PatchSetInterdiffOptions {
+    /// Constructs a new `PatchSetInterdiffOptions` with default settings
+    ///
+    /// ## Defaults
+    /// * strip = 1
+    pub fn new() -> Self {
+        Self { strip: 1 }
+    }
+
+    /// Set the number of leading path components to strip from paths in the patches
+    ///
+    /// Files are matched up by their paths after stripping, and the file patches returned by
+    /// [`interdiff`](Self::interdiff) use the stripped paths.
+    /// The default of `1` strips the `a/` and `b/` prefixes used by `git diff`.
+    ///
+    /// Paths from git's `rename from`/`rename to` headers
+    /// never have a prefix, so they are used as is.
+    pub fn set_strip(&mut self, strip: usize) -> &mut Self {
+        self.strip = strip;
+        self
+    }
+
+    /// Compares two revisions of a patch series which apply to the same original tree
+    ///
+    /// Returns a file patch for each file which differs between the results of applying `old`
+    /// and `new`, in the order in which the files first appear in `old` and then `new`. Applying
+    /// them to the result of `old` gives the result of `new`.
+    pub fn interdiff<'a, T: Text + ?Sized>(
+        &self,
+        old: &[FilePatch<'a, T>],
+        new: &[FilePatch<'a, T>],
+    ) -> Result<Vec<FilePatch<'a, T>>, PatchSetInterdiffError> {
+        let old = self.combine(old)?;
+        let mut new = self.combine(new)?;
+
+        let mut files = Vec::new();
+        for a in old {
+            let b = match new.iter().position(|b| b.key == a.key) {
+                Some(i) => new.remove(i),
+                None => a.untouched(),
+            };
+            files.extend(interdiff_file(a, b)?);
+        }
+        for b in new {
+            let a = b.untouched();
+            files.extend(interdiff_file(a, b)?);
+        }
+
+        Ok(files)
+    }
+
+    // Combines the file patches of one revision into a single patch per file
+    fn combine<'a, T: Text + ?Sized>(
+        &self,
+        patches: &[FilePatch<'a, T>],
+    ) -> Result<Vec<File<'a, T>>, PatchSetInterdiffError> {
+        let mut files: Vec<File<'a, T>> = Vec::new();
+
+        for patch in patches {
+            let (original, modified) = match patch.operation() {
+                FileOperation::Create(path) => (None, Some(self.strip(path))),
+                FileOperation::Delete(path) => (Some(self.strip(path)), None),
+                FileOperation::Modify { original, modified } => {
+                    (Some(self.strip(original)), Some(self.strip(modified)))
+                }
+                FileOperation::Rename { from, to } => (Some(from.clone()), Some(to.clone())),
+                FileOperation::Copy { to, .. } => {
+                    return Err(PatchSetInterdiffError::new(
+                        to.as_ref(),
+                        PatchSetInterdiffErrorKind::Copy,
+                    ));
+                }
+            };
+            let path = modified.as_ref().or(original.as_ref()).unwrap();
+            let text = match patch.patch() {
+                PatchKind::Text(text) => text,
+                PatchKind::Binary(_) => {
+                    return Err(PatchSetInterdiffError::new(
+                        path.as_ref(),
+                        PatchSetInterdiffErrorKind::Binary,
+                    ));
+                }
+            };
+
+            // Find the file the patch applies to, or start tracking a new one
+            let existing = match &original {
+                Some(original) => files
+                    .iter()
+                    .position(|file| file.path.as_ref() == Some(original)),
+                None => files
+                    .iter()
+                    .position(|file| file.path.is_none() && Some(&file.key) == modified.as_ref()),
+            };
+            let file = match existing {
+                Some(i) => &mut files[i],
+                None => {
+                    let key = original.clone().or(modified.clone()).unwrap();
+                    files.push(File {
+                        existed: original.is_some(),
+                        path: original.clone(),
+                        key,
+                        patch: Patch::new(None::<Cow<'a, T>>, None::<Cow<'a, T>>, Vec::new()),
+                        mode: patch.old_mode().copied(),
+                    });
+                    files.last_mut().unwrap()
+                }
+            };
+
+            file.patch = file.patch.compose(text).map_err(|e| {
+                PatchSetInterdiffError::new(path.as_ref(), PatchSetInterdiffErrorKind::Compose(e))
+            })?;
+            file.path = modified;
+            file.mode = patch.new_mode().copied().or(file.mode);
+        }
+
+        Ok(files)
+    }
+
+    fn strip<'a, T: Text + ?Sized>(&self, path: &Cow<'a, T>) -> Cow<'a, T> {
+        match path {
+            Cow::Borrowed(path) => Cow::Borrowed(strip_path(path, self.strip)),
+            Cow::Owned(path) => Cow::Owned(strip_path(path.borrow(), self.strip).to_owned()),
+        }
+    }
+}
+
+impl Default for PatchSetInterdiffOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A file touched by a revision of a patch series
+struct File<'a, T: ToOwned + ?Sized> {
+    /// The path of the file in the original tree, or where it was first created
+    key: Cow<'a, T>,
+    /// Whether the file exists in the original tree
+    existed: bool,
+    /// The path of the file after the patches, or `None` if it was deleted
+    path: Option<Cow<'a, T>>,
+    /// The combined changes to the file
+    patch: Patch<'a, T>,
+    mode: Option<FileMode>,
+}
+
+impl<'a, T: ToOwned + ?Sized> File<'a, T> {
+    // The same file in a revision which doesn't touch it
+    fn untouched(&self) -> Self {
+        Self {
+            key: self.key.clone(),
+            existed: self.existed,
+            path: self.existed.then(|| self.key.clone()),
+            patch: Patch::new(None::<Cow<'a, T>>, None::<Cow<'a, T>>, Vec::new()),
+            mode: None,
+        }
+    }
+}
+
+fn interdiff_file<'a, T: Text + ?Sized>(
+    a: File<'a, T>,
+    b: File<'a, T>,
+) -> Result<Option<FilePatch<'a, T>>, PatchSetInterdiffError> {
+    let patch = a.patch.interdiff(&b.patch).map_err(|e| {
+        PatchSetInterdiffError::new(a.key.as_ref(), PatchSetInterdiffErrorKind::Compose(e))
+    })?;
+
+    let (operation, old_mode, new_mode) = match (a.path, b.path) {
+        (None, None) => return Ok(None),
+        (None, Some(path)) => (FileOperation::Create(path), None, b.mode),
+        (Some(path), None) => (FileOperation::Delete(path), a.mode, None),
+        (Some(from), Some(to)) => {
+            let (old_mode, new_mode) = match (a.mode, b.mode) {
+                (Some(old), Some(new)) if old != new => (Some(old), Some(new)),
+                _ => (None, None),
+            };
+            if from != to {
+                (FileOperation::Rename { from, to }, old_mode, new_mode)
+            } else if patch.hunks().is_empty() && old_mode.is_none() {
+                return Ok(None);
+            } else {
+                let operation = FileOperation::Modify {
+                    original: from,
+                    modified: to,
+                };
+                (operation, old_mode, new_mode)
+            }
+        }
+    };
+
+    Ok(Some(FilePatch::new(operation, patch, old_mode, new_mode)))
+}
+
+/// An error returned when comparing patch series with
+/// [`PatchSetInterdiffOptions::interdiff`] fails.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PatchSetInterdiffError {
+    path: Vec<u8>,
+    kind: PatchSetInterdiffErrorKind,
+}
+
+impl PatchSetInterdiffError {
+    fn new<T: Text + ?Sized>(path: &T, kind: PatchSetInterdiffErrorKind) -> Self {
+        Self {
+            path: path.as_bytes().to_vec(),
+            kind,
+        }
+    }
+
+    /// Returns the path of the file which couldn't be compared.
+    pub fn path(&self) -> &[u8] {
+        &self.path
+    }
+
+    /// Returns the error from combining the patches to the file, if that failed.
+    pub fn compose_error(&self) -> Option<&ComposeError> {
+        match &self.kind {
+            PatchSetInterdiffErrorKind::Compose(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for PatchSetInterdiffError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "error comparing patches to {}: {}",
+            String::from_utf8_lossy(&self.path),
+            self.kind
+        )
+    }
+}
+
+impl core::error::Error for PatchSetInterdiffError {}
+
+/// The kind of error that occurred when comparing patch series.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum PatchSetInterdiffErrorKind {
+    /// The patches to a file don't apply to each other or to the same original file.
+    Compose(ComposeError),
+
+    /// Binary patches can't be compared.
+    Binary,
+
+    /// Copied files can't be compared.
+    Copy,
+}
+
+impl fmt::Display for PatchSetInterdiffErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Compose(e) => write!(f, "{e}"),
+            Self::Binary => write!(f, "binary patches are not supported"),
+            Self::Copy => write!(f, "copied files are not supported"),
+        }
+    }
+}
@@ -3,8 +3,11 @@
 //! This module provides [`PatchSet`] for parsing patches that contain changes
 //! to multiple files, like the output of `git diff` or `git format-patch`.
 
+mod apply;
 pub(crate) mod error;
+mod interdiff;
 mod parse;
+mod range_diff;
 #[cfg(test)]
 mod tests;
 
@@ -16,9 +19,24 @@ use crate::Patch;
 use crate::binary::BinaryPatch;
 use crate::utils::Text;
 
+pub use apply::FileStore;
+#[cfg(feature = "std")]
+pub use apply::FsStore;
+pub use apply::MemoryFile;
+pub use apply::MemoryStore;
+pub use apply::MemoryStoreError;
+pub use apply::PatchSetApplyError;
+pub use apply::PatchSetApplyErrors;
+pub use apply::PatchSetApplyOptions;
 pub use error::PatchSetParseError;
 use error::PatchSetParseErrorKind;
+pub use interdiff::PatchSetInterdiffError;
+pub use interdiff::PatchSetInterdiffOptions;
 pub use parse::PatchSet;
+pub use range_diff::Commit;
+pub use range_diff::RangeDiff;
+pub use range_diff::RangeDiffEntry;
+pub use range_diff::RangeDiffOptions;
 
 /// Options for parsing patch content.
 ///
@@ -57,6 +75,7 @@ pub use parse::PatchSet;
 #[derive(Debug, Clone)]
 pub struct ParseOptions {
     pub(crate) format: Format,
+    pub(crate) recount: bool,
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -83,6 +102,7 @@ impl ParseOptions {
     pub fn unidiff() -> Self {
         Self {
             format: Format::UniDiff,
+            recount: false,
         }
     }
 
@@ -99,8 +119,44 @@ impl ParseOptions {
     pub fn gitdiff() -> Self {
         Self {
             format: Format::GitDiff,
+            recount: false,
         }
     }
+
+    /// Ignore the line counts in hunk headers, deriving them from the hunk bodies instead
+    ///
+    /// This is the equivalent of `git apply --recount`.
+    /// See [`Patch::from_str_recount`] for details.
+    ///
+    /// ```
+    /// use diffy::patch_set::ParseOptions;
+    /// use diffy::patch_set::PatchSet;
+    ///
+    /// let s = "\
+    /// --- a/alpha
+    /// +++ b/alpha
+    /// @@ -1 +1 @@
+    /// -alpha
+    /// +ALPHA
+    /// +more
+    /// --- a/beta
+    /// +++ b/beta
+    /// @@ -1 +1 @@
+    /// -beta
+    /// +BETA
+    /// ";
+    ///
+    /// let mut opts = ParseOptions::unidiff();
+    /// opts.set_recount(true);
+    /// let patches: Vec<_> = PatchSet::parse(s, opts).collect::<Result<_, _>>().unwrap();
+    /// assert_eq!(patches.len(), 2);
+    /// let hunk = &patches[0].patch().as_text().unwrap().hunks()[0];
+    /// assert_eq!(hunk.new_range().len(), 2);
+    /// ```
+    pub fn set_recount(&mut self, recount: bool) -> &mut Self {
+        self.recount = recount;
+        self
+    }
 }
 
 /// File mode extracted from git extended headers.
@@ -268,6 +324,18 @@ impl<'a, T: ToOwned + ?Sized> FilePatch<'a, T> {
     }
 }
 
+// Strips the first `n` components from `path`
+fn strip_path<T: Text + ?Sized>(path: &T, n: usize) -> &T {
+    let mut remaining = path;
+    for _ in 0..n {
+        match remaining.split_at_exclusive("/") {
+            Some((_first, rest)) => remaining = rest,
+            None => return remaining,
+        }
+    }
+    remaining
+}
+
 /// The operation to perform based on a patch.
 ///
 /// This is determined by examining the `---` and `+++` header lines
@@ -365,31 +433,24 @@ impl<T: Text + ?Sized> FileOperation<'_, T> {
     /// This is similar to the `-p` option in GNU patch. For example,
     /// `strip_prefix(1)` on a path `a/src/lib.rs` would return `src/lib.rs`.
     pub fn strip_prefix(&self, n: usize) -> FileOperation<'_, T> {
-        fn strip<T: Text + ?Sized>(path: &T, n: usize) -> &T {
-            let mut remaining = path;
-            for _ in 0..n {
-                match remaining.split_at_exclusive("/") {
-                    Some((_first, rest)) => remaining = rest,
-                    None => return remaining,
-                }
-            }
-            remaining
-        }
-
         match self {
-            FileOperation::Delete(path) => FileOperation::Delete(Cow::Borrowed(strip(path, n))),
-            FileOperation::Create(path) => FileOperation::Create(Cow::Borrowed(strip(path, n))),
+            FileOperation::Delete(path) => {
+                FileOperation::Delete(Cow::Borrowed(strip_path(path, n)))
+            }
+            FileOperation::Create(path) => {
+                FileOperation::Create(Cow::Borrowed(strip_path(path, n)))
+            }
             FileOperation::Modify { original, modified } => FileOperation::Modify {
-                original: Cow::Borrowed(strip(original, n)),
-                modified: Cow::Borrowed(strip(modified, n)),
+                original: Cow::Borrowed(strip_path(original, n)),
+                modified: Cow::Borrowed(strip_path(modified, n)),
             },
             FileOperation::Rename { from, to } => FileOperation::Rename {
-                from: Cow::Borrowed(strip(from, n)),
-                to: Cow::Borrowed(strip(to, n)),
+                from: Cow::Borrowed(strip_path(from, n)),
+                to: Cow::Borrowed(strip_path(to, n)),
             },
             FileOperation::Copy { from, to } => FileOperation::Copy {
-                from: Cow::Borrowed(strip(from, n)),
-                to: Cow::Borrowed(strip(to, n)),
+                from: Cow::Borrowed(strip_path(from, n)),
+                to: Cow::Borrowed(strip_path(to, n)),
             },
         }
     }
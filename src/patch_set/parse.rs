@@ -151,7 +151,7 @@ fn next_unidiff_patch<'a, T: Text + ?Sized>(
 
     let (_, patch_input) = remaining.split_at(patch_start);
 
-    let opts = crate::patch::parse::ParseOpts::default();
+    let opts = crate::patch::parse::ParseOpts::default().recount(ps.opts.recount);
     let (result, consumed) = parse_one(patch_input, opts);
     // Always advance so the iterator makes progress even on error.
     let abs_patch_start = ps.offset + patch_start;
@@ -207,7 +207,7 @@ fn find_patch_start<T: Text + ?Sized>(input: &T) -> Option<usize> {
 /// > The log message and the patch are separated by a line with a three-dash line.
 ///
 /// [`git format-patch`]: https://git-scm.com/docs/git-format-patch
-fn strip_email_preamble<T: Text + ?Sized>(input: &T) -> &T {
+pub(super) fn strip_email_preamble<T: Text + ?Sized>(input: &T) -> &T {
     // only strip preamble for mbox-formatted input
     if !input.starts_with("From ") {
         return input;
@@ -295,7 +295,9 @@ fn next_gitdiff_patch<'a, T: Text + ?Sized>(
     // If we did, it would fail the pure rename/mode-change operation
     // since those ops have no unidiff patch portion
     // and is directly followed by the next `diff --git` header.
-    let opts = crate::patch::parse::ParseOpts::default().no_skip_preamble();
+    let opts = crate::patch::parse::ParseOpts::default()
+        .no_skip_preamble()
+        .recount(ps.opts.recount);
     let (result, consumed) = parse_one(remaining(ps), opts);
     ps.offset += consumed;
     let patch = match result {
@@ -0,0 +1,606 @@
+//! Compare two revisions of a patch series, like `git range-diff`.
+
+use alloc::string::String;
+use alloc::string::ToString;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+use core::fmt::Write;
+
+use super::FileOperation;
+use super::FilePatch;
+use super::ParseOptions;
+use super::PatchKind;
+use super::PatchSet;
+use super::PatchSetParseError;
+use super::error::PatchSetParseErrorKind;
+use super::parse::strip_email_preamble;
+use super::strip_path;
+use crate::Line;
+use crate::create_patch;
+
+/// A commit of a patch series, parsed from a `git format-patch` email.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Commit<'a> {
+    id: Option<&'a str>,
+    subject: String,
+    message: &'a str,
+    patches: Vec<FilePatch<'a, str>>,
+}
+
+impl<'a> Commit<'a> {
+    /// Parses the commits of a patch series from an mbox file, like the output of
+    /// `git format-patch --stdout`.
+    ///
+    /// Each email starts with a `From ` line followed by its headers. Anything before the first
+    /// email is ignored. An email without a diff, e.g. the cover letter of a series, is parsed as
+    /// a commit without file patches.
+    ///
+    /// ```
+    /// use diffy::patch_set::Commit;
+    ///
+    /// let mbox = "\
+    /// From 1234567890abcdef1234567890abcdef12345678 Mon Sep 17 00:00:00 2001
+    /// From: A U Thor <author@example.com>
+    /// Subject: [PATCH 1/1] Fix the thing
+    ///
+    /// It was broken.
+    /// ---
+    /// diff --git a/file.txt b/file.txt
+    /// --- a/file.txt
+    /// +++ b/file.txt
+    /// @@ -1 +1 @@
+    /// -broken
+    /// +fixed
+    /// --
+    /// 2.40.0
+    /// ";
+    ///
+    /// let commits = Commit::parse_mbox(mbox).unwrap();
+    /// assert_eq!(commits.len(), 1);
+    /// assert_eq!(
+    ///     commits[0].id(),
+    ///     Some("1234567890abcdef1234567890abcdef12345678")
+    /// );
+    /// assert_eq!(commits[0].subject(), "Fix the thing");
+    /// assert_eq!(commits[0].message(), "It was broken.");
+    /// assert_eq!(commits[0].patches().len(), 1);
+    /// ```
+    pub fn parse_mbox(input: &'a str) -> Result<Vec<Commit<'a>>, PatchSetParseError> {
+        let starts: Vec<usize> = line_offsets(input)
+            .filter(|&offset| is_email_start(&input[offset..]))
+            .collect();
+
+        let mut commits = Vec::with_capacity(starts.len());
+        for (i, &start) in starts.iter().enumerate() {
+            let end = starts.get(i + 1).copied().unwrap_or(input.len());
+            let commit = Self::parse_email(&input[start..end]).map_err(|mut e| {
+                e.offset_span(start);
+                e
+            })?;
+            commits.push(commit);
+        }
+
+        Ok(commits)
+    }
+
+    fn parse_email(email: &'a str) -> Result<Self, PatchSetParseError> {
+        let (from_line, rest) = email.split_once('\n').unwrap_or((email, ""));
+        let id = from_line["From ".len()..]
+            .split(' ')
+            .next()
+            .filter(|id| !id.is_empty());
+
+        // Headers end at the first empty line, and may continue on indented lines
+        let (headers, body) = match rest.find("\n\n") {
+            Some(pos) => (&rest[..pos], &rest[pos + 2..]),
+            None => (rest, ""),
+        };
+        let mut subject: Option<String> = None;
+        let mut in_subject = false;
+        for line in headers.lines() {
+            if line.starts_with([' ', '\t']) {
+                if in_subject {
+                    if let Some(subject) = &mut subject {
+                        subject.push(' ');
+                        subject.push_str(line.trim());
+                    }
+                }
+            } else if let Some(value) = line.strip_prefix("Subject:") {
+                subject = Some(value.trim().into());
+                in_subject = true;
+            } else {
+                in_subject = false;
+            }
+        }
+        let subject = subject.map_or_else(String::new, |subject| strip_subject_prefix(&subject));
+
+        let message = match body.find("\n---\n") {
+            Some(pos) => &body[..pos],
+            None if body.starts_with("---\n") => "",
+            None => body,
+        };
+
+        let patches = match PatchSet::parse(email, ParseOptions::gitdiff()).collect() {
+            Ok(patches) => patches,
+            Err(e) if e.kind == PatchSetParseErrorKind::NoPatchesFound => Vec::new(),
+            Err(mut e) => {
+                // Spans are relative to the patch after the preamble
+                e.offset_span(email.len() - strip_email_preamble(email).len());
+                return Err(e);
+            }
+        };
+
+        Ok(Self {
+            id,
+            subject,
+            message: message.trim_end(),
+            patches,
+        })
+    }
+
+    /// Returns the commit id from the `From ` line of the email, if any
+    pub fn id(&self) -> Option<&'a str> {
+        self.id
+    }
+
+    /// Returns the subject of the email, without its `[PATCH]` prefix
+    pub fn subject(&self) -> &str {
+        &self.subject
+    }
+
+    /// Returns the body of the commit message
+    pub fn message(&self) -> &'a str {
+        self.message
+    }
+
+    /// Returns the file patches of the commit
+    pub fn patches(&self) -> &[FilePatch<'a, str>] {
+        &self.patches
+    }
+
+    // Renders the commit message and patches, without line numbers, for comparing commits
+    fn render(&self) -> String {
+        let mut text = String::from("## Commit message ##\n");
+        for line in self.message.lines() {
+            text.push_str("    ");
+            text.push_str(line);
+            text.push('\n');
+        }
+
+        for patch in &self.patches {
+            text.push_str("\n## ");
+            match patch.operation() {
+                FileOperation::Create(path) => {
+                    let _ = write!(text, "{} (new)", strip_path(path.as_ref(), 1));
+                }
+                FileOperation::Delete(path) => {
+                    let _ = write!(text, "{} (deleted)", strip_path(path.as_ref(), 1));
+                }
+                FileOperation::Modify { original, modified } => {
+                    let original = strip_path(original.as_ref(), 1);
+                    let modified = strip_path(modified.as_ref(), 1);
+                    if original == modified {
+                        text.push_str(modified);
+                    } else {
+                        let _ = write!(text, "{original} => {modified}");
+                    }
+                }
+                FileOperation::Rename { from, to } => {
+                    let _ = write!(text, "{from} => {to}");
+                }
+                FileOperation::Copy { from, to } => {
+                    let _ = write!(text, "{from} => {to} (copy)");
+                }
+            }
+            text.push_str(" ##\n");
+
+            match patch.patch() {
+                PatchKind::Text(patch) => {
+                    for hunk in patch.hunks() {
+                        text.push_str("@@");
+                        if let Some(context) = hunk.function_context() {
+                            text.push(' ');
+                            text.push_str(context.trim_end());
+                        }
+                        text.push('\n');
+
+                        for line in hunk.lines() {
+                            let (sign, line) = match line {
+                                Line::Context(line) => (' ', line),
+                                Line::Delete(line) => ('-', line),
+                                Line::Insert(line) => ('+', line),
+                            };
+                            text.push(sign);
+                            text.push_str(line);
+                            if !line.ends_with('\n') {
+                                text.push('\n');
+                            }
+                        }
+                    }
+                }
+                PatchKind::Binary(_) => text.push_str("Binary files differ\n"),
+            }
+        }
+
+        text
+    }
+}
+
+// The offsets of the lines in `input`
+fn line_offsets(input: &str) -> impl Iterator<Item = usize> + '_ {
+    core::iter::once(0).chain(input.match_indices('\n').map(|(i, _)| i + 1))
+}
+
+// Whether `input` starts with the `From ` line of an email, followed by a header
+fn is_email_start(input: &str) -> bool {
+    let Some(rest) = input.strip_prefix("From ") else {
+        return false;
+    };
+    let Some((_, next)) = rest.split_once('\n') else {
+        return false;
+    };
+    let header = next.lines().next().unwrap_or("");
+    match header.split_once(':') {
+        Some((name, _)) => {
+            !name.is_empty() && name.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-')
+        }
+        None => false,
+    }
+}
+
+// Strips `[PATCH ...]` and similar prefixes from the subject of an email
+fn strip_subject_prefix(mut subject: &str) -> String {
+    while let Some(rest) = subject.strip_prefix('[') {
+        match rest.split_once(']') {
+            Some((_, rest)) => subject = rest.trim_start(),
+            None => break,
+        }
+    }
+    subject.into()
+}
+
+/// A collection of options for comparing two revisions of a patch series.
+///
+/// Corresponding commits of the two series are matched up by how similar their commit
+/// messages and patches are, and the differences between each pair are shown as a diff of
+/// their patches, like `git range-diff`.
+///
+/// ```
+/// use diffy::patch_set::Commit;
+/// use diffy::patch_set::RangeDiffOptions;
+///
+/// let old = "\
+/// From 1111111111111111111111111111111111111111 Mon Sep 17 00:00:00 2001
+/// Subject: [PATCH] Fix the thing
+///
+/// ---
+/// diff --git a/file.txt b/file.txt
+/// --- a/file.txt
+/// +++ b/file.txt
+/// @@ -1 +1 @@
+/// -broken
+/// +fixed
+/// ";
+/// let new = "\
+/// From 2222222222222222222222222222222222222222 Mon Sep 17 00:00:00 2001
+/// Subject: [PATCH v2] Fix the thing
+///
+/// ---
+/// diff --git a/file.txt b/file.txt
+/// --- a/file.txt
+/// +++ b/file.txt
+/// @@ -1 +1 @@
+/// -broken
+/// +really fixed
+/// ";
+///
+/// let old = Commit::parse_mbox(old).unwrap();
+/// let new = Commit::parse_mbox(new).unwrap();
+/// let range_diff = RangeDiffOptions::new().range_diff(&old, &new);
+///
+/// assert_eq!(
+///     range_diff.to_string(),
+///     "\
+/// 1:  1111111 ! 1:  2222222 Fix the thing
+///     @@ -3,4 +3,4 @@
+///      ### file.txt ##
+///      @@
+///      -broken
+///     -+fixed
+///     ++really fixed
+/// "
+/// );
+/// ```
+#[derive(Debug, Clone)]
+pub struct RangeDiffOptions {
+    creation_factor: usize,
+}
+
+impl RangeDiffOptions {
+    /// Constructs a new `RangeDiffOptions` with default settings
+    ///
+    /// ## Defaults
+    /// * creation_factor = 60
+    pub fn new() -> Self {
+        Self {
+            creation_factor: 60,
+        }
+    }
+
+    /// Set the creation factor, as a percentage
+    ///
+    /// This is the cost of treating a commit as removed from the old series or added to the new
+    /// one, relative to the size of its patch. Larger values allow less similar commits to be
+    /// matched up. This is the `--creation-factor` option of `git range-diff`.
+    pub fn set_creation_factor(&mut self, creation_factor: usize) -> &mut Self {
+        self.creation_factor = creation_factor;
+        self
+    }
+
+    /// Compares two revisions of a patch series
+    pub fn range_diff<'a>(&self, old: &'a [Commit<'a>], new: &'a [Commit<'a>]) -> RangeDiff<'a> {
+        let old_texts: Vec<String> = old.iter().map(Commit::render).collect();
+        let new_texts: Vec<String> = new.iter().map(Commit::render).collect();
+
+        // Find the cheapest assignment of old commits to new commits, where an old commit
+        // assigned to one of the last `old.len()` columns is removed, and a new commit assigned
+        // to one of the last `new.len()` rows is added
+        let (n, m) = (old.len(), new.len());
+        let creation_cost = |text: &str| (text.lines().count() * self.creation_factor / 100) as i64;
+        let mut costs = vec![vec![0; n + m]; n + m];
+        for (i, old) in old_texts.iter().enumerate() {
+            for (j, new) in new_texts.iter().enumerate() {
+                costs[i][j] = diff_size(old, new);
+            }
+            for cost in &mut costs[i][m..] {
+                *cost = creation_cost(old);
+            }
+        }
+        for (j, new) in new_texts.iter().enumerate() {
+            for row in &mut costs[n..] {
+                row[j] = creation_cost(new);
+            }
+        }
+        let assignment = assign(&costs);
+
+        let old_match: Vec<Option<usize>> = assignment[..n]
+            .iter()
+            .map(|&j| (j < m).then_some(j))
+            .collect();
+        let mut new_match = vec![None; m];
+        for (i, j) in old_match.iter().enumerate() {
+            if let Some(j) = *j {
+                new_match[j] = Some(i);
+            }
+        }
+
+        // List the pairs in the order of the new series, with removed commits listed where they
+        // were in the old series
+        let mut entries = Vec::new();
+        let mut shown = vec![false; n];
+        let (mut i, mut j) = (0, 0);
+        while i < n || j < m {
+            if i < n && old_match[i].is_none() {
+                entries.push(RangeDiffEntry {
+                    old: Some(i),
+                    new: None,
+                    diff: None,
+                });
+                i += 1;
+                continue;
+            }
+
+            while j < m && new_match[j].is_none() {
+                entries.push(RangeDiffEntry {
+                    old: None,
+                    new: Some(j),
+                    diff: None,
+                });
+                j += 1;
+            }
+            if j < m {
+                let k = new_match[j].unwrap();
+                let diff = (old_texts[k] != new_texts[j])
+                    .then(|| render_hunks(&old_texts[k], &new_texts[j]));
+                entries.push(RangeDiffEntry {
+                    old: Some(k),
+                    new: Some(j),
+                    diff,
+                });
+                shown[k] = true;
+                j += 1;
+            }
+            while i < n && shown[i] {
+                i += 1;
+            }
+        }
+
+        RangeDiff { old, new, entries }
+    }
+}
+
+impl Default for RangeDiffOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// The number of lines added and removed to turn `old` into `new`
+fn diff_size(old: &str, new: &str) -> i64 {
+    let patch = create_patch(old, new);
+    patch
+        .hunks()
+        .iter()
+        .flat_map(|hunk| hunk.lines())
+        .filter(|line| !matches!(line, Line::Context(_)))
+        .count() as i64
+}
+
+// Renders the hunks of the diff between `old` and `new`, without the filename headers
+fn render_hunks(old: &str, new: &str) -> String {
+    let patch = create_patch(old, new);
+    let mut text = String::new();
+    for hunk in patch.hunks() {
+        let _ = writeln!(text, "@@ -{} +{} @@", hunk.old_range(), hunk.new_range());
+        for line in hunk.lines() {
+            let (sign, line) = match line {
+                Line::Context(line) => (' ', line),
+                Line::Delete(line) => ('-', line),
+                Line::Insert(line) => ('+', line),
+            };
+            text.push(sign);
+            text.push_str(line);
+        }
+    }
+    text
+}
+
+// Solves the assignment problem for the square matrix `costs` with the Hungarian algorithm,
+// returning the column assigned to each row.
+fn assign(costs: &[Vec<i64>]) -> Vec<usize> {
+    let n = costs.len();
+    // Row and column potentials, and the row assigned to each column, with 1-based indices
+    let mut u = vec![0i64; n + 1];
+    let mut v = vec![0i64; n + 1];
+    let mut rows = vec![0usize; n + 1];
+    let mut way = vec![0usize; n + 1];
+
+    for i in 1..=n {
+        rows[0] = i;
+        let mut j0 = 0;
+        let mut min = vec![i64::MAX; n + 1];
+        let mut used = vec![false; n + 1];
+        loop {
+            used[j0] = true;
+            let i0 = rows[j0];
+            let mut delta = i64::MAX;
+            let mut j1 = 0;
+            for j in 1..=n {
+                if used[j] {
+                    continue;
+                }
+                let cost = costs[i0 - 1][j - 1] - u[i0] - v[j];
+                if cost < min[j] {
+                    min[j] = cost;
+                    way[j] = j0;
+                }
+                if min[j] < delta {
+                    delta = min[j];
+                    j1 = j;
+                }
+            }
+            for j in 0..=n {
+                if used[j] {
+                    u[rows[j]] += delta;
+                    v[j] -= delta;
+                } else {
+                    min[j] -= delta;
+                }
+            }
+            j0 = j1;
+            if rows[j0] == 0 {
+                break;
+            }
+        }
+        while j0 != 0 {
+            let j1 = way[j0];
+            rows[j0] = rows[j1];
+            j0 = j1;
+        }
+    }
+
+    let mut assignment = vec![0; n];
+    for j in 1..=n {
+        assignment[rows[j] - 1] = j - 1;
+    }
+    assignment
+}
+
+/// The result of comparing two revisions of a patch series with
+/// [`RangeDiffOptions::range_diff`].
+///
+/// Its [`Display`](fmt::Display) implementation renders it like `git range-diff`.
+#[derive(Debug, Clone)]
+pub struct RangeDiff<'a> {
+    old: &'a [Commit<'a>],
+    new: &'a [Commit<'a>],
+    entries: Vec<RangeDiffEntry>,
+}
+
+impl RangeDiff<'_> {
+    /// Returns the matched, removed and added commits, in the order of the new series
+    pub fn entries(&self) -> &[RangeDiffEntry] {
+        &self.entries
+    }
+}
+
+impl fmt::Display for RangeDiff<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let width = self.old.len().max(self.new.len()).to_string().len();
+        let column = |f: &mut fmt::Formatter<'_>, commits: &[Commit<'_>], index: Option<usize>| {
+            match index {
+                Some(i) => {
+                    let id = commits[i]
+                        .id
+                        .map_or("-------", |id| id.get(..7).unwrap_or(id));
+                    write!(f, "{:>width$}:  {id}", i + 1)
+                }
+                None => write!(f, "{:>width$}:  -------", "-"),
+            }
+        };
+
+        for entry in &self.entries {
+            let status = match (entry.old, entry.new) {
+                (Some(_), None) => '<',
+                (None, Some(_)) => '>',
+                _ if entry.diff.is_some() => '!',
+                _ => '=',
+            };
+            let subject = match entry.new {
+                Some(j) => &self.new[j].subject,
+                None => &self.old[entry.old.unwrap()].subject,
+            };
+
+            column(f, self.old, entry.old)?;
+            write!(f, " {status} ")?;
+            column(f, self.new, entry.new)?;
+            writeln!(f, " {subject}")?;
+
+            if let Some(diff) = &entry.diff {
+                for line in diff.lines() {
+                    writeln!(f, "    {line}")?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// A commit of the old series matched to a commit of the new series,
+/// or a commit which is only in one of them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RangeDiffEntry {
+    old: Option<usize>,
+    new: Option<usize>,
+    diff: Option<String>,
+}
+
+impl RangeDiffEntry {
+    /// Returns the index of the commit in the old series, or `None` if it was added
+    pub fn old_index(&self) -> Option<usize> {
+        self.old
+    }
+
+    /// Returns the index of the commit in the new series, or `None` if it was removed
+    pub fn new_index(&self) -> Option<usize> {
+        self.new
+    }
+
+    /// Returns the hunks of the diff between the commit messages and patches of the matched
+    /// commits, or `None` if they are the same or the commit is only in one of the series
+    pub fn diff(&self) -> Option<&str> {
+        self.diff.as_deref()
+    }
+}
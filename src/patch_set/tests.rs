@@ -896,3 +896,969 @@ mod patchset_unidiff_bytes {
         );
     }
 }
+
+mod apply {
+    use super::super::FileMode;
+    use super::super::FileStore;
+    use super::super::MemoryStore;
+    use super::super::MemoryStoreError;
+    use super::super::PatchSetApplyOptions;
+    use super::*;
+
+    fn apply(store: &mut MemoryStore, input: &str) {
+        PatchSetApplyOptions::new()
+            .apply(
+                store,
+                PatchSet::parse_bytes(input.as_bytes(), ParseOptions::gitdiff()),
+            )
+            .unwrap();
+    }
+
+    #[test]
+    fn create_modify_delete() {
+        let mut store = MemoryStore::new();
+        store.insert("modified.txt", "one\ntwo\n");
+        store.insert("deleted.txt", "gone\n");
+
+        apply(
+            &mut store,
+            "\
+diff --git a/created.txt b/created.txt
+new file mode 100755
+--- /dev/null
++++ b/created.txt
+@@ -0,0 +1 @@
++new
+diff --git a/modified.txt b/modified.txt
+--- a/modified.txt
++++ b/modified.txt
+@@ -1,2 +1,2 @@
+ one
+-two
++TWO
+diff --git a/deleted.txt b/deleted.txt
+deleted file mode 100644
+--- a/deleted.txt
++++ /dev/null
+@@ -1 +0,0 @@
+-gone
+",
+        );
+
+        let created = store.get("created.txt").unwrap();
+        assert_eq!(created.contents(), b"new\n");
+        assert_eq!(created.mode(), FileMode::Executable);
+        assert_eq!(store.get("modified.txt").unwrap().contents(), b"one\nTWO\n");
+        assert!(store.get("deleted.txt").is_none());
+        assert_eq!(store.len(), 2);
+    }
+
+    #[test]
+    fn rename_with_changes_and_mode() {
+        let mut store = MemoryStore::new();
+        store.insert("old/name.txt", "one\ntwo\n");
+
+        apply(
+            &mut store,
+            "\
+diff --git a/old/name.txt b/new/name.txt
+old mode 100644
+new mode 100755
+similarity index 50%
+rename from old/name.txt
+rename to new/name.txt
+--- a/old/name.txt
++++ b/new/name.txt
+@@ -1,2 +1,2 @@
+ one
+-two
++TWO
+",
+        );
+
+        assert!(store.get("old/name.txt").is_none());
+        let renamed = store.get("new/name.txt").unwrap();
+        assert_eq!(renamed.contents(), b"one\nTWO\n");
+        assert_eq!(renamed.mode(), FileMode::Executable);
+    }
+
+    #[test]
+    fn pure_rename_and_copy() {
+        let mut store = MemoryStore::new();
+        store.insert("a.txt", "a\n");
+        store.insert("b.txt", "b\n");
+
+        apply(
+            &mut store,
+            "\
+diff --git a/a.txt b/c.txt
+similarity index 100%
+rename from a.txt
+rename to c.txt
+diff --git a/b.txt b/d.txt
+similarity index 100%
+copy from b.txt
+copy to d.txt
+",
+        );
+
+        let paths: Vec<_> = store.iter().map(|(path, _)| path).collect();
+        assert_eq!(paths, [&b"b.txt"[..], b"c.txt", b"d.txt"]);
+        assert_eq!(store.get("c.txt").unwrap().contents(), b"a\n");
+        assert_eq!(store.get("d.txt").unwrap().contents(), b"b\n");
+    }
+
+    #[test]
+    fn stops_at_first_failure() {
+        let mut store = MemoryStore::new();
+        store.insert("first.txt", "one\n");
+        store.insert("second.txt", "something else\n");
+
+        let input = "\
+diff --git a/first.txt b/first.txt
+--- a/first.txt
++++ b/first.txt
+@@ -1 +1 @@
+-one
++ONE
+diff --git a/second.txt b/second.txt
+--- a/second.txt
++++ b/second.txt
+@@ -1 +1 @@
+-two
++TWO
+diff --git a/third.txt b/third.txt
+new file mode 100644
+--- /dev/null
++++ b/third.txt
+@@ -0,0 +1 @@
++three
+";
+        let err = PatchSetApplyOptions::new()
+            .apply(
+                &mut store,
+                PatchSet::parse_bytes(input.as_bytes(), ParseOptions::gitdiff()),
+            )
+            .unwrap_err();
+
+        assert_eq!(err.path(), Some(&b"second.txt"[..]));
+        assert!(err.store_error().is_none());
+        assert_eq!(err.apply_error().unwrap().hunk(), 1);
+        assert_eq!(
+            err.to_string(),
+            "error applying patch to second.txt: error applying hunk #1"
+        );
+        assert_eq!(store.get("first.txt").unwrap().contents(), b"ONE\n");
+        assert!(store.get("third.txt").is_none());
+    }
+
+    #[test]
+    fn missing_file_is_a_store_error() {
+        let mut store = MemoryStore::new();
+
+        let input = "\
+diff --git a/missing.txt b/missing.txt
+deleted file mode 100644
+--- a/missing.txt
++++ /dev/null
+@@ -1 +0,0 @@
+-gone
+";
+        let err = PatchSetApplyOptions::new()
+            .apply(
+                &mut store,
+                PatchSet::parse_bytes(input.as_bytes(), ParseOptions::gitdiff()),
+            )
+            .unwrap_err();
+
+        assert_eq!(err.store_error().unwrap().path(), b"missing.txt");
+        assert_eq!(
+            err.to_string(),
+            "error applying patch to missing.txt: no such file: missing.txt"
+        );
+        assert!(store.read(b"missing.txt").is_err());
+    }
+
+    #[test]
+    fn create_fails_if_file_exists() {
+        let mut store = MemoryStore::new();
+        store.insert("existing.txt", "keep\n");
+
+        let input = "\
+diff --git a/existing.txt b/existing.txt
+new file mode 100644
+--- /dev/null
++++ b/existing.txt
+@@ -0,0 +1 @@
++new
+";
+        let err = PatchSetApplyOptions::new()
+            .apply(
+                &mut store,
+                PatchSet::parse_bytes(input.as_bytes(), ParseOptions::gitdiff()),
+            )
+            .unwrap_err();
+
+        assert_eq!(
+            err.to_string(),
+            "error applying patch to existing.txt: file already exists"
+        );
+        assert_eq!(store.get("existing.txt").unwrap().contents(), b"keep\n");
+    }
+
+    #[test]
+    fn delete_fails_if_contents_differ() {
+        let mut store = MemoryStore::new();
+        store.insert("changed.txt", "changed\n");
+        store.insert("extended.txt", "gone\nextra\n");
+
+        let delete = |path: &str| {
+            alloc::format!(
+                "\
+diff --git a/{path} b/{path}
+deleted file mode 100644
+--- a/{path}
++++ /dev/null
+@@ -1 +0,0 @@
+-gone
+"
+            )
+        };
+        let apply = |store: &mut MemoryStore, input: &str| {
+            PatchSetApplyOptions::new()
+                .apply(
+                    store,
+                    PatchSet::parse_bytes(input.as_bytes(), ParseOptions::gitdiff()),
+                )
+                .unwrap_err()
+        };
+
+        let err = apply(&mut store, &delete("changed.txt"));
+        assert_eq!(
+            err.to_string(),
+            "error applying patch to changed.txt: error applying hunk #1"
+        );
+
+        let err = apply(&mut store, &delete("extended.txt"));
+        assert_eq!(
+            err.to_string(),
+            "error applying patch to extended.txt: removal patch leaves file contents"
+        );
+        assert_eq!(store.len(), 2);
+    }
+
+    #[test]
+    fn strip() {
+        let mut store = MemoryStore::new();
+        store.insert("file.txt", "one\n");
+
+        let input = "\
+--- file.txt
++++ file.txt
+@@ -1 +1 @@
+-one
++ONE
+";
+        PatchSetApplyOptions::new()
+            .set_strip(0)
+            .apply(
+                &mut store,
+                PatchSet::parse_bytes(input.as_bytes(), ParseOptions::unidiff()),
+            )
+            .unwrap();
+
+        assert_eq!(store.get("file.txt").unwrap().contents(), b"ONE\n");
+    }
+
+    #[test]
+    fn failed_rename_leaves_file_in_place() {
+        let mut store = MemoryStore::new();
+        store.insert("old.txt", "something else\n");
+
+        let input = "\
+diff --git a/old.txt b/new.txt
+similarity index 50%
+rename from old.txt
+rename to new.txt
+--- a/old.txt
++++ b/new.txt
+@@ -1 +1 @@
+-one
++ONE
+";
+        let err = PatchSetApplyOptions::new()
+            .apply(
+                &mut store,
+                PatchSet::parse_bytes(input.as_bytes(), ParseOptions::gitdiff()),
+            )
+            .unwrap_err();
+
+        assert_eq!(err.path(), Some(&b"new.txt"[..]));
+        assert!(store.get("old.txt").is_some());
+        assert!(store.get("new.txt").is_none());
+    }
+
+    const MULTI_FILE: &str = "\
+diff --git a/a.txt b/a.txt
+--- a/a.txt
++++ b/a.txt
+@@ -1 +1 @@
+-a
++A
+diff --git a/b.txt b/c.txt
+similarity index 100%
+rename from b.txt
+rename to c.txt
+diff --git a/d.txt b/d.txt
+deleted file mode 100644
+--- a/d.txt
++++ /dev/null
+@@ -1 +0,0 @@
+-d
+diff --git a/e.txt b/e.txt
+new file mode 100755
+--- /dev/null
++++ b/e.txt
+@@ -0,0 +1 @@
++e
+";
+
+    fn multi_file_store() -> MemoryStore {
+        let mut store = MemoryStore::new();
+        store.insert("a.txt", "a\n");
+        store.insert("b.txt", "b\n");
+        store.insert("d.txt", "d\n");
+        store
+    }
+
+    fn apply_atomic<S: FileStore>(
+        store: &mut S,
+        input: &str,
+    ) -> Result<(), super::super::PatchSetApplyErrors<S::Error>> {
+        PatchSetApplyOptions::new().apply_atomic(
+            store,
+            PatchSet::parse_bytes(input.as_bytes(), ParseOptions::gitdiff()),
+        )
+    }
+
+    #[test]
+    fn atomic_applies_all_patches() {
+        let mut store = multi_file_store();
+        apply_atomic(&mut store, MULTI_FILE).unwrap();
+
+        let mut expected = MemoryStore::new();
+        expected.insert("a.txt", "A\n");
+        expected.insert("c.txt", "b\n");
+        expected.insert("e.txt", "e\n");
+        expected.set_mode(b"e.txt", FileMode::Executable).unwrap();
+        assert_eq!(store, expected);
+    }
+
+    #[test]
+    fn atomic_sees_earlier_patches() {
+        let mut store = MemoryStore::new();
+        store.insert("a.txt", "a\n");
+
+        let input = "\
+diff --git a/a.txt b/b.txt
+similarity index 100%
+rename from a.txt
+rename to b.txt
+diff --git a/b.txt b/b.txt
+--- a/b.txt
++++ b/b.txt
+@@ -1 +1 @@
+-a
++B
+diff --git a/a.txt b/a.txt
+--- a/a.txt
++++ b/a.txt
+@@ -1 +1 @@
+-a
++A
+";
+        let errors = apply_atomic(&mut store, input).unwrap_err();
+        assert_eq!(errors.errors().len(), 1);
+        assert_eq!(
+            errors.to_string(),
+            "error applying patch to a.txt: no such file"
+        );
+
+        apply_atomic(&mut store, &input[..input.rfind("diff --git").unwrap()]).unwrap();
+        assert!(store.get("a.txt").is_none());
+        assert_eq!(store.get("b.txt").unwrap().contents(), b"B\n");
+    }
+
+    #[test]
+    fn atomic_reports_all_failures() {
+        let mut store = multi_file_store();
+        store.insert("a.txt", "not a\n");
+        store.remove(b"b.txt").unwrap();
+        let original = store.clone();
+
+        let errors = apply_atomic(&mut store, MULTI_FILE).unwrap_err();
+        let paths: Vec<_> = errors.errors().iter().map(|e| e.path().unwrap()).collect();
+        assert_eq!(paths, [&b"a.txt"[..], b"c.txt"]);
+        assert_eq!(
+            errors.to_string(),
+            "\
+error applying patch to a.txt: error applying hunk #1
+error applying patch to c.txt: no such file: b.txt"
+        );
+        assert_eq!(store, original);
+    }
+
+    #[test]
+    fn atomic_validates_created_and_deleted_files() {
+        let mut store = multi_file_store();
+        store.insert("d.txt", "not d\n");
+        store.insert("e.txt", "existing e\n");
+        let original = store.clone();
+
+        let errors = apply_atomic(&mut store, MULTI_FILE).unwrap_err();
+        assert_eq!(
+            errors.to_string(),
+            "\
+error applying patch to d.txt: error applying hunk #1
+error applying patch to e.txt: file already exists"
+        );
+        assert_eq!(store, original);
+    }
+
+    /// A store which fails to write to a single path
+    struct FailingStore {
+        store: MemoryStore,
+        fail: &'static [u8],
+    }
+
+    impl FileStore for FailingStore {
+        type Error = MemoryStoreError;
+
+        fn read(&self, path: &[u8]) -> Result<Vec<u8>, Self::Error> {
+            self.store.read(path)
+        }
+
+        fn write(&mut self, path: &[u8], contents: Vec<u8>) -> Result<(), Self::Error> {
+            if path == self.fail {
+                return Err(MemoryStore::new().read(path).unwrap_err());
+            }
+            self.store.write(path, contents)
+        }
+
+        fn remove(&mut self, path: &[u8]) -> Result<(), Self::Error> {
+            self.store.remove(path)
+        }
+
+        fn rename(&mut self, from: &[u8], to: &[u8]) -> Result<(), Self::Error> {
+            self.store.rename(from, to)
+        }
+
+        fn mode(&self, path: &[u8]) -> Result<FileMode, Self::Error> {
+            self.store.mode(path)
+        }
+
+        fn set_mode(&mut self, path: &[u8], mode: FileMode) -> Result<(), Self::Error> {
+            self.store.set_mode(path, mode)
+        }
+    }
+
+    #[test]
+    fn atomic_rolls_back_on_store_failure() {
+        let mut store = FailingStore {
+            store: multi_file_store(),
+            fail: b"e.txt",
+        };
+        store
+            .store
+            .set_mode(b"d.txt", FileMode::Executable)
+            .unwrap();
+        let original = store.store.clone();
+
+        let errors = apply_atomic(&mut store, MULTI_FILE).unwrap_err();
+        assert_eq!(errors.errors().len(), 1);
+        assert_eq!(errors.errors()[0].store_error().unwrap().path(), b"e.txt");
+        assert_eq!(store.store, original);
+
+        // Restoring the existing file which failed to be written fails too
+        store.fail = b"a.txt";
+        let errors = apply_atomic(&mut store, MULTI_FILE).unwrap_err();
+        let paths: Vec<_> = errors
+            .errors()
+            .iter()
+            .map(|err| err.store_error().unwrap().path())
+            .collect();
+        assert_eq!(paths, [b"a.txt", b"a.txt"]);
+        assert_eq!(store.store, original);
+    }
+
+    #[test]
+    fn check_does_not_modify_store() {
+        let mut store = multi_file_store();
+        let original = store.clone();
+
+        let check = |store: &MemoryStore| {
+            PatchSetApplyOptions::new().check(
+                store,
+                PatchSet::parse_bytes(MULTI_FILE.as_bytes(), ParseOptions::gitdiff()),
+            )
+        };
+
+        check(&store).unwrap();
+        assert_eq!(store, original);
+
+        store.insert("a.txt", "not a\n");
+        store.insert("e.txt", "e\n");
+        let errors = check(&store).unwrap_err();
+        assert!(errors.errors()[1].apply_error().is_none());
+        assert_eq!(
+            errors.to_string(),
+            "\
+error applying patch to a.txt: error applying hunk #1
+error applying patch to e.txt: file already exists"
+        );
+        assert_eq!(store.get("e.txt").unwrap().contents(), b"e\n");
+
+        // Hunks which are already applied are reported as such
+        let mut store = multi_file_store();
+        store.insert("a.txt", "A\n");
+        let errors = check(&store).unwrap_err();
+        let err = errors.errors()[0].apply_error().unwrap();
+        assert_eq!(err.hunk(), 1);
+        assert!(err.is_reversed());
+    }
+
+    #[test]
+    fn check_sees_changes_of_earlier_patches() {
+        let input = "\
+diff --git a/a.txt b/a.txt
+--- a/a.txt
++++ b/a.txt
+@@ -1 +1 @@
+-a
++A
+diff --git a/a.txt b/a.txt
+--- a/a.txt
++++ b/a.txt
+@@ -1 +1 @@
+-A
++AA
+diff --git a/b.txt b/c.txt
+similarity index 100%
+rename from b.txt
+rename to c.txt
+diff --git a/c.txt b/c.txt
+--- a/c.txt
++++ b/c.txt
+@@ -1 +1 @@
+-b
++B
+diff --git a/x.txt b/y.txt
+similarity index 100%
+rename from x.txt
+rename to y.txt
+";
+        let check = |store: &MemoryStore| {
+            PatchSetApplyOptions::new().check(
+                store,
+                PatchSet::parse_bytes(input.as_bytes(), ParseOptions::gitdiff()),
+            )
+        };
+
+        let mut store = multi_file_store();
+        store.insert("x.txt", "x\n");
+        check(&store).unwrap();
+
+        // Only the second patch to a.txt applies, and the renamed file doesn't exist
+        store.insert("a.txt", "A\n");
+        store.remove(b"x.txt").unwrap();
+        let errors = check(&store).unwrap_err();
+        let paths: Vec<_> = errors.errors().iter().map(|err| err.path()).collect();
+        assert_eq!(paths, [Some(&b"a.txt"[..]), Some(&b"y.txt"[..])]);
+        assert!(errors.errors()[0].apply_error().unwrap().is_reversed());
+        assert!(errors.errors()[1].store_error().is_some());
+    }
+}
+
+mod interdiff {
+    use super::super::FilePatch;
+    use super::super::MemoryStore;
+    use super::super::PatchSetApplyOptions;
+    use super::super::PatchSetInterdiffOptions;
+    use super::*;
+
+    fn parse(input: &str) -> Vec<FilePatch<'_, [u8]>> {
+        PatchSet::parse_bytes(input.as_bytes(), ParseOptions::gitdiff())
+            .collect::<Result<_, _>>()
+            .unwrap()
+    }
+
+    fn apply(store: &mut MemoryStore, patches: &[FilePatch<'_, [u8]>], strip: usize) {
+        let mut options = PatchSetApplyOptions::new();
+        options.set_strip(strip);
+        for patch in patches {
+            options.apply_file_patch(store, patch).unwrap();
+        }
+    }
+
+    // Computes the interdiff and checks that applying it to the result of `old` gives the
+    // result of `new`
+    fn interdiff<'a>(base: &MemoryStore, old: &'a str, new: &'a str) -> Vec<FilePatch<'a, [u8]>> {
+        let (old, new) = (parse(old), parse(new));
+        let interdiff = PatchSetInterdiffOptions::new()
+            .interdiff(&old, &new)
+            .unwrap();
+
+        let mut expected = base.clone();
+        apply(&mut expected, &new, 1);
+        let mut actual = base.clone();
+        apply(&mut actual, &old, 1);
+        apply(&mut actual, &interdiff, 0);
+        assert_eq!(actual, expected);
+
+        interdiff
+    }
+
+    #[test]
+    fn series_with_several_patches_to_a_file() {
+        let mut base = MemoryStore::new();
+        base.insert("file.txt", "a\nb\nc\nd\ne\nf\ng\nh\ni\nj\n");
+
+        let old = "\
+diff --git a/file.txt b/file.txt
+--- a/file.txt
++++ b/file.txt
+@@ -1,4 +1,4 @@
+-a
++A
+ b
+ c
+ d
+diff --git a/file.txt b/file.txt
+--- a/file.txt
++++ b/file.txt
+@@ -7,4 +7,4 @@
+ g
+ h
+ i
+-j
++J
+";
+        let new = "\
+diff --git a/file.txt b/file.txt
+--- a/file.txt
++++ b/file.txt
+@@ -1,4 +1,4 @@
+-a
++A
+ b
+ c
+ d
+diff --git a/file.txt b/file.txt
+--- a/file.txt
++++ b/file.txt
+@@ -7,4 +7,4 @@
+ g
+ h
+ i
+-j
++jj
+";
+
+        let interdiff = interdiff(&base, old, new);
+        assert_eq!(interdiff.len(), 1);
+        assert_eq!(
+            interdiff[0].patch().as_text().unwrap().to_bytes(),
+            b"\
+--- b/file.txt
++++ b/file.txt
+@@ -7,4 +7,4 @@
+ g
+ h
+ i
+-J
++jj
+"
+        );
+    }
+
+    #[test]
+    fn identical_series() {
+        let patches = parse(
+            "\
+diff --git a/file.txt b/file.txt
+--- a/file.txt
++++ b/file.txt
+@@ -1 +1 @@
+-a
++b
+",
+        );
+        let interdiff = PatchSetInterdiffOptions::new()
+            .interdiff(&patches, &patches)
+            .unwrap();
+        assert!(interdiff.is_empty());
+    }
+
+    #[test]
+    fn created_deleted_and_renamed_files() {
+        let mut base = MemoryStore::new();
+        base.insert("deleted.txt", "gone\n");
+        base.insert("renamed.txt", "one\ntwo\n");
+
+        let old = "\
+diff --git a/deleted.txt b/deleted.txt
+deleted file mode 100644
+--- a/deleted.txt
++++ /dev/null
+@@ -1 +0,0 @@
+-gone
+diff --git a/renamed.txt b/moved.txt
+similarity index 50%
+rename from renamed.txt
+rename to moved.txt
+--- a/renamed.txt
++++ b/moved.txt
+@@ -1,2 +1,2 @@
+ one
+-two
++TWO
+";
+        let new = "\
+diff --git a/created.txt b/created.txt
+new file mode 100644
+--- /dev/null
++++ b/created.txt
+@@ -0,0 +1 @@
++new
+diff --git a/renamed.txt b/renamed.txt
+--- a/renamed.txt
++++ b/renamed.txt
+@@ -1,2 +1,2 @@
+-one
++ONE
+ two
+";
+
+        let interdiff = interdiff(&base, old, new);
+        let operations: Vec<_> = interdiff.iter().map(|p| p.operation().clone()).collect();
+        assert_eq!(
+            operations,
+            [
+                FileOperation::Create(b"deleted.txt"[..].into()),
+                FileOperation::Rename {
+                    from: b"moved.txt"[..].into(),
+                    to: b"renamed.txt"[..].into(),
+                },
+                FileOperation::Create(b"created.txt"[..].into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn unsupported_patches() {
+        let binary = parse(
+            "\
+diff --git a/image.png b/image.png
+Binary files a/image.png and b/image.png differ
+",
+        );
+        let err = PatchSetInterdiffOptions::new()
+            .interdiff(&binary, &[])
+            .unwrap_err();
+        assert_eq!(err.path(), b"image.png");
+        assert_eq!(
+            err.to_string(),
+            "error comparing patches to image.png: binary patches are not supported"
+        );
+
+        let old = parse(
+            "\
+diff --git a/file.txt b/file.txt
+--- a/file.txt
++++ b/file.txt
+@@ -1 +1 @@
+-a
++b
+",
+        );
+        let new = parse(
+            "\
+diff --git a/file.txt b/file.txt
+--- a/file.txt
++++ b/file.txt
+@@ -1 +1 @@
+-c
++b
+",
+        );
+        let err = PatchSetInterdiffOptions::new()
+            .interdiff(&old, &new)
+            .unwrap_err();
+        assert_eq!(err.compose_error().unwrap().line(), 1);
+    }
+}
+
+mod range_diff {
+    use super::super::Commit;
+    use super::super::RangeDiffOptions;
+    use super::*;
+    use alloc::string::String;
+
+    // Renders a `git format-patch` email changing a line of `file`
+    fn email(id: char, subject: &str, file: &str, old: &str, new: &str) -> String {
+        let id: String = core::iter::repeat_n(id, 40).collect();
+        alloc::format!(
+            "\
+From {id} Mon Sep 17 00:00:00 2001
+From: A U Thor <author@example.com>
+Subject: [PATCH] {subject}
+
+The {subject} commit.
+---
+diff --git a/{file} b/{file}
+--- a/{file}
++++ b/{file}
+@@ -1,3 +1,3 @@
+ first
+-{old}
++{new}
+ last
+--
+2.40.0
+
+"
+        )
+    }
+
+    #[test]
+    fn matches_reordered_changed_added_and_removed_commits() {
+        let old = [
+            email('1', "one", "a.txt", "a", "A"),
+            email('2', "two", "b.txt", "b", "B"),
+            email('3', "three", "c.txt", "c", "C"),
+        ]
+        .concat();
+        let new = [
+            email('4', "two", "b.txt", "b", "B"),
+            email('5', "one", "a.txt", "a", "AA"),
+            email('6', "four", "d.txt", "d", "D"),
+        ]
+        .concat();
+
+        let old = Commit::parse_mbox(&old).unwrap();
+        let new = Commit::parse_mbox(&new).unwrap();
+        // The commits are so small that the default would match up "three" and "four"
+        let range_diff = RangeDiffOptions::new()
+            .set_creation_factor(30)
+            .range_diff(&old, &new);
+
+        let pairs: Vec<_> = range_diff
+            .entries()
+            .iter()
+            .map(|entry| (entry.old_index(), entry.new_index()))
+            .collect();
+        assert_eq!(
+            pairs,
+            [
+                (Some(1), Some(0)),
+                (Some(0), Some(1)),
+                (Some(2), None),
+                (None, Some(2)),
+            ]
+        );
+        assert_eq!(range_diff.entries()[0].diff(), None);
+
+        assert_eq!(
+            range_diff.to_string(),
+            "\
+2:  2222222 = 1:  4444444 two
+1:  1111111 ! 2:  5555555 one
+    @@ -5,5 +5,5 @@
+     @@
+      first
+     -a
+    -+A
+    ++AA
+      last
+3:  3333333 < -:  ------- three
+-:  ------- > 3:  6666666 four
+"
+        );
+    }
+
+    #[test]
+    fn creation_factor() {
+        let old = email('1', "one", "a.txt", "a", "A");
+        let new = email('2', "uno", "a.txt", "a", "B");
+        let old = Commit::parse_mbox(&old).unwrap();
+        let new = Commit::parse_mbox(&new).unwrap();
+
+        // Matching the commits costs more than removing one and adding the other
+        let range_diff = RangeDiffOptions::new()
+            .set_creation_factor(10)
+            .range_diff(&old, &new);
+        assert_eq!(range_diff.entries().len(), 2);
+
+        let range_diff = RangeDiffOptions::new()
+            .set_creation_factor(100)
+            .range_diff(&old, &new);
+        assert_eq!(range_diff.entries().len(), 1);
+        assert!(range_diff.entries()[0].diff().is_some());
+    }
+
+    #[test]
+    fn parse_mbox() {
+        let mbox = [
+            "\
+From 0000000000000000000000000000000000000000 Mon Sep 17 00:00:00 2001
+From: A U Thor <author@example.com>
+Subject: [PATCH 0/1] A cover letter
+ with a long subject
+
+From the author: this isn't the start of an email.
+
+",
+            &email('1', "one", "a.txt", "a", "A"),
+        ]
+        .concat();
+
+        let commits = Commit::parse_mbox(&mbox).unwrap();
+        assert_eq!(commits.len(), 2);
+        assert_eq!(commits[0].subject(), "A cover letter with a long subject");
+        assert_eq!(
+            commits[0].message(),
+            "From the author: this isn't the start of an email."
+        );
+        assert!(commits[0].patches().is_empty());
+        assert_eq!(commits[1].subject(), "one");
+        assert_eq!(commits[1].message(), "The one commit.");
+        assert_eq!(commits[1].patches().len(), 1);
+    }
+
+    #[test]
+    fn parse_error_offset() {
+        let first = email('1', "one", "a.txt", "a", "A");
+        let mbox = [
+            first.as_str(),
+            "\
+From 2222222222222222222222222222222222222222 Mon Sep 17 00:00:00 2001
+Subject: [PATCH] broken
+
+---
+diff --git a/b.txt b/b.txt
+old mode 100644
+new mode 999999
+",
+        ]
+        .concat();
+
+        let err = Commit::parse_mbox(&mbox).unwrap_err();
+        let message = err.to_string();
+        let offset: usize = message
+            .strip_prefix("error parsing patches at byte ")
+            .and_then(|rest| rest.split(':').next())
+            .and_then(|offset| offset.parse().ok())
+            .unwrap();
+        assert!(offset > first.len(), "{message}");
+    }
+}
@@ -72,16 +72,42 @@ pub(crate) fn fmt_escaped_byte(f: &mut impl core::fmt::Write, b: u8) -> core::fm
 /// Classifies lines, converting lines into unique `u64`s for quicker comparison
 pub struct Classifier<'a, T: ?Sized> {
     next_id: u64,
-    unique_ids: HashMap<&'a T, u64>,
+    ignore_cr_at_eol: bool,
+    whitespace: Whitespace,
+    unique_ids: HashMap<(&'a T, bool), u64>,
+    normalized_ids: HashMap<(Vec<u8>, bool), u64>,
 }
 
 impl<'a, T: ?Sized + Eq + Hash> Classifier<'a, T> {
-    fn classify(&mut self, record: &'a T) -> u64 {
-        match self.unique_ids.entry(record) {
+    /// Constructs a new `Classifier`, optionally treating lines ending in `\r\n` and `\n` as
+    /// equal
+    pub fn new(ignore_cr_at_eol: bool) -> Self {
+        Self {
+            next_id: 0,
+            ignore_cr_at_eol,
+            whitespace: Whitespace::Exact,
+            unique_ids: HashMap::default(),
+            normalized_ids: HashMap::default(),
+        }
+    }
+
+    /// Sets how whitespace is compared, which takes precedence over `ignore_cr_at_eol` unless
+    /// whitespace must match exactly
+    pub fn set_whitespace(&mut self, whitespace: Whitespace) -> &mut Self {
+        self.whitespace = whitespace;
+        self
+    }
+
+    fn classify<K: Eq + Hash>(
+        next_id: &mut u64,
+        unique_ids: &mut HashMap<K, u64>,
+        record: K,
+    ) -> u64 {
+        match unique_ids.entry(record) {
             Entry::Occupied(o) => *o.get(),
             Entry::Vacant(v) => {
-                let id = self.next_id;
-                self.next_id += 1;
+                let id = *next_id;
+                *next_id += 1;
                 *v.insert(id)
             }
         }
@@ -91,16 +117,137 @@ impl<'a, T: ?Sized + Eq + Hash> Classifier<'a, T> {
 impl<'a, T: ?Sized + Text> Classifier<'a, T> {
     pub fn classify_lines(&mut self, text: &'a T) -> (Vec<&'a T>, Vec<u64>) {
         LineIter::new(text)
-            .map(|line| (line, self.classify(line)))
+            .map(|line| {
+                let id = if self.whitespace != Whitespace::Exact {
+                    let record = self.whitespace.normalize(line.as_bytes());
+                    Self::classify(&mut self.next_id, &mut self.normalized_ids, record)
+                } else if self.ignore_cr_at_eol {
+                    let record = split_line_ending(line);
+                    Self::classify(&mut self.next_id, &mut self.unique_ids, record)
+                } else {
+                    Self::classify(&mut self.next_id, &mut self.unique_ids, (line, false))
+                };
+                (line, id)
+            })
             .unzip()
     }
 }
 
 impl<T: Eq + Hash + ?Sized> Default for Classifier<'_, T> {
     fn default() -> Self {
-        Self {
-            next_id: 0,
-            unique_ids: HashMap::default(),
+        Self::new(false)
+    }
+}
+
+/// How whitespace is compared when classifying lines
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Whitespace {
+    /// Whitespace must match exactly
+    Exact,
+    /// Whitespace at the end of lines is ignored, and all other runs of whitespace are equal,
+    /// like git's `--ignore-space-change`
+    IgnoreChange,
+    /// All whitespace is ignored, like git's `--ignore-all-space`
+    IgnoreAll,
+}
+
+impl Whitespace {
+    pub(crate) fn new(ignore_space_change: bool, ignore_all_space: bool) -> Self {
+        if ignore_all_space {
+            Whitespace::IgnoreAll
+        } else if ignore_space_change {
+            Whitespace::IgnoreChange
+        } else {
+            Whitespace::Exact
+        }
+    }
+
+    /// Returns the parts of `line` which are compared, and whether it ends in a newline
+    fn normalize(self, line: &[u8]) -> (Vec<u8>, bool) {
+        let (content, terminated) = match line.strip_suffix(b"\n") {
+            Some(content) => (content, true),
+            None => (line, false),
+        };
+
+        let mut normalized = Vec::with_capacity(content.len());
+        match self {
+            Whitespace::Exact => normalized.extend_from_slice(content),
+            Whitespace::IgnoreChange => {
+                // Runs of whitespace are replaced by a single space before the next
+                // non-whitespace byte, so whitespace at the end of the line is dropped
+                let mut in_space = false;
+                for &b in content {
+                    if b.is_ascii_whitespace() {
+                        in_space = true;
+                    } else {
+                        if in_space {
+                            normalized.push(b' ');
+                            in_space = false;
+                        }
+                        normalized.push(b);
+                    }
+                }
+            }
+            Whitespace::IgnoreAll => {
+                normalized.extend(content.iter().copied().filter(|b| !b.is_ascii_whitespace()))
+            }
+        }
+        (normalized, terminated)
+    }
+}
+
+/// Splits a line into its content and whether it was terminated by a newline, treating `\r\n`
+/// and `\n` alike
+pub(crate) fn split_line_ending<T: Text + ?Sized>(line: &T) -> (&T, bool) {
+    if let Some(content) = line.strip_suffix("\r\n") {
+        (content, true)
+    } else if let Some(content) = line.strip_suffix("\n") {
+        (content, true)
+    } else {
+        (line, false)
+    }
+}
+
+/// The style of line endings used by a text
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum LineEnding {
+    /// `\n`
+    Lf,
+    /// `\r\n`
+    CrLf,
+}
+
+impl LineEnding {
+    /// Detects the line ending style of a text based on its first line
+    pub(crate) fn detect<T: Text + ?Sized>(text: &T) -> Self {
+        match text.find("\n") {
+            Some(idx) if idx > 0 && text.as_bytes()[idx - 1] == b'\r' => LineEnding::CrLf,
+            _ => LineEnding::Lf,
+        }
+    }
+
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            LineEnding::Lf => "\n",
+            LineEnding::CrLf => "\r\n",
+        }
+    }
+
+    /// Appends `line` to `output`, replacing its line ending (if any) with this one
+    pub(crate) fn push_line(self, output: &mut String, line: &str) {
+        let (content, terminated) = split_line_ending(line);
+        output.push_str(content);
+        if terminated {
+            output.push_str(self.as_str());
+        }
+    }
+
+    /// Appends `line` to `output`, replacing its line ending (if any) with this one
+    pub(crate) fn push_line_bytes(self, output: &mut Vec<u8>, line: &[u8]) {
+        let (content, terminated) = split_line_ending(line);
+        output.extend_from_slice(content);
+        if terminated {
+            output.extend_from_slice(self.as_str().as_bytes());
         }
     }
 }
@@ -114,6 +261,14 @@ impl<'a, T: ?Sized> LineIter<'a, T> {
     }
 }
 
+impl<T: ?Sized> Clone for LineIter<'_, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T: ?Sized> Copy for LineIter<'_, T> {}
+
 impl<'a, T: Text + ?Sized> Iterator for LineIter<'a, T> {
     type Item = &'a T;
 